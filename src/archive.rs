@@ -0,0 +1,287 @@
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+
+use crate::error::LoaderError;
+
+//=-- Archive format of a package's volumes. 7z is extracted with NanaZip, the others in-process.
+//=-- Split archives are plain byte splits of one archive, so their volumes are read back to back.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ArchiveFormat {
+    #[default]
+    Auto, //=-- Detected from the first volume's magic bytes
+    #[serde(rename = "7z")]
+    SevenZip,
+    Zip,
+    #[serde(rename = "tar.gz", alias = "tgz")]
+    TarGz,
+    #[serde(rename = "tar.xz", alias = "txz")]
+    TarXz,
+    #[serde(rename = "tar.zst", alias = "tzst")]
+    TarZst,
+}
+
+impl fmt::Display for ArchiveFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ArchiveFormat::Auto => "auto",
+            ArchiveFormat::SevenZip => "7z",
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::TarGz => "tar.gz",
+            ArchiveFormat::TarXz => "tar.xz",
+            ArchiveFormat::TarZst => "tar.zst",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+const MAGIC_BYTES: &[(&[u8], ArchiveFormat)] = &[
+    (&[0x37, 0x7A, 0xBC, 0xAF, 0x27, 0x1C], ArchiveFormat::SevenZip),
+    (b"PK\x03\x04", ArchiveFormat::Zip),
+    (b"PK\x05\x06", ArchiveFormat::Zip), //=-- Empty zip
+    (&[0x1F, 0x8B], ArchiveFormat::TarGz),
+    (&[0xFD, 0x37, 0x7A, 0x58, 0x5A, 0x00], ArchiveFormat::TarXz),
+    (&[0x28, 0xB5, 0x2F, 0xFD], ArchiveFormat::TarZst),
+    (b"Rar!\x1a\x07", ArchiveFormat::SevenZip), //=-- RAR 4 and 5 (name.part1.rar volumes), which NanaZip extracts
+];
+
+//=-- The configured format, or the one detected from the start of the first volume
+pub fn resolve_format(configured: ArchiveFormat, first_volume: &Path) -> Result<ArchiveFormat, Box<dyn std::error::Error>> {
+    if configured != ArchiveFormat::Auto {
+        return Ok(configured);
+    }
+
+    let mut header = Vec::with_capacity(8);
+    fs::File::open(first_volume)?.take(8).read_to_end(&mut header)?;
+    MAGIC_BYTES.iter()
+        .find(|(magic, _)| header.starts_with(magic))
+        .map(|(_, format)| *format)
+        .ok_or_else(|| LoaderError::Extraction(format!(
+            "Cannot detect the archive format of {}, set archive_format in the package config",
+            first_volume.display()
+        )).into())
+}
+
+//=-- Whether extracting needs a password. 7z archives can't be checked without NanaZip, so they always ask.
+pub fn needs_password(format: ArchiveFormat, volumes: &[PathBuf]) -> Result<bool, Box<dyn std::error::Error>> {
+    match format {
+        ArchiveFormat::Auto | ArchiveFormat::SevenZip => Ok(true),
+        ArchiveFormat::Zip => {
+            let (mut archive, joined) = open_zip(volumes)?;
+            let mut encrypted = false;
+            for i in 0..archive.len() {
+                if archive.by_index_raw(i)?.encrypted() {
+                    encrypted = true;
+                    break;
+                }
+            }
+            drop(archive);
+            remove_joined(joined);
+            Ok(encrypted)
+        },
+        ArchiveFormat::TarGz | ArchiveFormat::TarXz | ArchiveFormat::TarZst => Ok(false),
+    }
+}
+
+//=-- Reads every volume of a split archive back to back
+fn open_volumes(volumes: &[PathBuf]) -> io::Result<Box<dyn Read>> {
+    let mut reader: Box<dyn Read> = Box::new(io::empty());
+    for volume in volumes {
+        reader = Box::new(reader.chain(fs::File::open(volume)?));
+    }
+    Ok(Box::new(io::BufReader::new(reader)))
+}
+
+//=-- Zip needs to seek to its central directory, so split volumes are joined into one file next to them first
+fn open_zip(volumes: &[PathBuf]) -> Result<(zip::ZipArchive<fs::File>, Option<PathBuf>), Box<dyn std::error::Error>> {
+    let first = volumes.first().ok_or_else(|| LoaderError::Extraction("Archive has no volumes".to_string()))?;
+    if volumes.len() == 1 {
+        return Ok((zip::ZipArchive::new(fs::File::open(first)?)?, None));
+    }
+
+    let joined = first.with_extension("joined");
+    io::copy(&mut open_volumes(volumes)?, &mut fs::File::create(&joined)?)?;
+    match fs::File::open(&joined).map_err(zip::result::ZipError::from).and_then(zip::ZipArchive::new) {
+        Ok(archive) => Ok((archive, Some(joined))),
+        Err(e) => {
+            remove_joined(Some(joined));
+            Err(e.into())
+        }
+    }
+}
+
+fn remove_joined(joined: Option<PathBuf>) {
+    if let Some(path) = joined {
+        let _ = fs::remove_file(path);
+    }
+}
+
+fn extract_zip(volumes: &[PathBuf], extract_dir: &Path, password: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let (mut archive, joined) = open_zip(volumes)?;
+    let result = (|| -> Result<(), Box<dyn std::error::Error>> {
+        for i in 0..archive.len() {
+            let encrypted = archive.by_index_raw(i)?.encrypted();
+            if encrypted && password.is_empty() {
+                return Err(LoaderError::WrongPassword("Wrong password".to_string()).into());
+            }
+            let mut entry = if encrypted {
+                archive.by_index_decrypt(i, password.as_bytes()).map_err(|e| match e {
+                    zip::result::ZipError::InvalidPassword => LoaderError::WrongPassword("Wrong password".to_string()).into(),
+                    e => Box::new(e) as Box<dyn std::error::Error>,
+                })?
+            } else {
+                archive.by_index(i)?
+            };
+
+            //=-- Entries that would land outside the extraction folder are skipped
+            let Some(relative) = entry.enclosed_name() else {
+                warn!("Skipping unsafe archive entry: {}", entry.name());
+                continue;
+            };
+            let target = extract_dir.join(relative);
+            if entry.is_dir() {
+                fs::create_dir_all(&target)?;
+                continue;
+            }
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut file = fs::File::create(&target)?;
+            //=-- A wrong ZipCrypto password can pass the header check and only fail on the checksum here
+            io::copy(&mut entry, &mut file).map_err(|e| -> Box<dyn std::error::Error> {
+                if encrypted { LoaderError::WrongPassword(format!("Wrong password? {}", e)).into() } else { e.into() }
+            })?;
+        }
+        Ok(())
+    })();
+    drop(archive);
+    remove_joined(joined);
+    result
+}
+
+fn extract_tar(format: ArchiveFormat, volumes: &[PathBuf], extract_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let reader = open_volumes(volumes)?;
+    let decoder: Box<dyn Read> = match format {
+        ArchiveFormat::TarGz => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+        ArchiveFormat::TarXz => Box::new(xz2::read::XzDecoder::new_multi_decoder(reader)),
+        ArchiveFormat::TarZst => Box::new(zstd::stream::read::Decoder::new(reader)?),
+        other => return Err(LoaderError::Extraction(format!("{} is not a tar format", other)).into()),
+    };
+    //=-- unpack refuses entries with absolute paths or ".." components
+    tar::Archive::new(decoder).unpack(extract_dir).map_err(|e| -> Box<dyn std::error::Error> {
+        //=-- Data the decoder can't make sense of is a broken archive, not a problem with the disk
+        match e.kind() {
+            io::ErrorKind::InvalidData | io::ErrorKind::InvalidInput | io::ErrorKind::UnexpectedEof => {
+                LoaderError::Extraction(e.to_string()).into()
+            },
+            _ => e.into(),
+        }
+    })?;
+    Ok(())
+}
+
+//=-- Extracts one archive (all of its volumes, in order) into `extract_dir`. 7z goes through NanaZip instead.
+pub fn extract(format: ArchiveFormat, volumes: &[PathBuf], extract_dir: &Path, password: &str) -> Result<(), Box<dyn std::error::Error>> {
+    match format {
+        ArchiveFormat::Zip => extract_zip(volumes, extract_dir, password),
+        ArchiveFormat::TarGz | ArchiveFormat::TarXz | ArchiveFormat::TarZst => extract_tar(format, volumes, extract_dir),
+        ArchiveFormat::Auto | ArchiveFormat::SevenZip => Err(LoaderError::Extraction(format!("{} archives are extracted with NanaZip", format)).into()),
+    }
+}
+
+//=-- Every file and folder under `dir`, relative to it and sorted so archives come out the same every time
+fn collect_entries(dir: &Path, prefix: &Path, entries: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut children: Vec<fs::DirEntry> = fs::read_dir(dir)?.collect::<Result<_, _>>()?;
+    children.sort_by_key(|entry| entry.file_name());
+    for child in children {
+        let relative = prefix.join(child.file_name());
+        entries.push(relative.clone());
+        if child.file_type()?.is_dir() {
+            collect_entries(&child.path(), &relative, entries)?;
+        }
+    }
+    Ok(())
+}
+
+fn create_zip(source_dir: &Path, target: &Path, password: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut entries = Vec::new();
+    collect_entries(source_dir, Path::new(""), &mut entries)?;
+
+    let mut writer = zip::ZipWriter::new(fs::File::create(target)?);
+    for relative in entries {
+        let path = source_dir.join(&relative);
+        let name = relative.to_string_lossy().replace('\\', "/");
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated)
+            .large_file(path.is_file() && fs::metadata(&path)?.len() >= u32::MAX as u64);
+        let options = if password.is_empty() {
+            options
+        } else {
+            options.with_aes_encryption(zip::AesMode::Aes256, password)
+        };
+
+        if path.is_dir() {
+            writer.add_directory(name, options)?;
+        } else {
+            writer.start_file(name, options)?;
+            io::copy(&mut fs::File::open(&path)?, &mut writer)?;
+        }
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+fn write_tar<W: io::Write>(writer: W, source_dir: &Path) -> io::Result<W> {
+    let mut builder = tar::Builder::new(writer);
+    builder.follow_symlinks(false);
+    builder.append_dir_all(".", source_dir)?;
+    builder.into_inner()
+}
+
+fn create_tar(format: ArchiveFormat, source_dir: &Path, target: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let file = io::BufWriter::new(fs::File::create(target)?);
+    //=-- Finishing writes the end of the compressed stream; its errors (and the final flush's) mean a truncated archive
+    let file = match format {
+        ArchiveFormat::TarGz => write_tar(flate2::write::GzEncoder::new(file, flate2::Compression::best()), source_dir)?.finish()?,
+        ArchiveFormat::TarXz => write_tar(xz2::write::XzEncoder::new(file, 9), source_dir)?.finish()?,
+        ArchiveFormat::TarZst => write_tar(zstd::stream::write::Encoder::new(file, 19)?, source_dir)?.finish()?,
+        other => return Err(LoaderError::Config(format!("{} is not a tar format", other)).into()),
+    };
+    file.into_inner()?.flush()?;
+    Ok(())
+}
+
+//=-- Packs everything in `source_dir` into one archive file. 7z archives are created with NanaZip instead.
+pub fn create(format: ArchiveFormat, source_dir: &Path, target: &Path, password: &str) -> Result<(), Box<dyn std::error::Error>> {
+    match format {
+        ArchiveFormat::Zip => create_zip(source_dir, target, password),
+        ArchiveFormat::TarGz | ArchiveFormat::TarXz | ArchiveFormat::TarZst => {
+            if !password.is_empty() {
+                return Err(LoaderError::Config(format!("{} archives cannot be encrypted, use zip or 7z", format)).into());
+            }
+            create_tar(format, source_dir, target)
+        },
+        ArchiveFormat::Auto | ArchiveFormat::SevenZip => Err(LoaderError::Config(format!("{} archives are created with NanaZip", format)).into()),
+    }
+}
+
+//=-- Cuts `path` into `<path>.001`, `<path>.002`, ... of at most `volume_size` bytes and removes the original
+pub fn split_file(path: &Path, volume_size: u64) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let total = fs::metadata(path)?.len();
+    let mut source = fs::File::open(path)?;
+    let mut volumes = Vec::new();
+    let mut written = 0;
+    while written < total || volumes.is_empty() {
+        let mut volume_path = path.as_os_str().to_owned();
+        volume_path.push(format!(".{:03}", volumes.len() + 1));
+        let volume_path = PathBuf::from(volume_path);
+        written += io::copy(&mut (&mut source).take(volume_size), &mut fs::File::create(&volume_path)?)?;
+        volumes.push(volume_path);
+    }
+    drop(source);
+    fs::remove_file(path)?;
+    Ok(volumes)
+}
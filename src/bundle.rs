@@ -0,0 +1,314 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{self, ErrorKind, LoaderError};
+use crate::{
+    channel, cleanup_package_dir, confirm_version_change, fetch_release, find_package, install_with_hooks,
+    manifest, mirrors, package_mirrors, parse_filelist, sha256_file, signature, ArchiveFormat, InstallReport, PackageRelease, PublishedList,
+    LoaderContext, Package, Settings, Version, VolumeNaming, VersionRequest, VersionScheme,
+};
+use crate::naming::is_plain_name;
+
+//=-- A bundle is an uncompressed tar (the volumes are already compressed) laid out as:
+//=--   bundle.json            manifest below
+//=--   <id>/version.txt       same content as the package's version_url
+//=--   <id>/filelist.txt      same names as the package's filelist, with hashes
+//=--   <id>/<volume>          every volume, under its repository file name
+//=--   <id>/signed/<list>     the signed filelist.txt or manifest.json as published, and its .sig
+const BUNDLE_FORMAT: u32 = 1;
+const MANIFEST_NAME: &str = "bundle.json";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BundleManifest {
+    format: u32,
+    created: u64,
+    packages: Vec<BundlePackage>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BundlePackage {
+    id: String,
+    name: String,
+    channel: String,
+    version: String,
+    version_scheme: VersionScheme,
+    //=-- The format the package's manifest declared, which overrides archive_format like it does on download
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    format: Option<ArchiveFormat>,
+    files: Vec<BundleFile>,
+    //=-- File name of the signed list under <id>/signed/, for packages with a public_key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signed: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BundleFile {
+    name: String,
+    sha256: String,
+    size: u64,
+}
+
+fn select_packages<'a>(settings: &'a Settings, ids: &[String]) -> Result<Vec<&'a Package>, Box<dyn std::error::Error>> {
+    if ids.is_empty() {
+        Ok(settings.packages.values().collect())
+    } else {
+        ids.iter().map(|id| find_package(settings, id)).collect()
+    }
+}
+
+fn append_text(builder: &mut tar::Builder<fs::File>, path: &str, content: &str) -> std::io::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(content.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0));
+    header.set_cksum();
+    builder.append_data(&mut header, path, content.as_bytes())
+}
+
+//=-- Downloads one package's volumes into the bundle and returns its manifest entry
+fn export_package(ctx: &LoaderContext, package: &Package, staging_dir: &Path, builder: &mut tar::Builder<fs::File>) -> Result<BundlePackage, Box<dyn std::error::Error>> {
+    if !is_plain_name(&package.id) {
+        return Err(LoaderError::Config(format!("Package id \"{}\" cannot be used as a bundle folder name", package.id)).into());
+    }
+    let PackageRelease { version, files: package_files, format, published, .. } = fetch_release(package, &VersionRequest::Latest)?;
+    info!("\n{}: {} [{}]", package.name, version, package.channel);
+
+    let mirrors = package_mirrors(ctx, package, &version, &package_files);
+    let package_dir = staging_dir.join(&package.id);
+    let mut files = Vec::new();
+    for file in package_files {
+        if !is_plain_name(&file.name) {
+            return Err(LoaderError::Integrity(format!("Filelist entry \"{}\" is not a plain file name", file.name)).into());
+        }
+        let path = package_dir.join(&file.name);
+        let (mirror, _) = mirrors::download_from_mirrors(ctx, &mirrors, &file, &path)
+            .map_err(|e| error::context(&format!("Error downloading {}", file.name), e))?;
+
+        let sha256 = sha256_file(&path)?;
+        let size = fs::metadata(&path)?.len();
+        builder.append_path_with_name(&path, format!("{}/{}", package.id, file.name))?;
+        info!("Added {} ({} bytes, from {})", file.name, size, mirror);
+        files.push(BundleFile { name: file.name, sha256, size });
+    }
+
+    let filelist: String = files.iter().map(|file| format!("{} {}\n", file.name, file.sha256)).collect();
+    append_text(builder, &format!("{}/version.txt", package.id), &version.to_string())?;
+    append_text(builder, &format!("{}/filelist.txt", package.id), &filelist)?;
+
+    let mut signed = None;
+    if let Some(PublishedList { file_name, content, signature: Some(signature) }) = published {
+        append_text(builder, &format!("{}/signed/{}", package.id, file_name), &content)?;
+        append_text(builder, &format!("{}/signed/{}.sig", package.id, file_name), &signature)?;
+        signed = Some(file_name.to_string());
+    }
+
+    Ok(BundlePackage {
+        id: package.id.clone(),
+        name: package.name.clone(),
+        channel: package.channel.clone(),
+        version: version.to_string(),
+        version_scheme: package.version_scheme,
+        format,
+        files,
+        signed,
+    })
+}
+
+//=-- `export-bundle <file> [id...]`: packs the selected packages (all when none are given) into one file.
+//=-- Returns the kind of the packages' failures, if any were left out.
+pub fn export_bundle(ctx: &LoaderContext, settings: &Settings, bundle_path: &Path, ids: &[String]) -> Result<Option<ErrorKind>, Box<dyn std::error::Error>> {
+    let packages = select_packages(settings, ids)?;
+    let staging_dir = ctx.dl_dir.join("bundle-export");
+    let tmp_path = PathBuf::from(format!("{}.partial", bundle_path.display()));
+
+    let mut builder = tar::Builder::new(fs::File::create(&tmp_path)?);
+    let mut manifest = BundleManifest {
+        format: BUNDLE_FORMAT,
+        created: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+        packages: Vec::new(),
+    };
+
+    let mut failures = Vec::new();
+    for package in packages {
+        //=-- A package with a bad channel is left out like any other failed package
+        let exported = channel::resolve_package_channel(package, ctx.cli_channel.as_deref(), &ctx.default_channel)
+            .and_then(|package| export_package(ctx, &package, &staging_dir, &mut builder));
+        match exported {
+            Ok(entry) => manifest.packages.push(entry),
+            Err(e) => {
+                error!("{} was not added to the bundle:\n  {}", package.name, e);
+                failures.push(ErrorKind::of(e.as_ref()));
+            },
+        }
+    }
+    let _ = cleanup_package_dir(&staging_dir);
+
+    if manifest.packages.is_empty() {
+        drop(builder);
+        let _ = fs::remove_file(&tmp_path);
+        //=-- Fails with the kind of the packages' failures, so an outage still exits as a network error
+        let kind = ErrorKind::combined(failures).unwrap_or(ErrorKind::Config);
+        return Err(LoaderError::with_kind(kind, "No packages could be exported, bundle not written".to_string()));
+    }
+
+    append_text(&mut builder, MANIFEST_NAME, &serde_json::to_string_pretty(&manifest)?)?;
+    builder.into_inner()?.sync_all()?;
+    fs::rename(&tmp_path, bundle_path)?;
+    info!("Wrote bundle with {} package(s) to {}", manifest.packages.len(), bundle_path.display());
+    Ok(ErrorKind::combined(failures))
+}
+
+//=-- Checks the bundled copy of the signed filelist or manifest against the package's public_key, and that it
+//=-- lists the same version and volumes as bundle.json. Without it bundle.json could claim anything.
+fn verify_signed_list(package: &Package, entry: &BundlePackage, version: &Version, package_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let list_name = entry.signed.as_deref()
+        .filter(|name| *name == "filelist.txt" || *name == "manifest.json")
+        .ok_or_else(|| LoaderError::Integrity(format!("Bundle has no signed filelist or manifest for {}", package.id)))?;
+    let path = package_dir.join("signed").join(list_name);
+    let read = |path: &Path| fs::read_to_string(path)
+        .map_err(|e| LoaderError::Integrity(format!("{} is missing from the bundle: {}", path.display(), e)));
+    let content = read(&path)?;
+    let signature_text = read(&path.with_file_name(format!("{}.sig", list_name)))?;
+    let source = format!("{}/signed/{}", entry.id, list_name);
+    signature::verify_signature(&package.public_key, &source, &content, &signature_text)?;
+
+    let signed_files = match list_name {
+        "manifest.json" => {
+            let release = manifest::read_manifest(package, &source, &content)?;
+            if &release.version != version {
+                return Err(LoaderError::Integrity(format!("Signed manifest is for version {}, not {}", release.version, version)).into());
+            }
+            release.files
+        },
+        _ => parse_filelist(package, &source, &content, version)?,
+    };
+
+    let matches = signed_files.len() == entry.files.len() && entry.files.iter().all(|file| {
+        signed_files.iter().any(|signed| signed.name == file.name && signed.sha256.as_deref() == Some(file.sha256.as_str()))
+    });
+    if !matches {
+        return Err(LoaderError::Integrity(format!("Bundled volumes of {} do not match its signed {}", package.id, list_name)).into());
+    }
+    Ok(())
+}
+
+//=-- Verifies a bundled package against its manifest entry and installs it like a download would
+//=-- Returns the kind of error install_with_hooks reported, if it failed
+fn import_package(ctx: &LoaderContext, package: &Package, entry: &BundlePackage, bundle_dir: &Path) -> Result<Option<ErrorKind>, Box<dyn std::error::Error>> {
+    if entry.version_scheme != package.version_scheme {
+        return Err(LoaderError::Config(format!(
+            "Bundle uses the {} version scheme, but the config uses {}",
+            entry.version_scheme, package.version_scheme
+        )).into());
+    }
+    let version = Version::parse(&entry.version, package.version_scheme)?;
+
+    //=-- A bundle is no reason to leave a pin, like an install from the repository would not
+    let pinned_version = package.pinned_version.trim();
+    if !pinned_version.is_empty() && Version::parse(pinned_version, package.version_scheme)? != version {
+        warn!("Skipping {}: it is pinned to {}, the bundle has {}", package.name, pinned_version, version);
+        return Ok(None);
+    }
+
+    let package_dir = bundle_dir.join(&entry.id);
+    let bundled_version = Version::parse(&fs::read_to_string(package_dir.join("version.txt"))?, package.version_scheme)?;
+    if bundled_version != version {
+        return Err(LoaderError::Integrity(format!("Bundle manifest says {} but version.txt says {}", version, bundled_version)).into());
+    }
+
+    if !package.public_key.trim().is_empty() {
+        verify_signed_list(package, entry, &version, &package_dir)?;
+    }
+
+    let output_dir = ctx.output_root.join(&package.output_path);
+    if confirm_version_change(ctx, package, &output_dir, &version).is_none() {
+        return Ok(None);
+    }
+
+    let naming = VolumeNaming::for_package(package)?;
+    let dl_dir = ctx.dl_dir.join(&package.id);
+    fs::create_dir_all(&dl_dir)?;
+    for file in &entry.files {
+        if !is_plain_name(&file.name) {
+            return Err(LoaderError::Integrity(format!("Bundle entry \"{}\" is not a plain file name", file.name)).into());
+        }
+        let source = package_dir.join(&file.name);
+        let actual = sha256_file(&source)
+            .map_err(|e| LoaderError::Integrity(format!("{} is missing from the bundle: {}", file.name, e)))?;
+        if actual != file.sha256 {
+            let _ = cleanup_package_dir(&dl_dir);
+            return Err(LoaderError::Integrity(format!("Integrity check failed for {} (expected {}, got {})", file.name, file.sha256, actual)).into());
+        }
+
+        let new_filename = naming.local_name(&file.name)?;
+        fs::copy(&source, dl_dir.join(&new_filename))?;
+        info!("Verified {} as: {}", file.name, new_filename);
+    }
+
+    //=-- install_with_hooks reports its own errors
+    let mut report = InstallReport::new(package);
+    install_with_hooks(ctx, package, &version, &dl_dir, &output_dir, &mut report);
+    Ok(report.error_kind)
+}
+
+//=-- `import-bundle <file> [id...]`: installs the selected packages (all when none are given) from a bundle.
+//=-- Returns the kind of the packages' failures, if any failed.
+pub fn import_bundle(ctx: &LoaderContext, settings: &Settings, bundle_path: &Path, ids: &[String]) -> Result<Option<ErrorKind>, Box<dyn std::error::Error>> {
+    let bundle_dir = ctx.dl_dir.join("bundle-import");
+    cleanup_package_dir(&bundle_dir)?;
+    fs::create_dir_all(&bundle_dir)?;
+
+    info!("Unpacking bundle {}", bundle_path.display());
+    tar::Archive::new(fs::File::open(bundle_path)?).unpack(&bundle_dir)
+        .map_err(|e| LoaderError::Extraction(format!("Failed to unpack bundle {}: {}", bundle_path.display(), e)))?;
+
+    let manifest: BundleManifest = serde_json::from_str(&fs::read_to_string(bundle_dir.join(MANIFEST_NAME))
+        .map_err(|_| LoaderError::Integrity(format!("{} is not a tools loader bundle ({} missing)", bundle_path.display(), MANIFEST_NAME)))?)
+        .map_err(|e| LoaderError::Integrity(format!("Invalid {} in {}: {}", MANIFEST_NAME, bundle_path.display(), e)))?;
+    if manifest.format != BUNDLE_FORMAT {
+        return Err(LoaderError::Integrity(format!("Unsupported bundle format {} (expected {})", manifest.format, BUNDLE_FORMAT)).into());
+    }
+
+    for id in ids {
+        if !manifest.packages.iter().any(|entry| &entry.id == id) {
+            warn!("{} is not in this bundle", id);
+        }
+    }
+
+    info!("\nPackages:");
+    let mut failures = Vec::new();
+    for entry in manifest.packages.iter().filter(|entry| ids.is_empty() || ids.contains(&entry.id)) {
+        if !is_plain_name(&entry.id) {
+            warn!("Skipping bundle entry with invalid id \"{}\"", entry.id);
+            continue;
+        }
+        let package = match find_package(settings, &entry.id) {
+            Ok(package) => package,
+            Err(e) => {
+                warn!("{} ({}) is in the bundle but not configured here:\n  {}", entry.name, entry.id, e);
+                continue;
+            }
+        };
+
+        //=-- Record the channel the bundle was exported from, not the one configured here
+        let mut package = package.clone();
+        package.channel = entry.channel.clone();
+        if let Some(format) = entry.format {
+            package.archive_format = format;
+        }
+        info!("\n{}: {} [{}] (from bundle)", package.name, entry.version, package.channel);
+
+        match import_package(ctx, &package, entry, &bundle_dir) {
+            Ok(failure) => failures.extend(failure),
+            Err(e) => {
+                error!("{} was not installed:\n  {}", package.name, e);
+                failures.push(ErrorKind::of(e.as_ref()));
+            },
+        }
+    }
+
+    cleanup_package_dir(&bundle_dir)?;
+    Ok(ErrorKind::combined(failures))
+}
@@ -0,0 +1,290 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use reqwest::blocking::{Client, Response};
+use reqwest::header::{ETAG, IF_NONE_MATCH};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::Settings;
+use crate::error::LoaderError;
+
+const DEFAULT_MAX_MB: u64 = 2048;
+
+//=-- Volumes are stored once per content hash in objects/<sha256>. The index remembers
+//=-- which URL (and ETag) produced which object, and when each object was last used.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheIndex {
+    #[serde(default)]
+    objects: BTreeMap<String, CacheObject>,
+    #[serde(default)]
+    urls: BTreeMap<String, CachedUrl>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheObject {
+    size: u64,
+    last_used: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedUrl {
+    sha256: String,
+    etag: Option<String>,
+}
+
+//=-- One cached volume, as listed by the `cache` command
+pub struct CacheEntry {
+    pub sha256: String,
+    pub size: u64,
+    pub age_secs: u64, //=-- Since it was last used
+    pub urls: Vec<String>, //=-- The URLs it was downloaded from
+}
+
+pub struct Cache {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+impl Cache {
+    //=-- Reads cache_dir and cache_max_mb from [main]. A cache_max_mb of 0 turns the cache off.
+    pub fn from_settings(settings: &Settings, config_dir: &Path) -> Option<Cache> {
+        let max_mb = settings.main.get("cache_max_mb")
+            .and_then(|s| s.trim().parse::<u64>().ok())
+            .unwrap_or(DEFAULT_MAX_MB);
+        if max_mb == 0 {
+            return None;
+        }
+
+        let dir = settings.main.get("cache_dir")
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .map(|s| config_dir.join(s)) //=-- join keeps absolute paths as they are
+            .unwrap_or_else(|| config_dir.join("cache"));
+        Some(Cache { dir, max_bytes: max_mb * 1024 * 1024 })
+    }
+
+    fn object_path(&self, sha256: &str) -> PathBuf {
+        self.dir.join("objects").join(sha256)
+    }
+
+    fn load_index(&self) -> CacheIndex {
+        fs::read_to_string(self.dir.join("index.json"))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save_index(&self, index: &CacheIndex) -> Result<(), Box<dyn std::error::Error>> {
+        fs::create_dir_all(&self.dir)?;
+        let tmp_path = self.dir.join("index.json.new");
+        fs::write(&tmp_path, serde_json::to_string_pretty(index)?)?;
+        fs::rename(&tmp_path, self.dir.join("index.json"))?;
+        Ok(())
+    }
+
+    //=-- Places the file at `url` into `target_path`, from the cache when possible.
+    //=-- With a known hash a cached copy is used without any network access; otherwise the
+    //=-- cached ETag is revalidated with the server. Returns true when the cache served the file.
+    pub fn fetch(&self, url: &str, expected_sha256: Option<&str>, target_path: &Path) -> Result<bool, Box<dyn std::error::Error>> {
+        let mut index = self.load_index();
+
+        if let Some(sha256) = expected_sha256 {
+            if index.objects.contains_key(sha256) && self.verify_object(&mut index, sha256) {
+                self.use_object(&mut index, sha256, target_path)?;
+                return Ok(true);
+            }
+        }
+
+        //=-- Only revalidate when the object the ETag refers to is still on disk and intact
+        let cached = index.urls.get(url)
+            .filter(|cached| expected_sha256.is_none_or(|sha256| sha256 == cached.sha256))
+            .map(|cached| (cached.sha256.clone(), cached.etag.clone()));
+        let cached = cached.filter(|(sha256, _)| self.verify_object(&mut index, sha256));
+
+        let client = Client::new();
+        let mut request = client.get(url);
+        if let Some(etag) = cached.as_ref().and_then(|(_, etag)| etag.as_ref()) {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        let response = request.send()?;
+        debug!("GET {} -> {}", url, response.status());
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            if let Some((sha256, _)) = cached {
+                self.use_object(&mut index, &sha256, target_path)?;
+                return Ok(true);
+            }
+        }
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(LoaderError::Network("File cannot be downloaded: 404 Not Found".to_string()).into());
+        }
+        if !response.status().is_success() {
+            return Err(LoaderError::Network(format!("File cannot be downloaded: {}", response.status())).into());
+        }
+
+        let etag = response.headers().get(ETAG)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string());
+
+        //=-- Stream into the cache while hashing, then move into place under the content hash
+        let objects_dir = self.dir.join("objects");
+        fs::create_dir_all(&objects_dir)?;
+        let tmp_path = objects_dir.join(format!("download-{}.tmp", std::process::id()));
+        let stored = Self::download_object(response, &tmp_path).and_then(|(sha256, size)| {
+            if let Some(expected) = expected_sha256.filter(|expected| *expected != sha256) {
+                return Err(LoaderError::Integrity(format!("Checksum mismatch for {} (expected {}, got {})", url, expected, sha256)).into());
+            }
+            fs::rename(&tmp_path, self.object_path(&sha256))?;
+            Ok((sha256, size))
+        });
+        //=-- Whatever went wrong, don't leave a partial download behind in objects/
+        let (sha256, size) = stored.inspect_err(|_| { let _ = fs::remove_file(&tmp_path); })?;
+
+        index.objects.insert(sha256.clone(), CacheObject { size, last_used: now_secs() });
+        index.urls.insert(url.to_string(), CachedUrl { sha256: sha256.clone(), etag });
+        self.use_object(&mut index, &sha256, target_path)?;
+        Ok(false)
+    }
+
+    //=-- Streamed so a dropped connection is a Network error and only a failed write is a Filesystem one
+    fn download_object(mut response: Response, tmp_path: &Path) -> Result<(String, u64), Box<dyn std::error::Error>> {
+        let mut file = fs::File::create(tmp_path)?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0u8; 64 * 1024];
+        let mut size = 0u64;
+        loop {
+            let read = response.read(&mut buffer)
+                .map_err(|e| LoaderError::Network(format!("File download was interrupted: {}", e)))?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+            file.write_all(&buffer[..read])?;
+            size += read as u64;
+        }
+        Ok((hex::encode(hasher.finalize()), size))
+    }
+
+    //=-- Hashes a cached object again before it is used. An object that is missing or was changed on disk
+    //=-- is dropped from the index so it gets downloaded again.
+    fn verify_object(&self, index: &mut CacheIndex, sha256: &str) -> bool {
+        let object_path = self.object_path(sha256);
+        match crate::sha256_file(&object_path) {
+            Ok(actual) if actual == sha256 => return true,
+            Ok(actual) => warn!("Cached object {} is corrupt (hashes to {}), downloading it again", sha256, actual),
+            Err(_) => debug!("Cached object {} is missing", sha256),
+        }
+        let _ = fs::remove_file(&object_path);
+        index.objects.remove(sha256);
+        index.urls.retain(|_, cached| cached.sha256 != sha256);
+        false
+    }
+
+    fn use_object(&self, index: &mut CacheIndex, sha256: &str, target_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(parent) = target_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let object_path = self.object_path(sha256);
+        if target_path.exists() {
+            fs::remove_file(target_path)?;
+        }
+        //=-- A hard link avoids a second copy of large volumes; fall back to copying across volumes
+        if fs::hard_link(&object_path, target_path).is_err() {
+            fs::copy(&object_path, target_path)?;
+        }
+        debug!("Cache object {} -> {}", sha256, target_path.display());
+
+        if let Some(object) = index.objects.get_mut(sha256) {
+            object.last_used = now_secs();
+        }
+        self.evict(index, sha256);
+        self.save_index(index)
+    }
+
+    //=-- Removes least recently used objects until the cache fits its size limit.
+    //=-- The object that is being used right now is never evicted.
+    fn evict(&self, index: &mut CacheIndex, keep_sha256: &str) {
+        let mut total: u64 = index.objects.values().map(|object| object.size).sum();
+        if total <= self.max_bytes {
+            return;
+        }
+
+        let mut by_age: Vec<(String, u64, u64)> = index.objects.iter()
+            .filter(|(sha256, _)| sha256.as_str() != keep_sha256)
+            .map(|(sha256, object)| (sha256.clone(), object.last_used, object.size))
+            .collect();
+        by_age.sort_by_key(|(_, last_used, _)| *last_used);
+
+        for (sha256, _, size) in by_age {
+            if total <= self.max_bytes {
+                break;
+            }
+            let _ = fs::remove_file(self.object_path(&sha256));
+            index.objects.remove(&sha256);
+            index.urls.retain(|_, cached| cached.sha256 != sha256);
+            total = total.saturating_sub(size);
+            debug!("Evicted {} from the download cache", sha256);
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn max_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+
+    //=-- Everything in the cache, most recently used first
+    pub fn entries(&self) -> Vec<CacheEntry> {
+        let index = self.load_index();
+        let now = now_secs();
+        let mut entries: Vec<CacheEntry> = index.objects.iter()
+            .map(|(sha256, object)| CacheEntry {
+                sha256: sha256.clone(),
+                size: object.size,
+                age_secs: now.saturating_sub(object.last_used),
+                urls: index.urls.iter()
+                    .filter(|(_, cached)| &cached.sha256 == sha256)
+                    .map(|(url, _)| url.clone())
+                    .collect(),
+            })
+            .collect();
+        entries.sort_by_key(|entry| entry.age_secs);
+        entries
+    }
+
+    pub fn clear(&self) -> io::Result<()> {
+        if self.dir.exists() {
+            fs::remove_dir_all(&self.dir)?;
+        }
+        info!("Cleared download cache: {}", self.dir.display());
+        Ok(())
+    }
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const MB: u64 = 1024 * 1024;
+    if bytes >= MB {
+        format!("{:.1} MB", bytes as f64 / MB as f64)
+    } else {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    }
+}
+
+pub fn format_age(secs: u64) -> String {
+    match secs {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{} min ago", secs / 60),
+        3600..=86399 => format!("{} h ago", secs / 3600),
+        _ => format!("{} days ago", secs / 86400),
+    }
+}
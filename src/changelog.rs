@@ -0,0 +1,77 @@
+use crate::{fetch, Package, Version, VersionScheme};
+
+//=-- A changelog is a text file with one heading per version, newest first, e.g.
+//=--   ## 2024-03-01--2
+//=--   - Fixed the thing
+//=--   ### Added
+//=--   - Another thing
+//=-- Headings whose first word isn't a version (like "### Added") are kept as part of the notes.
+pub struct ChangelogEntry {
+    pub version: Version,
+    pub notes: String,
+}
+
+fn parse_heading(line: &str, scheme: VersionScheme) -> Option<Version> {
+    if !line.starts_with('#') {
+        return None;
+    }
+    let token = line.trim_start_matches('#').split_whitespace().next()?;
+    let token = token.trim_start_matches('[').trim_end_matches(']');
+    let token = if scheme == VersionScheme::Semver { token.trim_start_matches('v') } else { token };
+    Version::parse(token, scheme).ok()
+}
+
+pub fn parse_changelog(content: &str, scheme: VersionScheme) -> Vec<ChangelogEntry> {
+    let mut entries: Vec<ChangelogEntry> = Vec::new();
+    for line in content.lines() {
+        if let Some(version) = parse_heading(line.trim(), scheme) {
+            entries.push(ChangelogEntry { version, notes: String::new() });
+        } else if let Some(entry) = entries.last_mut() {
+            entry.notes.push_str(line.trim_end());
+            entry.notes.push('\n');
+        }
+    }
+    for entry in &mut entries {
+        entry.notes = entry.notes.trim().to_string();
+    }
+    entries
+}
+
+pub fn fetch_changelog(package: &Package) -> Result<Vec<ChangelogEntry>, Box<dyn std::error::Error>> {
+    let content = fetch::fetch_text(&package.changelog_url, "Changelog")?;
+    Ok(parse_changelog(&content, package.version_scheme))
+}
+
+//=-- Entries newer than `installed` up to and including `target`, newest first
+pub fn entries_between<'a>(entries: &'a [ChangelogEntry], installed: Option<&Version>, target: &Version) -> Vec<&'a ChangelogEntry> {
+    let mut selected: Vec<&ChangelogEntry> = entries.iter()
+        .filter(|entry| &entry.version <= target)
+        .filter(|entry| installed.is_none_or(|installed| &entry.version > installed))
+        .collect();
+    selected.sort_by(|a, b| b.version.cmp(&a.version));
+    selected
+}
+
+//=-- The entries as text, each under a "== <version> ==" heading
+pub fn format_entries(entries: &[&ChangelogEntry]) -> String {
+    entries.iter()
+        .map(|entry| {
+            let notes = if entry.notes.is_empty() { "(no notes)" } else { entry.notes.as_str() };
+            format!("\n== {} ==\n{}\n", entry.version, notes)
+        })
+        .collect()
+}
+
+//=-- The changelog to show before an update, if the package publishes one (None when it doesn't or it can't be fetched)
+pub fn update_notes(package: &Package) -> Option<Vec<ChangelogEntry>> {
+    if package.changelog_url.trim().is_empty() {
+        return None;
+    }
+    match fetch_changelog(package) {
+        Ok(entries) => Some(entries),
+        Err(e) => {
+            warn!("Release notes are not available: {}", e);
+            None
+        }
+    }
+}
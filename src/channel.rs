@@ -0,0 +1,103 @@
+use std::fs;
+use std::path::Path;
+use serde::Deserialize;
+
+use crate::Package;
+use crate::error::LoaderError;
+
+pub const DEFAULT_CHANNEL: &str = "stable";
+
+//=-- Per-channel URL overrides. Empty fields fall back to the package's own URL (with {channel} filled in)
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Channel {
+    #[serde(default)]
+    pub version_url: String,
+    #[serde(default)]
+    pub filelist_url: String,
+    #[serde(default)]
+    pub repo_url: String,
+    #[serde(default)]
+    pub versions_url: String,
+    #[serde(default)]
+    pub changelog_url: String,
+    #[serde(default)]
+    pub manifest_url: String,
+}
+
+fn uses_channel_template(package: &Package) -> bool {
+    [&package.version_url, &package.filelist_url, &package.repo_url, &package.versions_url, &package.changelog_url, &package.manifest_url]
+        .iter()
+        .any(|url| url.contains("{channel}"))
+}
+
+//=-- Returns a copy of the package with its URLs pointing at the given channel
+pub fn apply_channel(package: &Package, channel: &str) -> Result<Package, Box<dyn std::error::Error>> {
+    let mut resolved = package.clone();
+    match package.channels.get(channel) {
+        Some(overrides) => {
+            for (url, channel_url) in [
+                (&mut resolved.version_url, &overrides.version_url),
+                (&mut resolved.filelist_url, &overrides.filelist_url),
+                (&mut resolved.repo_url, &overrides.repo_url),
+                (&mut resolved.versions_url, &overrides.versions_url),
+                (&mut resolved.changelog_url, &overrides.changelog_url),
+                (&mut resolved.manifest_url, &overrides.manifest_url),
+            ] {
+                if !channel_url.trim().is_empty() {
+                    *url = channel_url.clone();
+                }
+            }
+        },
+        None if channel == DEFAULT_CHANNEL || uses_channel_template(package) => {},
+        None => return Err(LoaderError::Config(format!("{} has no \"{}\" channel", package.name, channel)).into()),
+    }
+
+    for url in [
+        &mut resolved.version_url,
+        &mut resolved.filelist_url,
+        &mut resolved.repo_url,
+        &mut resolved.versions_url,
+        &mut resolved.changelog_url,
+        &mut resolved.manifest_url,
+    ] {
+        *url = url.replace("{channel}", channel);
+    }
+    for mirror in resolved.mirrors.iter_mut() {
+        *mirror = mirror.replace("{channel}", channel);
+    }
+    resolved.channel = channel.to_string();
+    Ok(resolved)
+}
+
+//=-- Picks the channel for a package: command line, then the package's own setting, then [main] channel, then stable.
+//=-- A package that lacks a globally chosen channel falls back to stable; one that names a missing channel itself is an error.
+pub fn resolve_package_channel(package: &Package, cli_channel: Option<&str>, default_channel: &str) -> Result<Package, Box<dyn std::error::Error>> {
+    let package_channel = package.channel.trim();
+    if cli_channel.is_none() && !package_channel.is_empty() {
+        return apply_channel(package, &package_channel.to_lowercase());
+    }
+
+    let channel = cli_channel.unwrap_or(default_channel).to_lowercase();
+    match apply_channel(package, &channel) {
+        Ok(resolved) => Ok(resolved),
+        Err(_) if channel != DEFAULT_CHANNEL => {
+            warn!("{} has no \"{}\" channel, using \"{}\"", package.name, channel, DEFAULT_CHANNEL);
+            apply_channel(package, DEFAULT_CHANNEL)
+        },
+        Err(e) => Err(e),
+    }
+}
+
+pub fn save_channel_file(channel: &str, output_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    fs::write(output_dir.join("channel.txt"), channel)?;
+    Ok(())
+}
+
+//=-- Installs made before channels existed have no channel.txt and came from stable
+pub fn get_installed_channel(output_dir: &Path) -> String {
+    fs::read_to_string(output_dir.join("channel.txt"))
+        .map(|channel| channel.trim().to_string())
+        .ok()
+        .filter(|channel| !channel.is_empty())
+        .unwrap_or_else(|| DEFAULT_CHANNEL.to_string())
+}
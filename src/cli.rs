@@ -0,0 +1,211 @@
+use std::path::PathBuf;
+
+use wb_toolsloader::logging::Verbosity;
+use wb_toolsloader::pack::PackOptions;
+use wb_toolsloader::VersionRequest;
+
+pub const USAGE: &str = "Usage: wb-toolsloader [--channel <name>] [command]
+
+Commands:
+  (none)                          Interactive package menu (full screen when the terminal supports it)
+  self-update                     Update the loader itself
+  list                            List the configured packages and their installed versions
+  status                          Compare every package's installed version with the available one
+  upgrade                         Install only the packages that are outdated or not installed
+  watch [--once]                  Keep checking for updates and apply them per package update_policy,
+                                  without prompting, until stopped (Ctrl+C / SIGTERM)
+  install <id> [--version <version> | --as-of <YYYY-MM-DD>]
+                                  Install a package (latest, pinned, or a specific version)
+  uninstall <id>                  Remove an installed package (runs its pre/post_uninstall hooks)
+  changelog <id>                  Show release notes between the installed and the available version
+  cache list                      List the contents of the download cache
+  cache clear                     Delete everything in the download cache
+  export-bundle <file> [id...]    Pack packages (all when no ids are given) into an offline bundle
+  import-bundle <file> [id...]    Install packages (all when no ids are given) from an offline bundle
+  pack <id> <folder> <out-dir> --version <version> [--volume-size <MB>] [--encrypt] [--sign-key <file>]
+                                  Create a package's volumes, version.txt and filelist.txt from a folder
+
+Options:
+  --channel <name>                Release channel to use for every package (e.g. stable, beta)
+  --config <file>                 Use this config instead of the Config.toml next to the loader.
+                                  Relative paths in it and the loader's version.txt are looked up next to it
+  --quiet | --verbose | --trace   Console verbosity (default: [main] log_level, else normal).
+                                  The log file always records everything
+  --no-tui                        Use the numbered line menu instead of the full-screen one. The line menu is
+                                  also used when input or output is redirected, or TERM=dumb
+  --json                          Print one JSON document on stdout instead of text (list, status, install
+                                  and upgrade). Messages go to stderr and prompts take their default answer
+
+Exit codes:
+  0 success            4 network error      8 filesystem error
+  1 other error        5 integrity error    9 cancelled
+  2 bad command line   6 extraction error  10 packages failed for different reasons
+  3 config error       7 wrong password    11 a hook failed or timed out
+When packages fail but the run finishes (install, upgrade, import-bundle, watch --once, the menu), the exit
+code is the kind of their failures.";
+
+pub struct Cli {
+    pub command: CliCommand,
+    pub channel: Option<String>,
+    pub config: Option<PathBuf>,
+    pub verbosity: Option<Verbosity>,
+    pub json: bool,
+    pub no_tui: bool,
+}
+
+pub enum CliCommand {
+    Menu,
+    SelfUpdate,
+    List,
+    Status,
+    Upgrade,
+    Watch { once: bool },
+    Install { id: String, request: VersionRequest },
+    Uninstall { id: String },
+    Changelog { id: String },
+    Cache { clear: bool },
+    ExportBundle { path: PathBuf, ids: Vec<String> },
+    ImportBundle { path: PathBuf, ids: Vec<String> },
+    Pack(PackOptions),
+}
+
+impl CliCommand {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CliCommand::Menu => "menu",
+            CliCommand::SelfUpdate => "self-update",
+            CliCommand::List => "list",
+            CliCommand::Status => "status",
+            CliCommand::Upgrade => "upgrade",
+            CliCommand::Watch { .. } => "watch",
+            CliCommand::Install { .. } => "install",
+            CliCommand::Uninstall { .. } => "uninstall",
+            CliCommand::Changelog { .. } => "changelog",
+            CliCommand::Cache { .. } => "cache",
+            CliCommand::ExportBundle { .. } => "export-bundle",
+            CliCommand::ImportBundle { .. } => "import-bundle",
+            CliCommand::Pack(_) => "pack",
+        }
+    }
+}
+
+pub fn parse_args(args: &[String]) -> Result<Cli, String> {
+    //=-- Global options may appear anywhere, so pull them out before looking at the command
+    let mut channel = None;
+    let mut config = None;
+    let mut verbosity = None;
+    let mut json = false;
+    let mut no_tui = false;
+    let mut rest = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--channel" {
+            channel = Some(iter.next().ok_or("--channel needs a value")?.clone());
+        } else if matches!(arg.as_str(), "--quiet" | "--verbose" | "--trace") {
+            verbosity = Verbosity::from_name(&arg[2..]);
+        } else if arg == "--json" {
+            json = true;
+        } else if arg == "--no-tui" {
+            no_tui = true;
+        } else if arg == "--config" {
+            config = Some(PathBuf::from(iter.next().ok_or("--config needs a value")?));
+        } else {
+            rest.push(arg.clone());
+        }
+    }
+
+    let command = parse_command(&rest)?;
+    if json && !matches!(command, CliCommand::List | CliCommand::Status | CliCommand::Upgrade | CliCommand::Install { .. }) {
+        return Err(format!("--json is not supported by {}", command.name()));
+    }
+    Ok(Cli { command, channel, config, verbosity, json, no_tui })
+}
+
+fn parse_command(args: &[String]) -> Result<CliCommand, String> {
+    let mut args = args.iter();
+    let command = match args.next() {
+        Some(command) => command,
+        None => return Ok(CliCommand::Menu),
+    };
+
+    match command.as_str() {
+        "self-update" => Ok(CliCommand::SelfUpdate),
+        "list" => Ok(CliCommand::List),
+        "status" => Ok(CliCommand::Status),
+        "upgrade" => Ok(CliCommand::Upgrade),
+        "watch" => match args.next().map(|s| s.as_str()) {
+            None => Ok(CliCommand::Watch { once: false }),
+            Some("--once") => Ok(CliCommand::Watch { once: true }),
+            Some(other) => Err(format!("watch: unknown option {}", other)),
+        },
+        "install" => {
+            let id = args.next().ok_or("install: missing package id")?.clone();
+            let mut request = VersionRequest::Latest;
+            while let Some(flag) = args.next() {
+                let value = args.next().ok_or_else(|| format!("install: {} needs a value", flag))?.clone();
+                request = match flag.as_str() {
+                    "--version" => VersionRequest::Exact(value),
+                    "--as-of" => VersionRequest::AsOf(value),
+                    _ => return Err(format!("install: unknown option {}", flag)),
+                };
+            }
+            Ok(CliCommand::Install { id, request })
+        },
+        "uninstall" => {
+            let id = args.next().ok_or("uninstall: missing package id")?.clone();
+            Ok(CliCommand::Uninstall { id })
+        },
+        "changelog" => {
+            let id = args.next().ok_or("changelog: missing package id")?.clone();
+            Ok(CliCommand::Changelog { id })
+        },
+        "cache" => match args.next().map(|s| s.as_str()) {
+            Some("list") | None => Ok(CliCommand::Cache { clear: false }),
+            Some("clear") => Ok(CliCommand::Cache { clear: true }),
+            Some(other) => Err(format!("cache: unknown subcommand {}", other)),
+        },
+        "export-bundle" | "import-bundle" => {
+            let path = PathBuf::from(args.next().ok_or_else(|| format!("{}: missing bundle file", command))?);
+            let ids: Vec<String> = args.cloned().collect();
+            if command == "export-bundle" {
+                Ok(CliCommand::ExportBundle { path, ids })
+            } else {
+                Ok(CliCommand::ImportBundle { path, ids })
+            }
+        },
+        "pack" => {
+            let id = args.next().ok_or("pack: missing package id")?.clone();
+            let source = PathBuf::from(args.next().ok_or("pack: missing folder to pack")?);
+            let out_dir = PathBuf::from(args.next().ok_or("pack: missing output folder")?);
+            let mut options = PackOptions {
+                id, source, out_dir,
+                version: String::new(),
+                volume_size_mb: 1024,
+                encrypt: false,
+                password: String::new(),
+                sign_key: None,
+            };
+            while let Some(flag) = args.next() {
+                if flag == "--encrypt" {
+                    options.encrypt = true;
+                    continue;
+                }
+                let value = args.next().ok_or_else(|| format!("pack: {} needs a value", flag))?.clone();
+                match flag.as_str() {
+                    "--version" => options.version = value,
+                    "--volume-size" => {
+                        options.volume_size_mb = value.parse().ok().filter(|mb| *mb > 0)
+                            .ok_or_else(|| format!("pack: invalid --volume-size {}", value))?;
+                    },
+                    "--sign-key" => options.sign_key = Some(PathBuf::from(value)),
+                    _ => return Err(format!("pack: unknown option {}", flag)),
+                }
+            }
+            if options.version.is_empty() {
+                return Err("pack: missing --version".into());
+            }
+            Ok(CliCommand::Pack(options))
+        },
+        _ => Err(format!("Unknown command: {}", command)),
+    }
+}
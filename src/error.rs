@@ -0,0 +1,144 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use serde::Serialize;
+
+//=-- Errors travel as Box<dyn Error>. Every failure the loader itself detects is created as a LoaderError;
+//=-- io, HTTP and config file errors are recognised by their type (see ErrorKind::of).
+#[derive(Debug)]
+pub enum LoaderError {
+    Config(String),      //=-- Config.toml is missing, invalid or lacks something the command needs
+    Network(String),     //=-- A repository, mirror or version URL could not be reached or refused the request
+    Integrity(String),   //=-- A download or published file doesn't match its hash, size or signature
+    Extraction(String),  //=-- An archive is missing volumes or could not be extracted
+    WrongPassword(String),
+    Filesystem(String),  //=-- Reading or writing local files failed
+    Cancelled(String),   //=-- The user chose to stop
+    Hook(String),        //=-- A pre/post install or uninstall hook failed or timed out
+}
+
+//=-- What went wrong, and the process exit code for it:
+//=--   0 success            4 network            8 filesystem
+//=--   1 other error        5 integrity          9 cancelled by the user
+//=--   2 bad command line   6 extraction        10 packages failed for different reasons
+//=--   3 config             7 wrong password    11 a hook failed or timed out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    Other,
+    Usage,
+    Config,
+    Network,
+    Integrity,
+    Extraction,
+    WrongPassword,
+    Filesystem,
+    Cancelled,
+    Mixed,
+    Hook,
+}
+
+impl ErrorKind {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::Other => 1,
+            ErrorKind::Usage => 2,
+            ErrorKind::Config => 3,
+            ErrorKind::Network => 4,
+            ErrorKind::Integrity => 5,
+            ErrorKind::Extraction => 6,
+            ErrorKind::WrongPassword => 7,
+            ErrorKind::Filesystem => 8,
+            ErrorKind::Cancelled => 9,
+            ErrorKind::Mixed => 10,
+            ErrorKind::Hook => 11,
+        }
+    }
+
+    //=-- The first recognisable error in the chain decides
+    pub fn of(error: &(dyn Error + 'static)) -> ErrorKind {
+        let mut current = Some(error);
+        while let Some(error) = current {
+            if let Some(error) = error.downcast_ref::<LoaderError>() {
+                return error.kind();
+            }
+            if error.is::<reqwest::Error>() {
+                return ErrorKind::Network;
+            }
+            if error.is::<config::ConfigError>() {
+                return ErrorKind::Config;
+            }
+            if error.is::<io::Error>() {
+                return ErrorKind::Filesystem;
+            }
+            current = error.source();
+        }
+        ErrorKind::Other
+    }
+
+    //=-- The kind for a run where packages failed with these kinds (None when nothing failed)
+    pub fn combined(kinds: impl IntoIterator<Item = ErrorKind>) -> Option<ErrorKind> {
+        kinds.into_iter().reduce(|a, b| if a == b { a } else { ErrorKind::Mixed })
+    }
+}
+
+impl LoaderError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            LoaderError::Config(_) => ErrorKind::Config,
+            LoaderError::Network(_) => ErrorKind::Network,
+            LoaderError::Integrity(_) => ErrorKind::Integrity,
+            LoaderError::Extraction(_) => ErrorKind::Extraction,
+            LoaderError::WrongPassword(_) => ErrorKind::WrongPassword,
+            LoaderError::Filesystem(_) => ErrorKind::Filesystem,
+            LoaderError::Cancelled(_) => ErrorKind::Cancelled,
+            LoaderError::Hook(_) => ErrorKind::Hook,
+        }
+    }
+
+    //=-- An error of `kind` with this message. Kinds without a LoaderError variant give a plain error.
+    pub fn with_kind(kind: ErrorKind, message: String) -> Box<dyn Error> {
+        let error = match kind {
+            ErrorKind::Config => LoaderError::Config(message),
+            ErrorKind::Network => LoaderError::Network(message),
+            ErrorKind::Integrity => LoaderError::Integrity(message),
+            ErrorKind::Extraction => LoaderError::Extraction(message),
+            ErrorKind::WrongPassword => LoaderError::WrongPassword(message),
+            ErrorKind::Filesystem => LoaderError::Filesystem(message),
+            ErrorKind::Cancelled => LoaderError::Cancelled(message),
+            ErrorKind::Hook => LoaderError::Hook(message),
+            ErrorKind::Other | ErrorKind::Usage | ErrorKind::Mixed => return message.into(),
+        };
+        Box::new(error)
+    }
+}
+
+impl fmt::Display for LoaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoaderError::Config(message)
+            | LoaderError::Network(message)
+            | LoaderError::Integrity(message)
+            | LoaderError::Extraction(message)
+            | LoaderError::WrongPassword(message)
+            | LoaderError::Filesystem(message)
+            | LoaderError::Cancelled(message)
+            | LoaderError::Hook(message) => f.write_str(message),
+        }
+    }
+}
+
+impl Error for LoaderError {}
+
+//=-- Keeps the kind of `error` while putting `context` in front of its message
+pub fn context(context: &str, error: Box<dyn Error>) -> Box<dyn Error> {
+    LoaderError::with_kind(ErrorKind::of(error.as_ref()), format!("{}: {}", context, error))
+}
+
+//=-- Gives errors of no recognisable kind the kind of the step that failed, e.g. zip errors while extracting
+pub fn or_kind(kind: ErrorKind, error: Box<dyn Error>) -> Box<dyn Error> {
+    match ErrorKind::of(error.as_ref()) {
+        ErrorKind::Other => LoaderError::with_kind(kind, error.to_string()),
+        _ => error,
+    }
+}
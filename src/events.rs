@@ -0,0 +1,109 @@
+use std::path::Path;
+
+use crate::changelog::{self, ChangelogEntry};
+use crate::{output, InstallReport, Package, Version};
+
+//=-- What an install is doing, for front ends that show progress (a GUI, a service's status page).
+//=-- The log gets the same information as text; observers get it as data.
+pub enum Event<'a> {
+    PackageStarted { package: &'a Package },
+    VolumeDownloaded { package: &'a Package, volume: &'a str, bytes: u64, from_cache: bool, done: usize, total: usize },
+    //=-- After each archive of the package is extracted and moved into the output folder
+    ExtractionProgress { package: &'a Package, archive: &'a str, done: usize, total: usize },
+    //=-- Sent right before the DecisionProvider is asked
+    PromptNeeded { package: &'a Package, question: &'a Question<'a> },
+    //=-- report.action says whether it failed
+    PackageFinished { package: &'a Package, report: &'a InstallReport },
+}
+
+//=-- Send + Sync so installs can run on a worker thread while a front end draws
+pub trait Observer: Send + Sync {
+    fn on_event(&self, event: &Event);
+}
+
+//=-- The observer of a LoaderContext until another is set
+pub struct IgnoreEvents;
+
+impl Observer for IgnoreEvents {
+    fn on_event(&self, _event: &Event) {}
+}
+
+//=-- Something an install needs to know from the user
+pub enum Question<'a> {
+    ReloadSameVersion { version: &'a Version },
+    Downgrade { installed: &'a Version, available: &'a Version },
+    //=-- Asked when the package publishes a changelog; `notes` are the entries between the two versions
+    ContinueUpdate { installed: &'a Version, available: &'a Version, notes: &'a [&'a ChangelogEntry] },
+    ExistingOutputDir { output_dir: &'a Path },
+    //=-- `retry` after a password failed (the one from the config or an earlier answer)
+    Password { retry: bool },
+}
+
+//=-- What to do with an output folder that already exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputDirAction {
+    Overwrite,
+    Delete, //=-- Delete it and extract into an empty folder
+}
+
+//=-- Answers the questions an install asks. The library itself never reads stdin.
+pub trait DecisionProvider: Send + Sync {
+    //=-- ReloadSameVersion, Downgrade and ContinueUpdate
+    fn confirm(&self, package: &Package, question: &Question) -> bool;
+    //=-- Whether updates fetch the package's changelog and ask ContinueUpdate (otherwise they just go ahead)
+    fn wants_release_notes(&self) -> bool {
+        true
+    }
+    fn existing_output_dir(&self, package: &Package, output_dir: &Path) -> OutputDirAction;
+    //=-- None skips the package
+    fn password(&self, package: &Package, retry: bool) -> Option<String>;
+}
+
+//=-- The default provider: asks through output::ask, so without a prompter (and with --json or in watch)
+//=-- every question takes its default answer, and the binary asks on the console
+pub struct PromptDecisions;
+
+impl DecisionProvider for PromptDecisions {
+    fn confirm(&self, package: &Package, question: &Question) -> bool {
+        match question {
+            Question::ReloadSameVersion { .. } => {
+                output::ask("Package version is the same. Reload anyway? (Y/N) [N]: ").eq_ignore_ascii_case("Y")
+            },
+            Question::Downgrade { .. } => {
+                output::ask("Download older version from repository? (Y/N) [N]: ").eq_ignore_ascii_case("Y")
+            },
+            Question::ContinueUpdate { available, notes, .. } => {
+                //=-- The notes are part of the prompt, so the front end shows them right above the question
+                let notes = if notes.is_empty() {
+                    info!("No release notes found for {}", available);
+                    String::new()
+                } else {
+                    format!("\nRelease notes for {}:\n{}\n", package.name, changelog::format_entries(notes))
+                };
+                !output::ask(&format!("{}Continue with update to {}? (Y/N) [Y]: ", notes, available)).eq_ignore_ascii_case("N")
+            },
+            Question::ExistingOutputDir { .. } | Question::Password { .. } => false,
+        }
+    }
+
+    fn wants_release_notes(&self) -> bool {
+        !output::is_unattended()
+    }
+
+    fn existing_output_dir(&self, _package: &Package, _output_dir: &Path) -> OutputDirAction {
+        //=-- Default to overwrite (empty input or "O")
+        match output::ask("(O)verwrite or (D)elete output folder? [O]: ").to_uppercase().as_str() {
+            "D" => OutputDirAction::Delete,
+            _ => OutputDirAction::Overwrite,
+        }
+    }
+
+    fn password(&self, _package: &Package, retry: bool) -> Option<String> {
+        let prompt = if retry {
+            "\nEnter password for extraction (press Enter [on a blank entry] to skip this package): "
+        } else {
+            "\nEnter password for extraction: "
+        };
+        Some(output::ask(prompt)).filter(|password| !password.is_empty())
+    }
+}
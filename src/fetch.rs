@@ -0,0 +1,151 @@
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use reqwest::blocking::Client;
+
+use crate::error::LoaderError;
+
+//=-- Package URLs may point at a web server, a `file://` URL, or a plain local/UNC path,
+//=-- so a network share or USB stick can serve as a repository without a web server.
+pub enum Location<'a> {
+    Remote(&'a str),
+    Local(PathBuf),
+}
+
+pub fn locate(url: &str) -> Location<'_> {
+    let lower = url.to_ascii_lowercase();
+    if lower.starts_with("http://") || lower.starts_with("https://") {
+        Location::Remote(url)
+    } else if lower.starts_with("file://") {
+        Location::Local(file_url_to_path(&url["file://".len()..]))
+    } else {
+        Location::Local(PathBuf::from(url))
+    }
+}
+
+pub fn is_local(url: &str) -> bool {
+    matches!(locate(url), Location::Local(_))
+}
+
+//=-- file:///C:/repo -> C:/repo, file:///srv/repo -> /srv/repo, file://server/share -> \\server\share
+fn file_url_to_path(rest: &str) -> PathBuf {
+    let decoded = percent_decode(rest);
+    if let Some(path) = decoded.strip_prefix('/') {
+        let bytes = path.as_bytes();
+        let has_drive = bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':';
+        if has_drive {
+            PathBuf::from(path)
+        } else {
+            PathBuf::from(format!("/{}", path))
+        }
+    } else if let Some(path) = decoded.strip_prefix("localhost/") {
+        PathBuf::from(format!("/{}", path))
+    } else {
+        PathBuf::from(format!("\\\\{}", decoded.replace('/', "\\")))
+    }
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(byte) = hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+//=-- Relative local paths in the config are relative to the loader's folder, like nanazip_exe
+pub fn resolve_relative(url: &str, base_dir: &Path) -> String {
+    if url.trim().is_empty() {
+        return url.to_string();
+    }
+    match locate(url) {
+        Location::Local(path) if path.is_relative() && !url.to_ascii_lowercase().starts_with("file://") => {
+            base_dir.join(path).to_string_lossy().into_owned()
+        },
+        _ => url.to_string(),
+    }
+}
+
+//=-- Reads a small text resource. `what` names it in errors, e.g. "Version" or "File list"
+pub fn fetch_text(url: &str, what: &str) -> Result<String, Box<dyn std::error::Error>> {
+    match locate(url) {
+        Location::Remote(url) => {
+            let client = Client::new();
+            let response = client.get(url).send()?;
+            debug!("GET {} -> {}", url, response.status());
+            if response.status() == reqwest::StatusCode::NOT_FOUND {
+                return Err(LoaderError::Network(format!("{} cannot be retrieved: 404 Not Found", what)).into());
+            }
+            if !response.status().is_success() {
+                return Err(LoaderError::Network(format!("{} cannot be retrieved: {}", what, response.status())).into());
+            }
+            Ok(response.text()?)
+        },
+        Location::Local(path) => fs::read_to_string(&path).inspect(|_| debug!("Read {}", path.display())).map_err(|e| {
+            if e.kind() == io::ErrorKind::NotFound {
+                LoaderError::Network(format!("{} cannot be retrieved: {} not found", what, path.display())).into()
+            } else {
+                LoaderError::Network(format!("{} cannot be retrieved from {}: {}", what, path.display(), e)).into()
+            }
+        }),
+    }
+}
+
+//=-- Downloads or copies the resource at `url` into `target_path`
+pub fn fetch_to_file(url: &str, target_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    //=-- Create parent directories if they don't exist
+    if let Some(parent) = target_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    match locate(url) {
+        Location::Remote(url) => {
+            let client = Client::new();
+            let mut response = client.get(url).send()?;
+            debug!("GET {} -> {}", url, response.status());
+            if response.status() == reqwest::StatusCode::NOT_FOUND {
+                return Err(LoaderError::Network("File cannot be downloaded: 404 Not Found".to_string()).into());
+            }
+            if !response.status().is_success() {
+                return Err(LoaderError::Network(format!("File cannot be downloaded: {}", response.status())).into());
+            }
+
+            //=-- Streamed so a dropped connection is a Network error and only a failed write is a Filesystem one
+            let mut file = fs::File::create(target_path)?;
+            let mut buffer = vec![0u8; 64 * 1024];
+            let mut written = 0u64;
+            loop {
+                let read = response.read(&mut buffer)
+                    .map_err(|e| LoaderError::Network(format!("File download was interrupted: {}", e)))?;
+                if read == 0 {
+                    break;
+                }
+                file.write_all(&buffer[..read])?;
+                written += read as u64;
+            }
+            trace!("Saved {} bytes to {}", written, target_path.display());
+        },
+        Location::Local(path) => {
+            fs::copy(&path, target_path).map_err(|e| {
+                if e.kind() == io::ErrorKind::NotFound {
+                    LoaderError::Network(format!("File cannot be copied: {} not found", path.display()))
+                } else {
+                    LoaderError::Network(format!("File cannot be copied from {}: {}", path.display(), e))
+                }
+            })?;
+            debug!("Copied {} -> {}", path.display(), target_path.display());
+        },
+    }
+    Ok(())
+}
@@ -0,0 +1,210 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::LoaderError;
+use crate::{copy_dir_all, Package, Version};
+
+const DEFAULT_TIMEOUT_SECS: u64 = 300;
+
+//=-- Commands a package can run around installs and uninstalls. They run through the shell (cmd /C on
+//=-- Windows, sh -c elsewhere) in the config's folder, with the package described in the environment:
+//=--   WBTL_HOOK              pre_install, post_install, pre_uninstall or post_uninstall
+//=--   WBTL_PACKAGE_ID        WBTL_PACKAGE_NAME      WBTL_CHANNEL
+//=--   WBTL_VERSION           the version being installed (or uninstalled)
+//=--   WBTL_PREVIOUS_VERSION  the version installed before, empty if none
+//=--   WBTL_OUTPUT_DIR        the package's output folder
+//=-- A failing pre_* hook stops the install/uninstall. A failing post_* hook fails the package, and with
+//=-- rollback_on_hook_failure the output folder is put back the way it was.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    PreInstall,
+    PostInstall,
+    PreUninstall,
+    PostUninstall,
+}
+
+impl Hook {
+    pub fn name(self) -> &'static str {
+        match self {
+            Hook::PreInstall => "pre_install",
+            Hook::PostInstall => "post_install",
+            Hook::PreUninstall => "pre_uninstall",
+            Hook::PostUninstall => "post_uninstall",
+        }
+    }
+
+    fn command(self, package: &Package) -> &str {
+        match self {
+            Hook::PreInstall => &package.pre_install,
+            Hook::PostInstall => &package.post_install,
+            Hook::PreUninstall => &package.pre_uninstall,
+            Hook::PostUninstall => &package.post_uninstall,
+        }
+        .trim()
+    }
+}
+
+//=-- What the hook is told about the package
+pub struct HookContext<'a> {
+    pub package: &'a Package,
+    pub version: &'a Version,
+    pub previous: Option<&'a Version>,
+    pub output_dir: &'a Path,
+    pub config_dir: &'a Path,
+}
+
+fn shell_command(command_line: &str) -> Command {
+    if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.arg("/C").arg(command_line);
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(command_line);
+        //=-- Its own process group, so kill_tree reaches everything the hook starts
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+        command
+    }
+}
+
+//=-- Stops the hook and every process it started. Killing only the sh/cmd wrapper would leave a slow
+//=-- installer running, and holding the output pipes.
+fn kill_tree(child: &mut Child) {
+    #[cfg(unix)]
+    let _ = Command::new("kill")
+        .args(["-s", "KILL", "--"])
+        .arg(format!("-{}", child.id()))
+        .stderr(Stdio::null())
+        .status();
+    #[cfg(windows)]
+    let _ = Command::new("taskkill")
+        .args(["/T", "/F", "/PID", &child.id().to_string()])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+//=-- Logs every line the hook prints as it arrives, then tells `done` it reached the end
+fn forward_output<R: Read + Send + 'static>(reader: R, hook: Hook, done: mpsc::Sender<()>) {
+    thread::spawn(move || {
+        for line in BufReader::new(reader).lines().map_while(Result::ok) {
+            info!("[{}] {}", hook.name(), line);
+        }
+        let _ = done.send(());
+    });
+}
+
+//=-- Runs the package's `hook` if it has one, killing it after hook_timeout_secs
+pub fn run(hook: Hook, ctx: &HookContext) -> Result<(), Box<dyn std::error::Error>> {
+    let command_line = hook.command(ctx.package);
+    if command_line.is_empty() {
+        return Ok(());
+    }
+    let timeout = Duration::from_secs(ctx.package.hook_timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS));
+    info!("Running {} hook for {}", hook.name(), ctx.package.name);
+    debug!("{}: {}", hook.name(), command_line);
+
+    let mut child = shell_command(command_line)
+        .current_dir(ctx.config_dir)
+        .env("WBTL_HOOK", hook.name())
+        .env("WBTL_PACKAGE_ID", &ctx.package.id)
+        .env("WBTL_PACKAGE_NAME", &ctx.package.name)
+        .env("WBTL_CHANNEL", &ctx.package.channel)
+        .env("WBTL_VERSION", ctx.version.to_string())
+        .env("WBTL_PREVIOUS_VERSION", ctx.previous.map(|version| version.to_string()).unwrap_or_default())
+        .env("WBTL_OUTPUT_DIR", ctx.output_dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| LoaderError::Hook(format!("{} hook cannot be started: {}", hook.name(), e)))?;
+
+    let (done, finished) = mpsc::channel();
+    if let Some(stdout) = child.stdout.take() {
+        forward_output(stdout, hook, done.clone());
+    }
+    if let Some(stderr) = child.stderr.take() {
+        forward_output(stderr, hook, done);
+    }
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() > deadline {
+            kill_tree(&mut child);
+            return Err(LoaderError::Hook(format!("{} hook timed out after {} s", hook.name(), timeout.as_secs())).into());
+        }
+        thread::sleep(Duration::from_millis(50));
+    };
+
+    //=-- Let the last lines through, but don't wait on background processes the hook left holding the pipes
+    let grace = Instant::now() + Duration::from_secs(1);
+    for _ in 0..2 {
+        if finished.recv_timeout(grace.saturating_duration_since(Instant::now())).is_err() {
+            break;
+        }
+    }
+
+    if !status.success() {
+        return Err(LoaderError::Hook(format!("{} hook failed: {}", hook.name(), status)).into());
+    }
+    debug!("{} hook finished", hook.name());
+    Ok(())
+}
+
+//=-- A copy of the output folder to put back if extraction or a post_* hook fails. Only kept with rollback_on_hook_failure.
+pub struct Rollback {
+    output_dir: PathBuf,
+    backup_dir: PathBuf,
+    existed: bool,
+}
+
+impl Rollback {
+    pub fn prepare(package: &Package, output_dir: &Path, backup_dir: &Path) -> Result<Option<Rollback>, Box<dyn std::error::Error>> {
+        if !package.rollback_on_hook_failure {
+            return Ok(None);
+        }
+        if package.is_root {
+            warn!("{} is a root package, its shared output folder is not backed up for rollback", package.name);
+            return Ok(None);
+        }
+        let existed = output_dir.exists();
+        if backup_dir.exists() {
+            fs::remove_dir_all(backup_dir)?;
+        }
+        if existed {
+            copy_dir_all(output_dir, backup_dir)
+                .map_err(|e| LoaderError::Filesystem(format!("Failed to back up {} for rollback: {}", output_dir.display(), e)))?;
+        }
+        Ok(Some(Rollback { output_dir: output_dir.to_path_buf(), backup_dir: backup_dir.to_path_buf(), existed }))
+    }
+
+    //=-- Puts the output folder back as it was before
+    pub fn restore(self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.output_dir.exists() {
+            fs::remove_dir_all(&self.output_dir)?;
+        }
+        if self.existed {
+            copy_dir_all(&self.backup_dir, &self.output_dir)?;
+            fs::remove_dir_all(&self.backup_dir)?;
+        }
+        info!("Rolled back {}", self.output_dir.display());
+        Ok(())
+    }
+
+    pub fn discard(self) {
+        if self.backup_dir.exists() {
+            let _ = fs::remove_dir_all(&self.backup_dir);
+        }
+    }
+}
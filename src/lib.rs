@@ -0,0 +1,1334 @@
+//! Installs and updates packages from WarpBits tools repositories.
+//!
+//! The `wb-toolsloader` binary is a front end over this library: it parses the command line, shows the menu
+//! and answers prompts on the console. Other tools can drive the same steps directly:
+//!
+//! ```no_run
+//! use std::path::Path;
+//! use wb_toolsloader::{load_settings, process_package, LoaderContext, VersionRequest};
+//!
+//! let config = Path::new("tools/Config.toml");
+//! let settings = load_settings(config)?;
+//! let ctx = LoaderContext::new(&settings, Path::new("tools"), "tools/output".into(), std::env::temp_dir().join("dl"))?;
+//! let package = wb_toolsloader::find_package(&settings, "mytool")?;
+//! let report = process_package(&ctx, package, &VersionRequest::Latest);
+//! println!("{}: {}", report.id, report.action);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! The steps of an install, each usable on its own:
+//! - [`fetch_release`] resolves the version to install and its volumes,
+//! - [`download_release`] downloads the volumes (through the cache and mirrors) and checks them,
+//! - [`install_with_hooks`] extracts them into the output folder between the package's hooks.
+//!
+//! [`process_package`] runs all three, [`upgrade_packages`], [`package_status`], [`list_packages`] and
+//! [`uninstall_package`] cover the other commands.
+//!
+//! The library never reads stdin. Questions (reload the same version? overwrite the output folder? archive
+//! password?) go to the context's [`events::DecisionProvider`]. The default one asks through [`output::ask`],
+//! which takes the default answer until a front end installs a prompter with [`output::set_prompter`].
+//! Progress (downloads, extraction, prompts, finished packages) goes to the context's [`events::Observer`].
+//! Failures are `Box<dyn Error>` carrying an [`error::LoaderError`] (config, network, integrity, extraction,
+//! wrong password, filesystem, cancelled or hook) or the io, HTTP or config error behind them.
+//! [`error::ErrorKind::of`] tells what kind of failure one is, which also decides the binary's exit code, and
+//! install steps record theirs in the [`InstallReport`].
+
+use config::Config;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::fs;
+use std::process::{Command, Output, Stdio};
+use serde::Deserialize;
+use indexmap::IndexMap;
+use sha2::{Digest, Sha256};
+
+#[macro_use]
+pub mod logging;
+
+mod archive;
+pub mod bundle;
+pub mod cache;
+pub mod changelog;
+pub mod channel;
+pub mod error;
+pub mod events;
+mod fetch;
+mod hooks;
+mod manifest;
+mod mirrors;
+mod naming;
+pub mod output;
+pub mod pack;
+pub mod self_update;
+mod signature;
+mod version;
+pub mod watch;
+
+pub use archive::ArchiveFormat;
+use cache::Cache;
+pub use channel::Channel;
+use error::{ErrorKind, LoaderError};
+use events::{DecisionProvider, Event, IgnoreEvents, Observer, OutputDirAction, PromptDecisions, Question};
+use hooks::{Hook, HookContext, Rollback};
+use mirrors::{MirrorSet, MirrorStrategy};
+use naming::VolumeNaming;
+pub use naming::VolumeNamingKind;
+pub use output::{Action, InstallReport, PackageInfo, PackageState, PackageStatus};
+use version::validate_date;
+pub use version::{Version, VersionScheme};
+pub use watch::UpdatePolicy;
+
+//=-- One [packages.<key>] table of Config.toml
+#[derive(Debug, Deserialize, Clone)]
+pub struct Package {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    #[serde(default)]
+    pub version_url: String,
+    #[serde(default)]
+    pub filelist_url: String,
+    pub repo_url: String,
+    pub output_path: String,
+    pub password: String,
+    pub is_root: bool,
+    #[serde(default)]
+    pub version_scheme: VersionScheme,
+    #[serde(default)]
+    pub versions_url: String,
+    #[serde(default)]
+    pub pinned_version: String,
+    #[serde(default)]
+    pub channel: String,
+    #[serde(default)]
+    pub channels: IndexMap<String, Channel>,
+    #[serde(default)]
+    pub changelog_url: String,
+    #[serde(default)]
+    pub manifest_url: String,
+    #[serde(default)]
+    pub mirrors: Vec<String>,
+    #[serde(default)]
+    pub volume_naming: VolumeNamingKind,
+    #[serde(default)]
+    pub volume_pattern: String,
+    #[serde(default)]
+    pub volume_suffix: String,
+    #[serde(default)]
+    pub archive_format: ArchiveFormat,
+    #[serde(default)]
+    pub public_key: String,
+    #[serde(default)]
+    pub update_policy: UpdatePolicy,
+    #[serde(default)]
+    pub pre_install: String,
+    #[serde(default)]
+    pub post_install: String,
+    #[serde(default)]
+    pub pre_uninstall: String,
+    #[serde(default)]
+    pub post_uninstall: String,
+    #[serde(default)]
+    pub hook_timeout_secs: Option<u64>,
+    #[serde(default)]
+    pub rollback_on_hook_failure: bool,
+}
+
+//=-- Everything in Config.toml (and the WBTL_* environment variables), see load_settings
+#[derive(Debug, Deserialize)]
+pub struct Settings {
+    pub archive: HashMap<String, String>,
+    pub packages: IndexMap<String, Package>,
+    pub main: HashMap<String, String>,
+    #[serde(default)]
+    pub mirrors: IndexMap<String, MirrorSet>,
+}
+
+pub fn get_current_version(output_dir: &Path, scheme: VersionScheme) -> Result<Option<Version>, Box<dyn std::error::Error>> {
+    let version_file = output_dir.join("version.txt");
+    if !version_file.exists() {
+        return Ok(None);
+    }
+    
+    let content = fs::read_to_string(&version_file)?;
+    let version = Version::parse(&content, scheme)
+        .map_err(|e| LoaderError::Config(format!("{} in {}", e, version_file.display())))?;
+    Ok(Some(version))
+}
+
+fn should_update_package(ctx: &LoaderContext, package: &Package, current: Option<&Version>, new: &Version) -> Result<bool, Box<dyn std::error::Error>> {
+    match current {
+        None => Ok(true),
+        Some(current) => {
+            if current == new {
+                Ok(ctx.confirm(package, &Question::ReloadSameVersion { version: new }))
+            } else if current > new {
+                info!("Local version ({}) is newer than repository version ({})", 
+                    current, new);
+                Ok(ctx.confirm(package, &Question::Downgrade { installed: current, available: new }))
+            } else if let Some(entries) = ctx.decisions.wants_release_notes().then(|| changelog::update_notes(package)).flatten() {
+                let notes = changelog::entries_between(&entries, Some(current), new);
+                Ok(ctx.confirm(package, &Question::ContinueUpdate { installed: current, available: new, notes: &notes }))
+            } else {
+                Ok(true) //=-- If current < new, it should update
+            }
+        }
+    }
+}
+
+pub fn get_version(url: &str) -> Result<String, Box<dyn std::error::Error>> {
+    Ok(fetch::fetch_text(url, "Version")?.trim().to_string())
+}
+
+//=-- One filelist entry. Lines may carry the volume's SHA-256 after the name: "<file> <sha256>".
+//=-- Manifests can also give the volume's size.
+pub struct FileEntry {
+    pub name: String,
+    pub sha256: Option<String>,
+    pub size: Option<u64>,
+}
+
+//=-- A version of a package and the volumes it is made of, from a manifest or version_url + filelist_url
+pub struct PackageRelease {
+    pub version: Version,
+    pub files: Vec<FileEntry>,
+    pub format: Option<ArchiveFormat>,
+    pub metadata: IndexMap<String, serde_json::Value>,
+    pub published: Option<PublishedList>,
+}
+
+//=-- The filelist or manifest a release was read from, as published, with its signature if the package checks
+//=-- one. Bundles carry it so an import can check the signature offline.
+pub struct PublishedList {
+    pub file_name: &'static str, //=-- "filelist.txt" or "manifest.json"
+    pub content: String,
+    pub signature: Option<String>,
+}
+
+fn parse_file_entry(line: &str) -> FileEntry {
+    if let Some((name, hash)) = line.rsplit_once(char::is_whitespace) {
+        if hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()) {
+            return FileEntry { name: name.trim().to_string(), sha256: Some(hash.to_lowercase()), size: None };
+        }
+    }
+    FileEntry { name: line.to_string(), sha256: None, size: None }
+}
+
+//=-- Filelists written by `pack` start with "# version <version>", so the signature also covers which version
+//=-- the volumes belong to. Other lines starting with '#' are comments.
+const FILELIST_VERSION_PREFIX: &str = "# version ";
+
+fn get_package_files(package: &Package, version: &Version) -> Result<(Vec<FileEntry>, PublishedList), Box<dyn std::error::Error>> {
+    let url = expand_url(&package.filelist_url, version);
+    let content = fetch::fetch_text(&url, "File list")?;
+    let signature = match package.public_key.trim().is_empty() {
+        true => None,
+        false => Some(signature::verify(&package.public_key, &url, &content)?),
+    };
+    let files = parse_filelist(package, &url, &content, version)?;
+    Ok((files, PublishedList { file_name: "filelist.txt", content, signature }))
+}
+
+//=-- The entries of a filelist read from `source` (a URL or bundle path), checked against the version it should describe
+pub(crate) fn parse_filelist(package: &Package, source: &str, content: &str, version: &Version) -> Result<Vec<FileEntry>, Box<dyn std::error::Error>> {
+    let signed = !package.public_key.trim().is_empty();
+    let listed_version = content.lines()
+        .find_map(|line| line.trim().strip_prefix(FILELIST_VERSION_PREFIX))
+        .map(|listed| Version::parse(listed, package.version_scheme))
+        .transpose()
+        .map_err(|e| LoaderError::Integrity(format!("Invalid version in {}: {}", source, e)))?;
+    match listed_version {
+        //=-- Otherwise an older release's signed filelist could be served under a newer version.txt
+        Some(listed) if &listed != version => {
+            return Err(LoaderError::Integrity(format!("File list {} is for version {}, not {}", source, listed, version)).into());
+        },
+        None if signed => {
+            return Err(LoaderError::Integrity(format!("Signed file list {} does not name its version", source)).into());
+        },
+        _ => {},
+    }
+
+    let files: Vec<FileEntry> = content
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(parse_file_entry)
+        .collect();
+    
+    Ok(files)
+}
+
+pub fn download_file(url: &str, target_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    fetch::fetch_to_file(url, target_path)
+}
+
+pub fn sha256_file(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let mut hasher = Sha256::new();
+    let mut file = fs::File::open(path)?;
+    io::copy(&mut file, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+//=-- Downloads a volume through the cache when it is enabled, verifying it against the filelist hash and size if known.
+//=-- Returns true when the cache served the file.
+fn download_package_file(ctx: &LoaderContext, url: &str, file: &FileEntry, target_path: &Path) -> Result<bool, Box<dyn std::error::Error>> {
+    let sha256 = file.sha256.as_deref();
+    //=-- Local repositories are already on disk, so caching them would only duplicate the files
+    let from_cache = match ctx.cache.as_ref().filter(|_| !fetch::is_local(url)) {
+        Some(cache) => cache.fetch(url, sha256, target_path)?,
+        None => {
+            download_file(url, target_path)?;
+            false
+        },
+    };
+
+    if let Some(expected) = file.size {
+        let actual = fs::metadata(target_path)?.len();
+        if actual != expected {
+            let _ = fs::remove_file(target_path);
+            return Err(LoaderError::Integrity(format!("Size mismatch (expected {} bytes, got {})", expected, actual)).into());
+        }
+    }
+    //=-- The cache hashes every object again before serving it, so a hit needs no second check
+    if from_cache {
+        return Ok(true);
+    }
+    if let Some(expected) = sha256 {
+        let actual = sha256_file(target_path)?;
+        if actual != expected {
+            let _ = fs::remove_file(target_path);
+            return Err(LoaderError::Integrity(format!("Checksum mismatch (expected {}, got {})", expected, actual)).into());
+        }
+    }
+    Ok(false)
+}
+
+fn handle_output_dir(ctx: &LoaderContext, output_dir: &Path, package: &Package) -> Result<(), Box<dyn std::error::Error>> {
+    if output_dir.exists() {
+        if package.is_root {
+            info!("This is a root package, so we are skipping deletion and will overwrite the existing files");
+        } else {
+            ctx.notify(Event::PromptNeeded { package, question: &Question::ExistingOutputDir { output_dir } });
+            match ctx.decisions.existing_output_dir(package, output_dir) {
+                OutputDirAction::Delete => {
+                    fs::remove_dir_all(output_dir)?;
+                    fs::create_dir_all(output_dir)?;
+                    info!("Deleted and recreated output folder");
+                },
+                OutputDirAction::Overwrite => info!("Will overwrite existing files"),
+            }
+        }
+    } else {
+        fs::create_dir_all(output_dir)?;
+    }
+    Ok(())
+}
+
+//=-- Runs NanaZip with the password answered over stdin (`prompts` times), so it never shows up in the process list
+fn run_nanazip(cmd: &mut Command, password: &str, prompts: usize) -> io::Result<Output> {
+    debug!("Running {:?}", cmd);
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        if !password.is_empty() {
+            //=-- NanaZip may exit without reading the password (e.g. unencrypted archive), so a broken pipe is fine
+            for _ in 0..prompts {
+                let _ = writeln!(stdin, "{}", password);
+            }
+        }
+        //=-- Dropping stdin closes the pipe, so a wrong or missing password fails instead of waiting for input
+    }
+
+    let output = child.wait_with_output()?;
+    debug!("NanaZip exited with {}", output.status);
+    trace!("NanaZip output:\n{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+    Ok(output)
+}
+
+//=-- Masks every occurrence of the secret in text that is about to be printed
+fn redact_secret(text: &str, secret: &str) -> String {
+    if secret.is_empty() {
+        text.to_string()
+    } else {
+        text.replace(secret, "********")
+    }
+}
+
+//=-- One archive in the download folder: its volumes in order and how to extract them
+struct ArchiveSet {
+    base_name: String,
+    format: ArchiveFormat,
+    volumes: Vec<PathBuf>,
+}
+
+//=-- Groups the downloaded volumes into archives and works out each archive's format
+fn find_archives(naming: &VolumeNaming, configured: ArchiveFormat, package_dir: &Path) -> Result<Vec<ArchiveSet>, Box<dyn std::error::Error>> {
+    let mut volumes: IndexMap<String, Vec<(u32, PathBuf)>> = IndexMap::new();
+    for entry in fs::read_dir(package_dir)? {
+        let entry = entry?;
+        let Some((base_name, number)) = entry.file_name().to_str().and_then(|name| naming.split_local_name(name)) else {
+            continue;
+        };
+        volumes.entry(base_name).or_default().push((number, entry.path()));
+    }
+
+    let mut archives = Vec::new();
+    for (base_name, mut parts) in volumes {
+        parts.sort_by_key(|(number, _)| *number);
+        if parts[0].0 != 1 {
+            return Err(LoaderError::Extraction(format!("First volume of {} is missing", base_name)).into());
+        }
+        let volumes: Vec<PathBuf> = parts.into_iter().map(|(_, path)| path).collect();
+        let format = archive::resolve_format(configured, &volumes[0])?;
+        archives.push(ArchiveSet { base_name, format, volumes });
+    }
+    Ok(archives)
+}
+
+//=-- NanaZip is pointed at the first volume of the archive and finds the rest itself
+fn extract_with_nanazip(nanazip_path: &Path, package_dir: &Path, archive_path: &Path, extract_dir: &Path, password: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::new(nanazip_path);
+    cmd.current_dir(package_dir)
+       .arg("x")
+       .arg("-y") //=-- Force yes on all queries
+       .arg(archive_path)
+       .arg(format!("-o{}", extract_dir.display()));
+
+    match run_nanazip(&mut cmd, password, 1) {
+        Ok(output) => {
+            if !output.status.success() {
+                let error_msg = redact_secret(&String::from_utf8_lossy(&output.stderr), password);
+                if error_msg.contains("Wrong password?") {
+                    return Err(LoaderError::WrongPassword("Wrong password".to_string()).into());
+                }
+                return Err(LoaderError::Extraction(format!(
+                    "Failed to extract {}: {}", 
+                    archive_path.display(),
+                    error_msg
+                )).into());
+            }
+            Ok(())
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            error!("!==-- The config for NanaZip's location is incorrect. NanaZip executable not found. --==!");
+            Err(LoaderError::Config(format!("NanaZip cannot be started: {}", e)).into())
+        },
+        Err(e) => Err(Box::new(e)),
+    }
+}
+
+fn extract_archives(ctx: &LoaderContext, package: &Package, archives: &[ArchiveSet], package_dir: &Path, output_dir: &Path, password: &str) -> Result<(), Box<dyn std::error::Error>> {
+    for (done, archive_set) in archives.iter().enumerate() {
+        let archive_path = &archive_set.volumes[0];
+        let extract_dir = package_dir.join(format!("{}.extracted", archive_set.base_name));
+        fs::create_dir_all(&extract_dir)?;
+
+        if archive_set.format == ArchiveFormat::SevenZip {
+            extract_with_nanazip(&ctx.nanazip_path, package_dir, archive_path, &extract_dir, password)?;
+        } else {
+            debug!("Extracting {} volume(s) of {} in-process", archive_set.volumes.len(), archive_set.base_name);
+            archive::extract(archive_set.format, &archive_set.volumes, &extract_dir, password)
+                .map_err(|e| error::or_kind(ErrorKind::Extraction, e))
+                .map_err(|e| LoaderError::with_kind(ErrorKind::of(e.as_ref()), redact_secret(&e.to_string(), password)))?;
+        }
+        info!("Extracted {} ({}) to {}", archive_path.display(), archive_set.format, extract_dir.display());
+
+        //=-- Move extracted files to output directory
+        fs::create_dir_all(output_dir)?;
+        let normalized_output_dir = normalize_path_buf(output_dir)
+            .map_err(|e| LoaderError::Filesystem(format!("Failed to normalize output path {}: {}", output_dir.display(), e)))?;
+        
+        debug!("Normalized output directory: {}", normalized_output_dir.display());
+        
+        for entry in fs::read_dir(&extract_dir)? {
+            let entry = entry?;
+            let source_path = entry.path();
+            let target_path = normalized_output_dir.join(entry.file_name());
+            
+            debug!("Processing: {} -> {}", source_path.display(), target_path.display());
+            
+            //=-- If target exists, try to remove it first
+            if target_path.exists() {
+                if target_path.is_dir() {
+                    retry_file_operation(
+                        || fs::remove_dir_all(&target_path).map_err(|e| Box::new(e) as Box<dyn std::error::Error>),
+                        3,
+                        100
+                    ).map_err(|e| LoaderError::Filesystem(format!("Failed to remove existing directory {}: {}", target_path.display(), e)))?;
+                } else {
+                    retry_file_operation(
+                        || fs::remove_file(&target_path).map_err(|e| Box::new(e) as Box<dyn std::error::Error>),
+                        3,
+                        100
+                    ).map_err(|e| LoaderError::Filesystem(format!("Failed to remove existing file {}: {}", target_path.display(), e)))?;
+                }
+            }
+            
+            //=-- Try to move the file with retries
+            retry_file_operation(
+                || {
+                    if source_path.is_dir() {
+                        match fs::rename(&source_path, &target_path) {
+                            Ok(()) => Ok(()),
+                            Err(_) => {
+                                // For directories, try copy_dir_all if rename fails
+                                copy_dir_all(&source_path, &target_path)
+                                    .and_then(|_| fs::remove_dir_all(&source_path))
+                                    .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
+                            }
+                        }
+                    } else {
+                        match fs::rename(&source_path, &target_path) {
+                            Ok(()) => Ok(()),
+                            Err(_) => {
+                                fs::copy(&source_path, &target_path)
+                                    .and_then(|_| fs::remove_file(&source_path))
+                                    .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
+                            }
+                        }
+                    }
+                },
+                3,
+                100
+            ).map_err(|e| LoaderError::Filesystem(format!("Failed to move {} to {}: {}", source_path.display(), target_path.display(), e)))?;
+        }
+        info!("Moved files to {}", output_dir.display());
+
+        //=-- Clean up extraction directory
+        fs::remove_dir_all(&extract_dir)?;
+        ctx.notify(Event::ExtractionProgress { package, archive: &archive_set.base_name, done: done + 1, total: archives.len() });
+    }
+    Ok(())
+}
+
+fn save_version_file(version: &Version, output_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let version_file = output_dir.join("version.txt");
+    fs::write(version_file, version.to_string())?;
+    Ok(())
+}
+
+pub fn cleanup_package_dir(dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    if dir.exists() {
+        fs::remove_dir_all(dir)?;
+    }
+    Ok(())
+}
+
+pub fn get_local_version(exe_dir: &Path) -> Result<Option<Version>, Box<dyn std::error::Error>> {
+    let version_path = exe_dir.join("version.txt");
+    if version_path.exists() {
+        let version_str = fs::read_to_string(&version_path)?;
+        let version = Version::parse(&version_str, VersionScheme::DateIteration)
+            .map_err(|e| LoaderError::Config(format!("{} in {}", e, version_path.display())))?;
+        Ok(Some(version))
+    } else {
+        Ok(None)
+    }
+}
+
+use std::thread;
+use std::time::Duration;
+
+fn retry_file_operation<F, T>(mut operation: F, retries: u32, delay_ms: u64) -> Result<T, Box<dyn std::error::Error>>
+where
+    F: FnMut() -> Result<T, Box<dyn std::error::Error>>,
+{
+    let mut last_error = None;
+    for _ in 0..retries {
+        match operation() {
+            Ok(result) => return Ok(result),
+            Err(e) => {
+                last_error = Some(e);
+                thread::sleep(Duration::from_millis(delay_ms));
+            }
+        }
+    }
+    Err(last_error.unwrap())
+}
+
+// Helper function to recursively copy directories
+fn copy_dir_all(src: &Path, dst: &Path) -> io::Result<()> {
+    if !dst.exists() {
+        fs::create_dir_all(dst)?;
+    }
+
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let ty = entry.file_type()?;
+        let source = entry.path();
+        let destination = dst.join(entry.file_name());
+
+        if ty.is_dir() {
+            copy_dir_all(&source, &destination)?;
+        } else {
+            fs::copy(&source, &destination)?;
+        }
+    }
+    Ok(())
+}
+
+fn normalize_path_buf(path: &Path) -> io::Result<PathBuf> {
+    let mut components = Vec::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::ParentDir => {
+                components.pop();
+            },
+            std::path::Component::Normal(name) => components.push(name),
+            std::path::Component::RootDir => components.push(component.as_os_str()),
+            std::path::Component::Prefix(prefix) => components.push(prefix.as_os_str()),
+            _ => {}
+        }
+    }
+    let mut result = PathBuf::new();
+    for component in components {
+        result.push(component);
+    }
+    Ok(result)
+}
+
+pub fn load_settings(config_path: &Path) -> Result<Settings, Box<dyn std::error::Error>> {
+    let config_name = config_path.to_str()
+        .ok_or_else(|| LoaderError::Config(format!("Config path {} is not valid UTF-8", config_path.display())))?;
+    let settings = Config::builder()
+        //=-- Override with local Config.toml next to executable
+        .add_source(config::File::with_name(config_name).required(false))
+        //=-- Add environment variable source with prefix WBTL
+        .add_source(config::Environment::with_prefix("WBTL").separator("__"))
+          //=-- Ex: WBTL_ARCHIVE__NANAZIP_EXE=path/to/nanazip.exe
+          //=-- Ex: WBTL_PACKAGES__MYPACKAGE__OUTPUT_PATH=path/to/output
+        .build()?
+        .try_deserialize()?;
+    Ok(resolve_local_repositories(settings, config_path.parent().unwrap_or(Path::new("."))))
+}
+
+//=-- Makes relative local repository paths relative to the loader's folder instead of the working directory
+fn resolve_local_repositories(mut settings: Settings, config_dir: &Path) -> Settings {
+    for package in settings.packages.values_mut() {
+        for url in [
+            &mut package.version_url,
+            &mut package.filelist_url,
+            &mut package.repo_url,
+            &mut package.versions_url,
+            &mut package.changelog_url,
+            &mut package.manifest_url,
+        ] {
+            *url = fetch::resolve_relative(url, config_dir);
+        }
+        for mirror in package.mirrors.iter_mut() {
+            *mirror = fetch::resolve_relative(mirror, config_dir);
+        }
+        for channel in package.channels.values_mut() {
+            for url in [
+                &mut channel.version_url,
+                &mut channel.filelist_url,
+                &mut channel.repo_url,
+                &mut channel.versions_url,
+                &mut channel.changelog_url,
+                &mut channel.manifest_url,
+            ] {
+                *url = fetch::resolve_relative(url, config_dir);
+            }
+        }
+    }
+    settings
+}
+
+//=-- Which version of a package to install
+pub enum VersionRequest {
+    Latest,         //=-- The pinned version if the package has one, else whatever version_url returns
+    Exact(String),  //=-- A specific version from the versioned repository layout
+    AsOf(String),   //=-- The newest version published on or before YYYY-MM-DD (date-iteration packages only)
+}
+
+//=-- Where and how packages are installed, shared by every step of a run
+pub struct LoaderContext {
+    pub config_dir: PathBuf,   //=-- Hooks run here, relative paths in the config are relative to it
+    pub nanazip_path: PathBuf,
+    pub dl_dir: PathBuf,       //=-- Volumes are downloaded to <dl_dir>/<id>; cleaned up after each package
+    pub output_root: PathBuf,  //=-- Packages install to <output_root>/<output_path>
+    pub cli_channel: Option<String>, //=-- Overrides every package's channel (--channel)
+    pub default_channel: String,
+    pub observer: Box<dyn Observer>,          //=-- Told about progress; ignores it by default
+    pub decisions: Box<dyn DecisionProvider>, //=-- Answers prompts; asks through output::ask by default
+    cache: Option<Cache>,
+    mirror_sets: Vec<MirrorSet>,
+    mirror_strategy: MirrorStrategy,
+}
+
+impl LoaderContext {
+    pub fn new(settings: &Settings, config_dir: &Path, output_root: PathBuf, dl_dir: PathBuf) -> Result<LoaderContext, Box<dyn std::error::Error>> {
+        Ok(LoaderContext {
+            config_dir: config_dir.to_path_buf(),
+            nanazip_path: nanazip_path(settings, config_dir)?,
+            dl_dir,
+            output_root,
+            cli_channel: None,
+            default_channel: settings.main.get("channel")
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .unwrap_or_else(|| channel::DEFAULT_CHANNEL.to_string()),
+            observer: Box::new(IgnoreEvents),
+            decisions: Box::new(PromptDecisions),
+            cache: Cache::from_settings(settings, config_dir),
+            mirror_sets: settings.mirrors.values().cloned().collect(),
+            mirror_strategy: MirrorStrategy::from_setting(settings.main.get("mirror_strategy")),
+        })
+    }
+
+    pub fn output_dir(&self, package: &Package) -> PathBuf {
+        self.output_root.join(&package.output_path)
+    }
+
+    pub fn notify(&self, event: Event) {
+        self.observer.on_event(&event);
+    }
+
+    //=-- Tells the observer a yes/no question is coming, then asks the decision provider
+    fn confirm(&self, package: &Package, question: &Question) -> bool {
+        self.notify(Event::PromptNeeded { package, question });
+        self.decisions.confirm(package, question)
+    }
+}
+
+//=-- [archive] nanazip_exe, relative to the config's folder
+pub fn nanazip_path(settings: &Settings, config_dir: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let nanazip_relative_path = settings.archive.get("nanazip_exe")
+        .ok_or_else(|| LoaderError::Config("nanazip_exe not found in config".to_string()))?;
+    Ok(config_dir.join(nanazip_relative_path))
+}
+
+//=-- Packages with `{version}` in filelist_url/repo_url (or manifest_url) can serve any published version, not just the latest
+fn is_versioned_layout(package: &Package) -> bool {
+    if has_manifest(package) {
+        return package.manifest_url.contains("{version}");
+    }
+    package.filelist_url.contains("{version}") || package.repo_url.contains("{version}")
+}
+
+fn has_manifest(package: &Package) -> bool {
+    !package.manifest_url.trim().is_empty()
+}
+
+fn expand_url(url: &str, version: &Version) -> String {
+    url.replace("{version}", &version.to_string())
+}
+
+//=-- Mirrors serving the package's volumes, in the order they should be tried
+fn package_mirrors(ctx: &LoaderContext, package: &Package, version: &Version, files: &[FileEntry]) -> Vec<String> {
+    let mirrors = mirrors::repo_mirrors(package, version, &ctx.mirror_sets);
+    match files.first() {
+        Some(probe) if mirrors.len() > 1 && ctx.mirror_strategy == MirrorStrategy::Latency => {
+            mirrors::sort_by_latency(mirrors, &probe.name)
+        },
+        _ => mirrors,
+    }
+}
+
+fn get_published_versions(package: &Package) -> Result<Vec<Version>, Box<dyn std::error::Error>> {
+    if package.versions_url.trim().is_empty() {
+        return Err(LoaderError::Config(format!("{} has no versions_url, so its published versions are unknown", package.name)).into());
+    }
+    let content = fetch::fetch_text(&package.versions_url, "Version list")?;
+    let mut versions = content
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| Version::parse(line, package.version_scheme))
+        .collect::<Result<Vec<Version>, _>>()
+        .map_err(|e| LoaderError::Config(format!("{} in {}", e, package.versions_url)))?;
+    versions.sort();
+    Ok(versions)
+}
+
+fn find_published_version(package: &Package, version_str: &str) -> Result<Version, Box<dyn std::error::Error>> {
+    let version = Version::parse(version_str, package.version_scheme)?;
+    if !package.versions_url.trim().is_empty() && !get_published_versions(package)?.contains(&version) {
+        return Err(LoaderError::Config(format!("Version {} of {} is not published", version, package.name)).into());
+    }
+    Ok(version)
+}
+
+fn resolve_target_version(package: &Package, request: &VersionRequest) -> Result<Version, Box<dyn std::error::Error>> {
+    resolve_target_version_from(package, request, || Version::parse(&get_version(&package.version_url)?, package.version_scheme))
+}
+
+//=-- Like resolve_target_version, but `latest` supplies the latest version (only called when it is needed)
+fn resolve_target_version_from<F>(package: &Package, request: &VersionRequest, latest: F) -> Result<Version, Box<dyn std::error::Error>>
+where
+    F: Fn() -> Result<Version, Box<dyn std::error::Error>>,
+{
+    let pinned_version = package.pinned_version.trim();
+    let version = match request {
+        VersionRequest::Latest if pinned_version.is_empty() => return latest(),
+        VersionRequest::Latest => find_published_version(package, pinned_version)?,
+        VersionRequest::Exact(version_str) => find_published_version(package, version_str)?,
+        VersionRequest::AsOf(date) => {
+            if package.version_scheme != VersionScheme::DateIteration {
+                return Err(LoaderError::Config(format!("--as-of needs a date-iteration package, {} uses {}", package.name, package.version_scheme)).into());
+            }
+            validate_date(date)?;
+            get_published_versions(package)?
+                .into_iter()
+                .rfind(|version| version.date().is_some_and(|d| d <= date.as_str()))
+                .ok_or_else(|| LoaderError::Config(format!("{} has no version published on or before {}", package.name, date)))?
+        },
+    };
+
+    //=-- Without a versioned layout the repository only holds the latest version
+    if !is_versioned_layout(package) {
+        let latest = latest()?;
+        if latest != version {
+            return Err(LoaderError::Config(format!(
+                "{} does not use a versioned repository layout and only serves its latest version ({})",
+                package.name, latest
+            )).into());
+        }
+    }
+    Ok(version)
+}
+
+//=-- Resolves the requested version and its volumes. A manifest_url answers both in one request;
+//=-- with `{version}` in it, the version is resolved first and the matching manifest fetched.
+pub fn fetch_release(package: &Package, request: &VersionRequest) -> Result<PackageRelease, Box<dyn std::error::Error>> {
+    if has_manifest(package) && !is_versioned_layout(package) {
+        let release = manifest::fetch_manifest(package, &package.manifest_url)?;
+        //=-- Checks pins and explicit requests against the manifest's version
+        resolve_target_version_from(package, request, || Ok(release.version.clone()))?;
+        return Ok(release);
+    }
+    fetch_release_of(package, resolve_target_version(package, request)?)
+}
+
+//=-- The volumes of a version that is already resolved
+fn fetch_release_of(package: &Package, version: Version) -> Result<PackageRelease, Box<dyn std::error::Error>> {
+    if !has_manifest(package) {
+        let (files, published) = get_package_files(package, &version)?;
+        return Ok(PackageRelease { version, files, format: None, metadata: IndexMap::new(), published: Some(published) });
+    }
+
+    let release = manifest::fetch_manifest(package, &expand_url(&package.manifest_url, &version))?;
+    if release.version != version {
+        return Err(LoaderError::Integrity(format!("Manifest for {} {} describes version {}", package.name, version, release.version)).into());
+    }
+    Ok(release)
+}
+
+//=-- What a status check read from the repository: the version, or the whole release where a manifest
+//=-- answers both in one request. An install goes on from it instead of asking again.
+pub enum RemoteRelease {
+    Version(Version),
+    Release(PackageRelease),
+}
+
+impl RemoteRelease {
+    pub fn version(&self) -> &Version {
+        match self {
+            RemoteRelease::Version(version) => version,
+            RemoteRelease::Release(release) => &release.version,
+        }
+    }
+
+    fn into_release(self, package: &Package) -> Result<PackageRelease, Box<dyn std::error::Error>> {
+        match self {
+            RemoteRelease::Version(version) => fetch_release_of(package, version),
+            RemoteRelease::Release(release) => Ok(release),
+        }
+    }
+}
+
+//=-- The version an install would fetch, without fetching its volume list where that takes an extra request
+pub fn fetch_target_version(package: &Package, request: &VersionRequest) -> Result<Version, Box<dyn std::error::Error>> {
+    Ok(fetch_remote_release(package, request)?.version().clone())
+}
+
+fn fetch_remote_release(package: &Package, request: &VersionRequest) -> Result<RemoteRelease, Box<dyn std::error::Error>> {
+    if has_manifest(package) && !is_versioned_layout(package) {
+        return Ok(RemoteRelease::Release(fetch_release(package, request)?));
+    }
+    Ok(RemoteRelease::Version(resolve_target_version(package, request)?))
+}
+
+fn print_release_metadata(release: &PackageRelease) {
+    for (key, value) in &release.metadata {
+        match value {
+            serde_json::Value::String(text) => info!("  {}: {}", key, text),
+            other => info!("  {}: {}", key, other),
+        }
+    }
+}
+
+//=-- Installed version and channel as shown to the user, e.g. "2024-01-01--1 (beta)"
+pub fn installed_version_label(package: &Package, output_dir: &Path) -> Option<String> {
+    let version = get_current_version(output_dir, package.version_scheme).ok().flatten()?;
+    Some(format!("{} ({})", version, channel::get_installed_channel(output_dir)))
+}
+
+pub fn process_package(ctx: &LoaderContext, package: &Package, request: &VersionRequest) -> InstallReport {
+    ctx.notify(Event::PackageStarted { package });
+    let report = install_package(ctx, package, |package| fetch_release(package, request));
+    ctx.notify(Event::PackageFinished { package, report: &report });
+    report
+}
+
+//=-- process_package for a release a status check already read from the repository
+pub fn process_checked_package(ctx: &LoaderContext, package: &Package, remote: RemoteRelease) -> InstallReport {
+    ctx.notify(Event::PackageStarted { package });
+    let report = install_package(ctx, package, |package| remote.into_release(package));
+    ctx.notify(Event::PackageFinished { package, report: &report });
+    report
+}
+
+//=-- `fetch` gets the release for the package once it points at its channel
+fn install_package<F>(ctx: &LoaderContext, package: &Package, fetch: F) -> InstallReport
+where
+    F: FnOnce(&Package) -> Result<PackageRelease, Box<dyn std::error::Error>>,
+{
+    let mut report = InstallReport::new(package);
+    let package_output_dir = ctx.output_root.join(&package.output_path);
+    report.installed_version = get_current_version(&package_output_dir, package.version_scheme).ok().flatten()
+        .map(|version| version.to_string());
+
+    //=-- Point the package at its release channel
+    let package = match channel::resolve_package_channel(package, ctx.cli_channel.as_deref(), &ctx.default_channel) {
+        Ok(resolved) => resolved,
+        Err(e) => {
+            error!("{} is not available:\n  {}", package.name, e);
+            report.fail(e);
+            return report;
+        }
+    };
+    let package = &package;
+    report.channel = package.channel.clone();
+
+    //=-- Get and check the version to install and its files before downloading anything
+    let release = match fetch(package) {
+        Ok(release) => release,
+        Err(e) => {
+            error!("{} is not available:\n  {}", package.name, e);
+            report.fail(e);
+            return report;
+        }
+    };
+    report.remote_version = Some(release.version.to_string());
+    info!("{}: {} [{}]", package.name, release.version, package.channel);
+    print_release_metadata(&release);
+    let PackageRelease { version, files, format, .. } = release;
+    let mut package = package.clone();
+    if let Some(format) = format {
+        package.archive_format = format;
+    }
+    let package = &package;
+
+    match confirm_version_change(ctx, package, &package_output_dir, &version) {
+        Some(action) => report.action = action,
+        None => return report,
+    }
+
+    let Some(package_dl_dir) = download_release(ctx, package, &version, &files, &mut report) else {
+        return report;
+    };
+    install_with_hooks(ctx, package, &version, &package_dl_dir, &package_output_dir, &mut report);
+    report
+}
+
+//=-- Downloads the volumes of `version` into the package's download folder, under the names the extractor
+//=-- expects. Returns the folder, or None (with the errors in `report`) when a volume could not be downloaded.
+pub fn download_release(ctx: &LoaderContext, package: &Package, version: &Version, files: &[FileEntry], report: &mut InstallReport) -> Option<PathBuf> {
+    info!("\n{} ({}) files:", package.name, package.id);
+    let package_dl_dir = ctx.dl_dir.join(&package.id);
+
+    //=-- Check every filelist entry against the naming rule before downloading anything. The local names are
+    //=-- joined onto the download folder, so one like "../../x" must not get that far.
+    let local_name = |naming: &VolumeNaming, file: &FileEntry| {
+        let new_filename = naming.local_name(&file.name)?;
+        if !naming::is_plain_name(&new_filename) {
+            return Err(LoaderError::Integrity(format!("Filelist entry \"{}\" is not a plain file name", file.name)).into());
+        }
+        Ok(new_filename)
+    };
+    let local_names = match VolumeNaming::for_package(package)
+        .and_then(|naming| files.iter().map(|file| local_name(&naming, file)).collect::<Result<Vec<String>, Box<dyn std::error::Error>>>())
+    {
+        Ok(local_names) => local_names,
+        Err(e) => {
+            error!("Error: {}", e);
+            report.fail(e);
+            return None;
+        }
+    };
+
+    let mirrors = package_mirrors(ctx, package, version, files);
+    let mut failed = false;
+    for (done, (file, new_filename)) in files.iter().zip(local_names).enumerate() {
+        info!("{}", file.name);
+        
+        //=-- Download under the name the extractor expects
+        let target_path = package_dl_dir.join(&new_filename);
+        match mirrors::download_from_mirrors(ctx, &mirrors, file, &target_path) {
+            Ok((mirror, from_cache)) => {
+                if from_cache {
+                    info!("Using cached copy of {} as: {}", mirror, new_filename);
+                } else {
+                    info!("Downloaded from {} as: {}", mirror, new_filename);
+                }
+                let bytes = fs::metadata(&target_path).map(|metadata| metadata.len()).unwrap_or(0);
+                ctx.notify(Event::VolumeDownloaded { package, volume: &file.name, bytes, from_cache, done: done + 1, total: files.len() });
+            },
+            Err(e) => {
+                error!("Error downloading {}: {}", file.name, e);
+                report.fail(error::context(&format!("Error downloading {}", file.name), e));
+                failed = true;
+            },
+        }
+    }
+
+    if failed {
+        error!("Not installing {}: some volumes could not be downloaded", package.name);
+        let _ = cleanup_package_dir(&package_dl_dir);
+        return None;
+    }
+    Some(package_dl_dir)
+}
+
+//=-- install_volumes between the package's pre_install and post_install hooks
+pub fn install_with_hooks(ctx: &LoaderContext, package: &Package, version: &Version, dl_dir: &Path, output_dir: &Path, report: &mut InstallReport) {
+    let previous = get_current_version(output_dir, package.version_scheme).ok().flatten();
+    let hook_ctx = HookContext { package, version, previous: previous.as_ref(), output_dir, config_dir: &ctx.config_dir };
+    let rollback = hooks::run(Hook::PreInstall, &hook_ctx)
+        .and_then(|_| Rollback::prepare(package, output_dir, &ctx.dl_dir.join(format!("{}.rollback", package.id))));
+    let rollback = match rollback {
+        Ok(rollback) => rollback,
+        Err(e) => {
+            error!("Not installing {}: {}", package.name, e);
+            report.fail(e);
+            let _ = cleanup_package_dir(dl_dir);
+            return;
+        }
+    };
+
+    install_volumes(ctx, package, version, dl_dir, output_dir, report);
+    let hook_result = if report.action == Action::Failed {
+        //=-- Extraction may have replaced part of the output folder before it failed; install_volumes reported why
+        Err(None)
+    } else {
+        hooks::run(Hook::PostInstall, &hook_ctx).map_err(Some)
+    };
+    match (hook_result, rollback) {
+        (Ok(()), Some(rollback)) => rollback.discard(),
+        (Ok(()), None) => {},
+        (Err(e), rollback) => {
+            if let Some(e) = e {
+                error!("{}", e);
+                report.fail(e);
+            }
+            if let Some(Err(e)) = rollback.map(Rollback::restore) {
+                error!("Rollback failed: {}", e);
+                report.fail(error::context("Rollback failed", e));
+            }
+        },
+    }
+}
+
+//=-- Shows the installed version and asks whether moving to `version` is wanted.
+//=-- Returns what installing it would do, or None when it should be skipped.
+fn confirm_version_change(ctx: &LoaderContext, package: &Package, output_dir: &Path, version: &Version) -> Option<Action> {
+    //=-- Check current version and prompt if needed
+    let current_version = match get_current_version(output_dir, package.version_scheme) {
+        Ok(v) => v,
+        Err(e) => {
+            warn!("Failed to read current version: {}", e);
+            None
+        }
+    };
+    if let Some(label) = installed_version_label(package, output_dir) {
+        info!("Installed version: {}", label);
+    }
+
+    match should_update_package(ctx, package, current_version.as_ref(), version) {
+        Ok(true) => {
+            match &current_version {
+                Some(current) if current > version => {
+                    info!("Downgrading to version: {}", version);
+                    Some(Action::Downgraded)
+                },
+                Some(current) if current == version => {
+                    info!("Reloading version: {}", version);
+                    Some(Action::Reinstalled)
+                },
+                Some(_) => {
+                    info!("Updating to version: {}", version);
+                    Some(Action::Updated)
+                },
+                None => {
+                    info!("Installing version: {}", version);
+                    Some(Action::Installed)
+                },
+            }
+        },
+        Ok(false) => {
+            info!("Skipping package update");
+            None
+        },
+        Err(e) => {
+            error!("Error checking version: {}", e);
+            None
+        }
+    }
+}
+
+//=-- Extracts the volumes in `dl_dir` into the output directory and records the installed version
+fn install_volumes(ctx: &LoaderContext, package: &Package, version: &Version, dl_dir: &Path, output_dir: &Path, report: &mut InstallReport) {
+    let naming = match VolumeNaming::for_package(package) {
+        Ok(naming) => naming,
+        Err(e) => {
+            error!("Error: {}", e);
+            report.fail(e);
+            return;
+        }
+    };
+
+    let archives = match find_archives(&naming, package.archive_format, dl_dir) {
+        Ok(archives) => archives,
+        Err(e) => {
+            error!("Error: {}", e);
+            report.fail(e);
+            return;
+        }
+    };
+    //=-- tar archives and unencrypted zips are extracted without asking for a password
+    let needs_password = archives.iter()
+        .map(|archive_set| archive::needs_password(archive_set.format, &archive_set.volumes))
+        .collect::<Result<Vec<bool>, _>>()
+        .map(|needed| needed.contains(&true));
+    let needs_password = match needs_password {
+        Ok(needs_password) => needs_password,
+        Err(e) => {
+            error!("Error reading archive: {}", e);
+            report.fail(error::context("Error reading archive", error::or_kind(ErrorKind::Extraction, e)));
+            return;
+        }
+    };
+
+    //=-- Handle output directory before starting extraction attempts
+    if let Err(e) = handle_output_dir(ctx, output_dir, package) {
+        error!("Error preparing output directory: {}", e);
+        report.fail(error::context("Error preparing output directory", e));
+        return;
+    }
+
+    //=-- Prompt for password and handle retries
+    let mut retry_mode = false;
+    let mut last_password: String;
+    let mut last_error = None;
+    
+    loop {
+        if !needs_password {
+            match extract_archives(ctx, package, &archives, dl_dir, output_dir, "") {
+                Ok(_) => info!("Successfully extracted archives"),
+                Err(e) => {
+                    error!("Error during extraction: {}", e);
+                    report.fail(error::context("Error during extraction", e));
+                },
+            }
+            break;
+        }
+
+        let current_password = if !package.password.is_empty() && !retry_mode {
+            &package.password
+        } else {
+            ctx.notify(Event::PromptNeeded { package, question: &Question::Password { retry: retry_mode } });
+            match ctx.decisions.password(package, retry_mode) {
+                Some(password) => {
+                    last_password = password;
+                    logging::add_secret(&last_password);
+                    &last_password
+                },
+                None => {
+                    warn!("Skipping package due to empty password");
+                    report.fail(last_error.take().unwrap_or_else(|| LoaderError::Cancelled("No password given".to_string()).into()));
+                    break;
+                },
+            }
+        };
+
+        //=-- Extract archives
+        match extract_archives(ctx, package, &archives, dl_dir, output_dir, current_password) {
+            Ok(_) => {
+                info!("Successfully extracted archives");
+                break; //=-- Exit password retry loop on success
+            },
+            Err(e) => {
+                error!("Error during extraction: {}", e);
+                last_error = Some(error::context("Error during extraction", e));
+                if !package.password.is_empty() && !retry_mode {
+                    warn!("Password from config failed, falling back to manual entry");
+                }
+                retry_mode = true;
+            }
+        }
+    }
+
+    //=-- A failed extraction leaves the previous version recorded
+    if report.action == Action::Failed {
+        let _ = cleanup_package_dir(dl_dir);
+        return;
+    }
+
+    //=-- Save version file after all archives are successfully extracted
+    if let Err(e) = save_version_file(version, output_dir) {
+        warn!("Warning: Failed to save version file: {}", e);
+    }
+    if let Err(e) = channel::save_channel_file(&package.channel, output_dir) {
+        warn!("Warning: Failed to save channel file: {}", e);
+    }
+
+    //=-- Clean up downloaded files
+    if let Err(e) = cleanup_package_dir(dl_dir) {
+        error!("Error cleaning up package directory: {}", e);
+    }
+}
+
+//=-- Packages in menu order: root packages first, then alphabetical
+pub fn sorted_packages(settings: &Settings) -> Vec<(&String, &Package)> {
+    let mut package_vec: Vec<(&String, &Package)> = settings.packages.iter().collect();
+    package_vec.sort_by(|a, b| {
+        if a.1.is_root == b.1.is_root {
+            //#-- If both are root or both are not root, sort by name
+            a.1.name.cmp(&b.1.name)
+        } else {
+            //#-- If one is root and the other isn't, root comes first
+            b.1.is_root.cmp(&a.1.is_root)
+        }
+    });
+    package_vec
+}
+
+//=-- Installs the packages that are not installed or have a newer version available. Packages that are
+//=-- up to date or newer locally are left alone without asking.
+pub fn upgrade_packages(ctx: &LoaderContext, packages: &[(&String, &Package)]) -> Vec<InstallReport> {
+    let mut reports = Vec::new();
+    for (_, package) in packages {
+        let (status, remote) = check_package(ctx, package);
+        match (status.state, remote) {
+            (PackageState::Outdated | PackageState::NotInstalled, Some(remote)) => {
+                info!("\nPackage:");
+                reports.push(process_checked_package(ctx, package, remote));
+            },
+            (PackageState::UpToDate | PackageState::NewerLocally, _) => {
+                debug!("{}: {} ({}), skipping", package.name, status.state, status.installed_version.as_deref().unwrap_or("-"));
+                reports.push(InstallReport::unchanged(&status));
+            },
+            _ => {
+                let error = status.error.clone().unwrap_or_default();
+                error!("{} is not available:\n  {}", package.name, error);
+                let mut report = InstallReport::unchanged(&status);
+                report.fail(LoaderError::with_kind(status.error_kind.unwrap_or(ErrorKind::Other), error));
+                reports.push(report);
+            },
+        }
+    }
+    reports
+}
+
+pub fn package_status(ctx: &LoaderContext, package: &Package) -> PackageStatus {
+    check_package(ctx, package).0
+}
+
+//=-- package_status, plus what it read from the repository when that succeeded
+pub fn check_package(ctx: &LoaderContext, package: &Package) -> (PackageStatus, Option<RemoteRelease>) {
+    let output_dir = ctx.output_root.join(&package.output_path);
+    let installed = match get_current_version(&output_dir, package.version_scheme) {
+        Ok(installed) => installed,
+        Err(e) => {
+            warn!("Failed to read current version of {}: {}", package.name, e);
+            None
+        }
+    };
+    let resolved = channel::resolve_package_channel(package, ctx.cli_channel.as_deref(), &ctx.default_channel);
+    let channel = resolved.as_ref().map(|resolved| resolved.channel.clone()).unwrap_or_else(|_| package.channel.clone());
+    let remote = resolved.and_then(|resolved| fetch_remote_release(&resolved, &VersionRequest::Latest));
+    let remote_version = remote.as_ref().map(|remote| remote.version());
+
+    let state = match (&installed, &remote_version) {
+        (_, Err(_)) => PackageState::Unreachable,
+        (None, Ok(_)) => PackageState::NotInstalled,
+        (Some(installed), Ok(remote)) if installed < remote => PackageState::Outdated,
+        (Some(installed), Ok(remote)) if installed > remote => PackageState::NewerLocally,
+        (Some(_), Ok(_)) => PackageState::UpToDate,
+    };
+    let status = PackageStatus {
+        id: package.id.clone(),
+        name: package.name.clone(),
+        channel,
+        installed_channel: installed.as_ref().map(|_| channel::get_installed_channel(&output_dir)),
+        installed_version: installed.map(|version| version.to_string()),
+        remote_version: remote_version.as_ref().ok().map(|version| version.to_string()),
+        error: remote.as_ref().err().map(|e| e.to_string()),
+        error_kind: remote.as_ref().err().map(|e| ErrorKind::of(e.as_ref())),
+        state,
+    };
+    (status, remote.ok())
+}
+
+//=-- The configured packages with what is installed for each, in menu order
+pub fn list_packages(settings: &Settings, output_root: &Path) -> Vec<PackageInfo> {
+    sorted_packages(settings).into_iter()
+        .map(|(_, package)| {
+            let output_dir = output_root.join(&package.output_path);
+            let installed_version = get_current_version(&output_dir, package.version_scheme).ok().flatten();
+            PackageInfo {
+                id: package.id.clone(),
+                name: package.name.clone(),
+                description: package.description.clone(),
+                installed_channel: installed_version.as_ref().map(|_| channel::get_installed_channel(&output_dir)),
+                installed_version: installed_version.map(|version| version.to_string()),
+                pinned_version: Some(package.pinned_version.trim().to_string()).filter(|pinned| !pinned.is_empty()),
+                output_dir: output_dir.display().to_string(),
+            }
+        })
+        .collect()
+}
+
+//=-- Removes the package's output folder between its pre_uninstall and post_uninstall hooks.
+//=-- Returns the version that was removed.
+pub fn uninstall_package(ctx: &LoaderContext, package: &Package) -> Result<Version, Box<dyn std::error::Error>> {
+    if package.is_root {
+        return Err(LoaderError::Config(format!("{} is a root package, its files share the output root and cannot be uninstalled", package.name)).into());
+    }
+    let output_dir = ctx.output_dir(package);
+    let version = get_current_version(&output_dir, package.version_scheme)?
+        .ok_or_else(|| LoaderError::Config(format!("{} is not installed", package.name)))?;
+
+    let mut package = package.clone();
+    package.channel = channel::get_installed_channel(&output_dir);
+    let package = &package;
+    let hook_ctx = HookContext { package, version: &version, previous: Some(&version), output_dir: &output_dir, config_dir: &ctx.config_dir };
+    hooks::run(Hook::PreUninstall, &hook_ctx)?;
+    let rollback = Rollback::prepare(package, &output_dir, &ctx.dl_dir.join(format!("{}.rollback", package.id)))?;
+
+    fs::remove_dir_all(&output_dir)?;
+    info!("Removed {}", output_dir.display());
+    if let Err(e) = hooks::run(Hook::PostUninstall, &hook_ctx) {
+        if let Some(rollback) = rollback {
+            rollback.restore()?;
+        }
+        return Err(e);
+    }
+    if let Some(rollback) = rollback {
+        rollback.discard();
+    }
+    info!("Uninstalled {} {}", package.name, version);
+    Ok(version)
+}
+
+pub fn find_package<'a>(settings: &'a Settings, id: &str) -> Result<&'a Package, Box<dyn std::error::Error>> {
+    settings.packages.values()
+        .find(|package| package.id == id)
+        .ok_or_else(|| LoaderError::Config(format!("Package not found in config: {}", id)).into())
+}
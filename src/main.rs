@@ -1,351 +1,85 @@
-use config::Config;
 use std::collections::HashMap;
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
 use std::fs;
-use std::process::Command;
 use std::env;
-use serde::Deserialize;
-use reqwest::blocking::Client;
-use regex::Regex;
 use indexmap::IndexMap;
 
-#[derive(Debug, Deserialize, Clone)]
-struct Package {
-    id: String,
-    name: String,
-    description: String,
-    version_url: String,
-    filelist_url: String,
-    repo_url: String,
-    output_path: String,
-    password: String,
-    is_root: bool,
-}
-
-#[derive(Debug, Deserialize)]
-struct Settings {
-    archive: HashMap<String, String>,
-    packages: IndexMap<String, Package>,
-    main: HashMap<String, String>,
-}
-
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
-struct Version {
-    date: String,
-    iteration: i32,
-}
-
-impl Version {
-    fn parse(version_str: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let parts: Vec<&str> = version_str.trim().split("--").collect();
-        if parts.len() != 2 {
-            return Err("Invalid version format. Expected YYYY-MM-DD--N".into());
-        }
-
-        let date = parts[0].to_string();
-        let iteration = parts[1].parse::<i32>()?;
-
-        Ok(Version { date, iteration })
-    }
-
-    fn verdate_to_string(&self) -> String {
-        format!("{}--{}", self.date, self.iteration)
-    }
-}
-
-fn get_current_version(output_dir: &Path) -> Result<Option<Version>, Box<dyn std::error::Error>> {
-    let version_file = output_dir.join("version.txt");
-    if !version_file.exists() {
-        return Ok(None);
-    }
-    
-    let content = fs::read_to_string(version_file)?;
-    Ok(Some(Version::parse(&content)?))
-}
-
-fn should_update_package(current: Option<&Version>, new: &Version) -> Result<bool, Box<dyn std::error::Error>> {
-    match current {
-        None => Ok(true),
-        Some(current) => {
-            if current == new {
-                print!("Package version is the same. Reload anyway? (Y/N) [N]: ");
-                io::stdout().flush()?;
-                let mut buffer = String::new();
-                io::stdin().read_line(&mut buffer).unwrap();
-                Ok(buffer.trim().eq_ignore_ascii_case("Y"))
-            } else if current > new {
-                println!("Local version ({}) is newer than repository version ({})", 
-                    current.verdate_to_string(), new.verdate_to_string());
-                print!("Download older version from repository? (Y/N) [N]: ");
-                io::stdout().flush()?;
-                let mut buffer = String::new();
-                io::stdin().read_line(&mut buffer).unwrap();
-                Ok(buffer.trim().eq_ignore_ascii_case("Y"))
-            } else {
-                Ok(true) //=-- If current < new, it should update
-            }
-        }
-    }
-}
-
-fn get_version(url: &str) -> Result<String, Box<dyn std::error::Error>> {
-    let client = Client::new();
-    let response = client.get(url).send()?;
-    if response.status() == reqwest::StatusCode::NOT_FOUND {
-        return Err("Version cannot be retrieved: 404 Not Found".into());
-    }
-    Ok(response.text()?.trim().to_string())
-}
-
-fn get_package_version_string(package: &Package) -> Result<String, Box<dyn std::error::Error>> {
-    let version = get_version(&package.version_url)?;
-    Ok(format!("{}: {}", package.name, version))
-}
-
-fn get_package_files(package: &Package) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-    let client = Client::new();
-    let response = client.get(&package.filelist_url).send()?;
-    if response.status() == reqwest::StatusCode::NOT_FOUND {
-        return Err("File list cannot be retrieved: 404 Not Found".into());
-    }
-    
-    let content = response.text()?;
-    let files: Vec<String> = content
-        .lines()
-        .map(|line| line.trim().to_string())
-        .filter(|line| !line.is_empty())
-        .collect();
-    
-    Ok(files)
-}
-
-fn transform_filename(filename: &str) -> Option<String> {
-    let re = Regex::new(r"--n(\d+)\.globby$").ok()?;
-    let caps = re.captures(filename)?;
-    let number = caps.get(1)?.as_str().parse::<i32>().ok()?;
-    let new_suffix = format!(".7z.{:03}", number);
-    Some(re.replace(filename, new_suffix).to_string())
-}
-
-fn get_base_name(filename: &str) -> Option<String> {
-    filename.split(".7z.").next().map(|s| s.to_string())
-}
-
-fn download_file(url: &str, target_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
-    //=-- Create parent directories if they don't exist
-    if let Some(parent) = target_path.parent() {
-        fs::create_dir_all(parent)?;
-    }
-
-    let client = Client::new();
-    let response = client.get(url).send()?;
-    if response.status() == reqwest::StatusCode::NOT_FOUND {
-        return Err("File cannot be downloaded: 404 Not Found".into());
-    }
-
-    let mut file = fs::File::create(target_path)?;
-    io::copy(&mut response.bytes()?.as_ref(), &mut file)?;
-    Ok(())
-}
-
-fn handle_output_dir(output_dir: &Path, package: &Package) -> Result<(), Box<dyn std::error::Error>> {
-    if output_dir.exists() {
-        if package.is_root {
-            println!("This is a root package, so we are skipping deletion and will overwrite the existing files");
-        } else {
-            print!("(O)verwrite or (D)elete output folder? [O]: ");
-            io::stdout().flush()?;
-            let mut buffer = String::new();
-            io::stdin().read_line(&mut buffer).unwrap();
-            let choice = buffer.trim().to_uppercase();
-            
-            if choice == "D" {
-                fs::remove_dir_all(output_dir)?;
-                fs::create_dir_all(output_dir)?;
-                println!("Deleted and recreated output folder");
-            } else {
-                //=-- Default to overwrite (empty input or "O")
-                println!("Will overwrite existing files");
-            }
-        }
-    } else {
-        fs::create_dir_all(output_dir)?;
-    }
-    Ok(())
+#[macro_use]
+extern crate wb_toolsloader;
+
+mod cli;
+mod tui;
+
+use cli::CliCommand;
+use wb_toolsloader::cache::{format_age, format_size, Cache};
+use wb_toolsloader::error::{ErrorKind, LoaderError};
+use wb_toolsloader::{
+    bundle, changelog, channel, cleanup_package_dir, fetch_target_version, find_package, get_current_version,
+    get_version, installed_version_label, list_packages, load_settings, logging, nanazip_path,
+    output, pack, package_status, process_package, self_update, sorted_packages, uninstall_package, upgrade_packages,
+    watch, Action, InstallReport, LoaderContext, Package, PackageStatus, Settings, Version, VersionRequest,
+    VersionScheme,
+};
+
+//=-- The console front end: command line, menu and prompts. Everything else is in the library (lib.rs).
+
+//=-- Answers the library's prompts on the console
+//=-- If the console can't be read, the answer is empty, which is the prompt's default
+fn ask_console(prompt: &str) -> String {
+    print!("{}", prompt);
+    let _ = io::stdout().flush();
+    let mut buffer = String::new();
+    let _ = io::stdin().read_line(&mut buffer);
+    buffer.trim().to_string()
 }
 
-fn extract_archives(nanazip_path: &Path, package_dir: &Path, output_dir: &Path, password: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let archives: Vec<_> = fs::read_dir(package_dir)?
-        .filter_map(|entry| entry.ok())
-        .filter(|entry| {
-            entry.path().extension()
-                .and_then(|ext| ext.to_str())
-                .map(|ext| ext.starts_with("001"))
-                .unwrap_or(false)
-        })
-        .collect();
-    for archive in archives {
-        let archive_path = archive.path();
-        if let Some(base_name) = get_base_name(archive_path.file_name().unwrap().to_str().unwrap()) {
-            let extract_dir = package_dir.join(&base_name);
-            fs::create_dir_all(&extract_dir)?;
-
-            let mut cmd = Command::new(nanazip_path);
-            cmd.current_dir(package_dir)
-               .arg("x")
-               .arg("-y") //=-- Force yes on all queries
-               .arg(&archive_path)
-               .arg(format!("-o{}", extract_dir.display()));
-
-            if !password.is_empty() {
-                cmd.arg(format!("-p{}", password));
-            }
-
-            match cmd.output() {
-                Ok(output) => {
-                    if !output.status.success() {
-                        let error_msg = String::from_utf8_lossy(&output.stderr);
-                        if error_msg.contains("Wrong password?") {
-                            return Err("Wrong password".into());
-                        }
-                        return Err(format!(
-                            "Failed to extract {}: {}", 
-                            archive_path.display(),
-                            error_msg
-                        ).into());
-                    }
-                    println!("Extracted {} to {}", archive_path.display(), extract_dir.display());
-
-                    //=-- Move extracted files to output directory
-                    fs::create_dir_all(output_dir)?;
-                    let normalized_output_dir = normalize_path_buf(output_dir)
-                        .map_err(|e| format!("Failed to normalize output path {}: {}", output_dir.display(), e))?;
-                    
-                    println!("Normalized output directory: {}", normalized_output_dir.display());
-                    
-                    for entry in fs::read_dir(&extract_dir)? {
-                        let entry = entry?;
-                        let source_path = entry.path();
-                        let target_path = normalized_output_dir.join(entry.file_name());
-                        
-                        println!("Processing: {} -> {}", source_path.display(), target_path.display());
-                        
-                        //=-- If target exists, try to remove it first
-                        if target_path.exists() {
-                            if target_path.is_dir() {
-                                retry_file_operation(
-                                    || fs::remove_dir_all(&target_path).map_err(|e| Box::new(e) as Box<dyn std::error::Error>),
-                                    3,
-                                    100
-                                ).map_err(|e| format!("Failed to remove existing directory {}: {}", target_path.display(), e))?;
-                            } else {
-                                retry_file_operation(
-                                    || fs::remove_file(&target_path).map_err(|e| Box::new(e) as Box<dyn std::error::Error>),
-                                    3,
-                                    100
-                                ).map_err(|e| format!("Failed to remove existing file {}: {}", target_path.display(), e))?;
-                            }
-                        }
-                        
-                        //=-- Try to move the file with retries
-                        retry_file_operation(
-                            || {
-                                if source_path.is_dir() {
-                                    match fs::rename(&source_path, &target_path) {
-                                        Ok(()) => Ok(()),
-                                        Err(_) => {
-                                            // For directories, try copy_dir_all if rename fails
-                                            copy_dir_all(&source_path, &target_path)
-                                                .and_then(|_| fs::remove_dir_all(&source_path))
-                                                .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
-                                        }
-                                    }
-                                } else {
-                                    match fs::rename(&source_path, &target_path) {
-                                        Ok(()) => Ok(()),
-                                        Err(_) => {
-                                            fs::copy(&source_path, &target_path)
-                                                .and_then(|_| fs::remove_file(&source_path))
-                                                .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
-                                        }
-                                    }
-                                }
-                            },
-                            3,
-                            100
-                        ).map_err(|e| format!("Failed to move {} to {}: {}", source_path.display(), target_path.display(), e))?;
-                    }
-                    println!("Moved files to {}", output_dir.display());
+//=-- For `pack --encrypt` when the package has no password in the config
+fn prompt_new_password() -> Result<String, Box<dyn std::error::Error>> {
+    let read_password = |prompt: &str| -> io::Result<String> {
+        print!("{}", prompt);
+        io::stdout().flush()?;
+        let mut buffer = String::new();
+        io::stdin().read_line(&mut buffer)?;
+        Ok(buffer.trim().to_string())
+    };
 
-                    //=-- Clean up extraction directory
-                    fs::remove_dir_all(&extract_dir)?;
-                },
-                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
-                    println!("!==-- The config for NanaZip's location is incorrect. NanaZip executable not found. --==!");
-                    return Err(Box::new(e));
-                },
-                Err(e) => return Err(Box::new(e)),
-            }
-        }
+    let password = read_password("Enter password for the archive: ")?;
+    if password.is_empty() {
+        return Err("An empty password cannot be used with --encrypt".into());
     }
-    Ok(())
-}
-
-fn save_version_file(version: &Version, output_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
-    let version_file = output_dir.join("version.txt");
-    let version_str = format!("{}--{}", version.date, version.iteration);
-    fs::write(version_file, version_str)?;
-    Ok(())
-}
-
-fn cleanup_package_dir(dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
-    if dir.exists() {
-        fs::remove_dir_all(dir)?;
+    if read_password("Repeat the password: ")? != password {
+        return Err("Passwords do not match".into());
     }
-    Ok(())
+    logging::add_secret(&password);
+    Ok(password)
 }
 
-fn get_local_version(exe_dir: &Path) -> Result<Option<Version>, Box<dyn std::error::Error>> {
-    let version_path = exe_dir.join("version.txt");
-    if version_path.exists() {
-        let version_str = fs::read_to_string(version_path)?;
-        Ok(Some(Version::parse(&version_str)?))
-    } else {
-        Ok(None)
+fn prompt_continue_or_quit() -> bool {
+    //=-- An unattended run always continues; the warning before this prompt is in the log
+    if output::is_unattended() {
+        return true;
     }
+    output::ask("Would you like to (C)ontinue or (Q)uit? [Q]: ").eq_ignore_ascii_case("c")
 }
 
-fn prompt_continue_or_quit() -> bool {
-    print!("Would you like to (C)ontinue or (Q)uit? [Q]: ");
-    io::stdout().flush().unwrap();
-    let mut buffer = String::new();
-    io::stdin().read_line(&mut buffer).unwrap();
-    
-    buffer.trim().eq_ignore_ascii_case("c")
+//=-- Answering Q to prompt_continue_or_quit
+fn quit_error() -> Box<dyn std::error::Error> {
+    LoaderError::Cancelled("Stopped at the user's request".to_string()).into()
 }
 
 fn prompt_yes_no(prompt: &str) -> bool {
-    print!("{}? (Y/N) [N]: ", prompt);
-    io::stdout().flush().unwrap();
-    let mut buffer = String::new();
-    io::stdin().read_line(&mut buffer).unwrap();
-    
-    buffer.trim().eq_ignore_ascii_case("y")
+    output::ask(&format!("{}? (Y/N) [N]: ", prompt)).eq_ignore_ascii_case("y")
 }
 
 fn normalize_path(path_str: &str) -> String {
-    path_str.trim_end_matches(|c| c == '/' || c == '\\').to_string()
+    path_str.trim_end_matches(['/', '\\']).to_string()
 }
 
 fn prompt_for_path(config_dir: &Path) -> Option<PathBuf> {
     loop {
-        print!("Enter path or leave blank for the \"output\" folder relative to this application (or E to exit): ");
-        io::stdout().flush().unwrap();
-        let mut buffer = String::new();
-        io::stdin().read_line(&mut buffer).unwrap();
-        
-        let input = buffer.trim();
+        let input = output::ask("Enter path or leave blank for the \"output\" folder relative to this application (or E to exit): ");
+        let input = input.as_str();
         if input.eq_ignore_ascii_case("e") || input.eq_ignore_ascii_case("exit") {
             return None;
         }
@@ -381,7 +115,7 @@ fn resolve_output_root(config_dir: &Path, settings: &Settings) -> Option<PathBuf
     if path.exists() {
         Some(path)
     } else {
-        println!("Output root path does not exist: {}", path.display());
+        warn!("Output root path does not exist: {}", path.display());
         if prompt_yes_no("Would you like to enter a different path") {
             prompt_for_path(config_dir)
         } else {
@@ -409,183 +143,433 @@ fn cleanup_temp_dir(dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-use std::thread;
-use std::time::Duration;
-
-fn retry_file_operation<F, T>(mut operation: F, retries: u32, delay_ms: u64) -> Result<T, Box<dyn std::error::Error>>
-where
-    F: FnMut() -> Result<T, Box<dyn std::error::Error>>,
-{
-    let mut last_error = None;
-    for _ in 0..retries {
-        match operation() {
-            Ok(result) => return Ok(result),
-            Err(e) => {
-                last_error = Some(e);
-                thread::sleep(Duration::from_millis(delay_ms));
+//=-- Explicit `self-update` command: installs the release even when self_update is turned off in the config
+fn run_self_update(exe_path: &Path, settings: &Settings, temp_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let release_url = self_update::release_url(settings)
+        .ok_or_else(|| LoaderError::Config("release_url not found in config, cannot self-update".to_string()))?;
+    let local_version = self_update::installed_version(exe_path);
+    let remote_version = self_update::release_version(&release_url)?;
+    if let Some(local) = &local_version {
+        if local >= &remote_version {
+            info!("WarpBits Tools Loader is up to date, running version: {}", local);
+            return Ok(());
+        }
+    }
+
+    self_update::update(&release_url, &remote_version, exe_path, temp_dir)?;
+    info!("WarpBits Tools Loader updated to version: {}", remote_version);
+    Ok(())
+}
+
+//=-- Compares the loader with [main] version_url. Self-updates (and restarts) when it is out of date and
+//=-- self-update is on, otherwise asks whether to continue.
+fn check_loader_version(exe_path: &Path, settings: &Settings, temp_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let local_version = self_update::installed_version(exe_path);
+    let version_url = settings.main.get("version_url")
+        .ok_or_else(|| LoaderError::Config("version_url not found in config".to_string()))?;
+    let remote_version = Version::parse(&get_version(version_url)?, VersionScheme::DateIteration)?;
+
+    match local_version {
+        Some(local) => {
+            if local < remote_version {
+                match self_update::release_url(settings).filter(|_| self_update::is_enabled(settings)) {
+                    Some(release_url) => {
+                        //=-- version_url and release_url can disagree; only restart into a release that is actually newer,
+                        //=-- otherwise every start would reinstall the same release and restart again
+                        let updated = self_update::release_version(&release_url).and_then(|release| {
+                            if release <= local {
+                                return Err(format!("release_url still offers version {}", release).into());
+                            }
+                            info!("WarpBits Tools Loader is out of date, updating to version: {}", release);
+                            self_update::update(&release_url, &release, exe_path, temp_dir)
+                        });
+                        match updated {
+                            Ok(()) => self_update::restart(exe_path, temp_dir),
+                            Err(e) => {
+                                warn!("WarpBits Tools Loader is out of date (remote version: {}), but self-update failed: {}", remote_version, e);
+                                if !prompt_continue_or_quit() {
+                                    return Err(quit_error());
+                                }
+                            }
+                        }
+                    },
+                    None => {
+                        warn!("WarpBits Tools Loader is out of date, please download the new version: {}", remote_version);
+                        if !prompt_continue_or_quit() {
+                            return Err(quit_error());
+                        }
+                    }
+                }
+            } else if local > remote_version {
+                warn!("WarpBits Tools Loader's version is in the future.\nYou may want to download a fresh copy.\nCurrent: {}. Remote: {}", 
+                    local, remote_version);
+                if !prompt_continue_or_quit() {
+                    return Err(quit_error());
+                }
+            } else {
+                info!("WarpBits Tools Loader is up to date, running version: {}", local);
+            }
+        },
+        None => {
+            warn!("WarpBits Tools Loader version file not found, please download a fresh copy. Remote version: {}", remote_version);
+            if !prompt_continue_or_quit() {
+                return Err(quit_error());
             }
         }
     }
-    Err(last_error.unwrap())
+    Ok(())
+}
+
+//=-- What the package menu's prompt picked
+#[derive(Clone, Copy)]
+enum MenuSelection {
+    All,
+    Upgrade,
+    Package(usize),
 }
 
-// Helper function to recursively copy directories
-fn copy_dir_all(src: &Path, dst: &Path) -> io::Result<()> {
-    if !dst.exists() {
-        fs::create_dir_all(dst)?;
+fn print_upgrade_summary(reports: &[InstallReport]) {
+    println!("\nUpgrade summary:");
+    let version = |version: &Option<String>| version.clone().unwrap_or_else(|| "-".to_string());
+    for report in reports {
+        match report.action {
+            Action::Skipped => continue,
+            Action::Installed => println!("  {}: installed {}", report.id, version(&report.remote_version)),
+            Action::Failed => println!("  {}: failed: {}", report.id, report.errors.join("; ")),
+            action => println!("  {}: {} {} -> {}", report.id, action, version(&report.installed_version), version(&report.remote_version)),
+        }
     }
+    let unchanged = reports.iter().filter(|report| report.action == Action::Skipped).count();
+    println!("  {} package(s) unchanged", unchanged);
+}
 
-    for entry in fs::read_dir(src)? {
-        let entry = entry?;
-        let ty = entry.file_type()?;
-        let source = entry.path();
-        let destination = dst.join(entry.file_name());
+//=-- `list` command: the configured packages with what is installed for each
+fn run_list(settings: &Settings, output_root: &Path) {
+    let packages = list_packages(settings, output_root);
 
-        if ty.is_dir() {
-            copy_dir_all(&source, &destination)?;
-        } else {
-            fs::copy(&source, &destination)?;
+    if output::is_json() {
+        output::emit("list", &packages);
+        return;
+    }
+    for info in &packages {
+        let mut line = format!("{} ({}): {}", info.name, info.id, info.description);
+        match (&info.installed_version, &info.installed_channel) {
+            (Some(version), Some(channel)) => line.push_str(&format!(" [installed: {} ({})]", version, channel)),
+            _ => line.push_str(" [not installed]"),
+        }
+        if let Some(pinned) = &info.pinned_version {
+            line.push_str(&format!(" [pinned: {}]", pinned));
+        }
+        println!("{}", line);
+    }
+}
+
+//=-- `status` command: installed and available version of every package, without changing anything
+fn run_status(ctx: &LoaderContext, settings: &Settings) {
+    let statuses: Vec<PackageStatus> = sorted_packages(settings).into_iter()
+        .map(|(_, package)| package_status(ctx, package))
+        .collect();
+
+    if output::is_json() {
+        output::emit("status", &statuses);
+        return;
+    }
+    let name_width = statuses.iter().map(|status| status.name.len()).max().unwrap_or(0).max("Package".len());
+    println!("{:<name_width$}  {:<24}  {:<24}  State", "Package", "Installed", "Available");
+    for status in &statuses {
+        let installed = match (&status.installed_version, &status.installed_channel) {
+            (Some(version), Some(channel)) => format!("{} ({})", version, channel),
+            _ => "-".to_string(),
+        };
+        let available = status.remote_version.as_ref()
+            .map(|version| format!("{} ({})", version, status.channel))
+            .unwrap_or_else(|| "-".to_string());
+        println!("{:<name_width$}  {:<24}  {:<24}  {}", status.name, installed, available, status.state);
+        if let Some(error) = &status.error {
+            println!("{:<name_width$}  {}", "", error);
+        }
+    }
+}
+
+//=-- `uninstall <id>` command: asks first, then removes the package (see uninstall_package)
+fn run_uninstall(ctx: &LoaderContext, package: &Package) -> Result<(), Box<dyn std::error::Error>> {
+    //=-- Root packages and packages that aren't installed are refused by uninstall_package without asking
+    let output_dir = ctx.output_dir(package);
+    if let (false, Some(version)) = (package.is_root, get_current_version(&output_dir, package.version_scheme)?) {
+        if !prompt_yes_no(&format!("Uninstall {} {} from {}", package.name, version, output_dir.display())) {
+            return Err(LoaderError::Cancelled("Uninstall cancelled".to_string()).into());
         }
     }
+    uninstall_package(ctx, package)?;
     Ok(())
 }
 
-fn normalize_path_buf(path: &Path) -> io::Result<PathBuf> {
-    let mut components = Vec::new();
-    for component in path.components() {
-        match component {
-            std::path::Component::ParentDir => {
-                if !components.is_empty() {
-                    components.pop();
-                }
-            },
-            std::path::Component::Normal(name) => components.push(name),
-            std::path::Component::RootDir => components.push(component.as_os_str()),
-            std::path::Component::Prefix(prefix) => components.push(prefix.as_os_str()),
-            _ => {}
+//=-- `cache` command without --clear
+fn print_cache(cache: &Cache) {
+    println!("Download cache: {}", cache.dir().display());
+    let entries = cache.entries();
+    if entries.is_empty() {
+        println!("(empty)");
+        return;
+    }
+
+    for entry in &entries {
+        println!("{}  {:>10}  used {}", &entry.sha256[..12.min(entry.sha256.len())], format_size(entry.size), format_age(entry.age_secs));
+        for url in &entry.urls {
+            println!("    {}", url);
         }
     }
-    let mut result = PathBuf::new();
-    for component in components {
-        result.push(component);
+    let total: u64 = entries.iter().map(|entry| entry.size).sum();
+    println!("{} object(s), {} of {}", entries.len(), format_size(total), format_size(cache.max_bytes()));
+}
+
+//=-- `changelog <id>` command: notes between the installed version and the version an install would fetch
+fn run_changelog(ctx: &LoaderContext, package: &Package) -> Result<(), Box<dyn std::error::Error>> {
+    let package = channel::resolve_package_channel(package, ctx.cli_channel.as_deref(), &ctx.default_channel)?;
+    if package.changelog_url.trim().is_empty() {
+        return Err(LoaderError::Config(format!("{} has no changelog_url in config", package.name)).into());
+    }
+
+    let target = fetch_target_version(&package, &VersionRequest::Latest)?;
+    let installed = get_current_version(&ctx.output_root.join(&package.output_path), package.version_scheme)?;
+    match &installed {
+        Some(installed) => println!("{} [{}]: installed {}, available {}", package.name, package.channel, installed, target),
+        None => println!("{} [{}]: not installed, available {}", package.name, package.channel, target),
+    }
+
+    let entries = changelog::fetch_changelog(&package)?;
+    let selected = changelog::entries_between(&entries, installed.as_ref(), &target);
+    if selected.is_empty() {
+        println!("No release notes between the installed and the available version");
+    } else {
+        print!("{}", changelog::format_entries(&selected));
     }
-    Ok(result)
+    Ok(())
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let cli = match cli::parse_args(&args) {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("{}\n{}", e, cli::USAGE);
+            std::process::exit(ErrorKind::Usage.exit_code());
+        }
+    };
+
+    if let Some(verbosity) = cli.verbosity {
+        logging::set_verbosity(verbosity);
+    }
+    if cli.json {
+        output::enable_json();
+    }
+    if let CliCommand::Watch { .. } = cli.command {
+        output::set_unattended();
+    }
+    output::set_prompter(ask_console);
+
     let temp_dir = resolve_temp_dir(&Settings {
         archive: HashMap::new(),
         packages: IndexMap::new(),
         main: HashMap::new(),
+        mirrors: IndexMap::new(),
     });
 
-    let result = (|| -> Result<(), Box<dyn std::error::Error>> {
+    //=-- Ok(Some(kind)) when the run finished but packages failed
+    let result = (|| -> Result<Option<ErrorKind>, Box<dyn std::error::Error>> {
         loop {
-            let exe_path = std::env::current_exe().expect("Failed to get executable path");
-            let config_dir = exe_path.parent().expect("Failed to get executable directory");
-            let config_path = config_dir.join("Config.toml");
+            let exe_path = std::env::current_exe()
+                .map_err(|e| LoaderError::Filesystem(format!("Failed to get executable path: {}", e)))?;
+            let config_path = match &cli.config {
+                Some(path) => path.clone(),
+                None => exe_path.parent()
+                    .ok_or_else(|| LoaderError::Filesystem("Failed to get executable directory".to_string()))?
+                    .join("Config.toml"),
+            };
+            let config_dir = config_path.parent()
+                .ok_or_else(|| LoaderError::Config(format!("Failed to get config directory of {}", config_path.display())))?;
             let dl_dir = temp_dir.join("dl");
             
-            println!("Looking for config at: {:?}", config_path);
-
-            let settings: Settings = Config::builder()
-                //=-- Override with local Config.toml next to executable
-                .add_source(config::File::with_name(config_path.to_str().unwrap()).required(false))
-                //=-- Add environment variable source with prefix WBTL
-                .add_source(config::Environment::with_prefix("WBTL").separator("__"))
-                  //=-- Ex: WBTL_ARCHIVE__NANAZIP_EXE=path/to/nanazip.exe
-                  //=-- Ex: WBTL_PACKAGES__MYPACKAGE__OUTPUT_PATH=path/to/output
-                .build()
-                .unwrap()
-                .try_deserialize()
-                .unwrap();
-
-            //=-- Get NanaZip path from config and resolve it relative to the executable directory
-            let nanazip_relative_path = settings.archive.get("nanazip_exe")
-                .expect("nanazip_exe not found in config");
-            let nanazip_path = config_dir.join(nanazip_relative_path);
+            info!("Looking for config at: {:?}", config_path);
+
+            let settings = load_settings(&config_path)?;
+            if cli.verbosity.is_none() {
+                if let Some(verbosity) = settings.main.get("log_level").and_then(|name| logging::Verbosity::from_name(name)) {
+                    logging::set_verbosity(verbosity);
+                }
+            }
+            match logging::open_file(&settings, config_dir) {
+                Ok(Some(path)) => debug!("Logging to {}", path.display()),
+                Ok(None) => {},
+                Err(e) => warn!("Log file cannot be opened: {}", e),
+            }
+            for package in settings.packages.values() {
+                logging::add_secret(&package.password);
+            }
+
+            if let CliCommand::SelfUpdate = cli.command {
+                return run_self_update(&exe_path, &settings, &temp_dir).map(|_| None);
+            }
+
+            if let CliCommand::Cache { clear } = cli.command {
+                let cache = Cache::from_settings(&settings, config_dir)
+                    .ok_or_else(|| LoaderError::Config("The download cache is turned off (cache_max_mb = 0)".to_string()))?;
+                if clear {
+                    cache.clear()?;
+                } else {
+                    print_cache(&cache);
+                }
+                return Ok(None);
+            }
+
+            if let CliCommand::Pack(options) = &cli.command {
+                let package = find_package(&settings, &options.id)?;
+                let mut options = options.clone();
+                if options.encrypt && package.password.is_empty() {
+                    options.password = prompt_new_password()?;
+                }
+                return pack::pack(&nanazip_path(&settings, config_dir)?, package, &options).map(|_| None);
+            }
 
             //=-- Resolve output root path
             let output_root = match resolve_output_root(config_dir, &settings) {
                 Some(path) => path,
-                None => return Ok(()),
+                None if output::is_unattended() => return Err(LoaderError::Config("No output root".to_string()).into()),
+                None => return Err(LoaderError::Cancelled("No output root chosen".to_string()).into()),
             };
-            println!("Using output root: {}", output_root.display());
+            info!("Using output root: {}", output_root.display());
 
-            //=-- Check version
-            let local_version = get_local_version(config_dir).unwrap_or(None);
-            let remote_version_str = get_version(&settings.main.get("version_url").expect("version_url not found in config")).unwrap_or_default();
-            let remote_version = Version::parse(&remote_version_str).unwrap();
+            if let CliCommand::List = cli.command {
+                run_list(&settings, &output_root);
+                return Ok(None);
+            }
 
-            match local_version {
-                Some(local) => {
-                    if local < remote_version {
-                        println!("WarpBits Tools Loader is out of date, please download the new version: {}", remote_version.verdate_to_string());
-                        if !prompt_continue_or_quit() {
-                            return Ok(());
-                        }
-                    } else if local > remote_version {
-                        println!("WarpBits Tools Loader's version is in the future.\nYou may want to download a fresh copy.\nCurrent: {}. Remote: {}", 
-                            local.verdate_to_string(), remote_version.verdate_to_string());
-                        if !prompt_continue_or_quit() {
-                            return Ok(());
-                        }
-                    } else {
-                        println!("WarpBits Tools Loader is up to date, running version: {}", local.verdate_to_string());
+            //=-- Bundles are for offline machines and status only reports, so neither needs the loader's version_url
+            //=-- watch runs as a service and must survive outages, so a failed check is only logged
+            match cli.command {
+                CliCommand::ExportBundle { .. } | CliCommand::ImportBundle { .. } | CliCommand::Status => {},
+                CliCommand::Watch { .. } => {
+                    if let Err(e) = check_loader_version(&exe_path, &settings, &temp_dir) {
+                        warn!("Could not check the loader's version, watching anyway: {}", e);
                     }
                 },
-                None => {
-                    println!("WarpBits Tools Loader version file not found, please download a fresh copy. Remote version: {}", remote_version.verdate_to_string());
-                    if !prompt_continue_or_quit() {
-                        return Ok(());
-                    }
-                }
+                _ => check_loader_version(&exe_path, &settings, &temp_dir)?,
+            }
+
+            let mut ctx = LoaderContext::new(&settings, config_dir, output_root, dl_dir)?;
+            ctx.cli_channel = cli.channel.clone();
+
+            match &cli.command {
+                CliCommand::ExportBundle { path, ids } => {
+                    let result = bundle::export_bundle(&ctx, &settings, path, ids);
+                    let _ = cleanup_package_dir(&ctx.dl_dir);
+                    return result;
+                },
+                CliCommand::ImportBundle { path, ids } => {
+                    let result = bundle::import_bundle(&ctx, &settings, path, ids);
+                    let _ = cleanup_package_dir(&ctx.dl_dir);
+                    return result;
+                },
+                _ => {},
+            }
+
+            if let CliCommand::Watch { once } = cli.command {
+                return watch::run(&ctx, &settings, config_dir, once);
             }
 
-            //=-- Convert the packages to a sorted vec (root packages first, then alphabetical)
-            let mut package_vec: Vec<(&String, &Package)> = settings.packages.iter().collect();
-            package_vec.sort_by(|a, b| {
-                if a.1.is_root == b.1.is_root {
-                    //#-- If both are root or both are not root, sort by name
-                    a.1.name.cmp(&b.1.name)
+            if let CliCommand::Upgrade = cli.command {
+                let reports = upgrade_packages(&ctx, &sorted_packages(&settings));
+                if let Err(e) = cleanup_package_dir(&ctx.dl_dir) {
+                    error!("Error cleaning up download directory: {}", e);
+                }
+                if cli.json {
+                    output::emit(cli.command.name(), &reports);
                 } else {
-                    //#-- If one is root and the other isn't, root comes first
-                    b.1.is_root.cmp(&a.1.is_root)
+                    print_upgrade_summary(&reports);
                 }
-            });
+                return Ok(ErrorKind::combined(reports.iter().filter_map(|report| report.error_kind)));
+            }
+
+            if let CliCommand::Status = cli.command {
+                run_status(&ctx, &settings);
+                return Ok(None);
+            }
+
+            if let CliCommand::Uninstall { id } = &cli.command {
+                let result = run_uninstall(&ctx, find_package(&settings, id)?);
+                let _ = cleanup_package_dir(&ctx.dl_dir);
+                return result.map(|_| None);
+            }
+
+            if let CliCommand::Changelog { id } = &cli.command {
+                return run_changelog(&ctx, find_package(&settings, id)?).map(|_| None);
+            }
+
+            if let CliCommand::Install { id, request } = &cli.command {
+                let package = find_package(&settings, id)?;
+                info!("\nPackage:");
+                let report = process_package(&ctx, package, request);
+                if let Err(e) = cleanup_package_dir(&ctx.dl_dir) {
+                    error!("Error cleaning up download directory: {}", e);
+                }
+                let failure = report.error_kind;
+                if cli.json {
+                    output::emit(cli.command.name(), &[report]);
+                }
+                return Ok(failure);
+            }
+
+            let package_vec = sorted_packages(&settings);
 
             if package_vec.is_empty() {
-                println!("No packages found in config!");
-                return Ok(());
+                warn!("No packages found in config!");
+                return Ok(None);
             }
 
-            let selected_index = loop {
+            if tui::is_supported(cli.no_tui) {
+                return tui::run(&mut ctx, &package_vec);
+            }
+
+            let selection = loop {
                 //=-- Display numbered list
                 println!("\nAvailable packages:");
                 println!("A. All packages");
+                println!("U. Upgrade (only packages that are outdated or not installed)");
                 for (i, (_, package)) in package_vec.iter().enumerate() {
-                    println!("{}. {}: {}", i + 1, package.name, package.description);
+                    let mut line = format!("{}. {}: {}", i + 1, package.name, package.description);
+                    if let Some(label) = installed_version_label(package, &ctx.output_dir(package)) {
+                        line.push_str(&format!(" [installed: {}]", label));
+                    }
+                    if !package.pinned_version.trim().is_empty() {
+                        line.push_str(&format!(" [pinned: {}]", package.pinned_version.trim()));
+                    }
+                    println!("{}", line);
                 }
                 println!("E. Exit");
 
                 //=-- Get user input from the console
-                print!("\nSelect a package number (A for all, E to exit): ");
-                io::stdout().flush().unwrap();
+                print!("\nSelect a package number (A for all, U to upgrade, E to exit): ");
+                io::stdout().flush()?;
                 let mut buffer = String::new();
-                io::stdin().read_line(&mut buffer).unwrap();
+                //=-- End of input exits, instead of repeating the menu forever
+                if io::stdin().read_line(&mut buffer)? == 0 {
+                    return Ok(None);
+                }
                 
                 let input = buffer.trim();
                 
                 //=-- Parse selection
                 if input.eq_ignore_ascii_case("e") || input.eq_ignore_ascii_case("exit") {
-                    return Ok(());
+                    return Ok(None);
                 } else if input.eq_ignore_ascii_case("a") || input.eq_ignore_ascii_case("all") {
-                    break None; //=-- All packages = None
+                    break MenuSelection::All;
+                } else if input.eq_ignore_ascii_case("u") || input.eq_ignore_ascii_case("upgrade") {
+                    break MenuSelection::Upgrade;
                 } else {
                     //=-- Parse and validate number, handling cases like "1." or "1.0"
                     match input.split('.').next().and_then(|s| s.parse::<usize>().ok()) {
                         Some(n) if n > 0 && n <= package_vec.len() => {
-                            break Some(n - 1);
+                            break MenuSelection::Package(n - 1);
                         }
                         _ => {
                             println!("Invalid selection! ({})", input);
@@ -596,217 +580,61 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             };
 
             //=-- Process selected package(s)
-            println!("\n{}:", if selected_index.is_some() { "Package" } else { "Packages" });
-            for (i, (_, package)) in package_vec.iter().enumerate() {
-                if let Some(idx) = selected_index {
-                    if i != idx {
-                        continue;
-                    }
-                }
-                
-                //=-- Print version and check availability
-                let is_available = match get_package_version_string(package) {
-                    Ok(version_string) => {
-                        println!("{}", version_string);
-                        true
-                    },
-                    Err(e) => {
-                        println!("{} is not available:\n  {}", package.name, e);
-                        false
+            let mut reports = Vec::new();
+            if let MenuSelection::Upgrade = selection {
+                reports = upgrade_packages(&ctx, &package_vec);
+                print_upgrade_summary(&reports);
+            } else {
+                println!("\n{}:", if let MenuSelection::Package(_) = selection { "Package" } else { "Packages" });
+                for (i, (_, package)) in package_vec.iter().enumerate() {
+                    if let MenuSelection::Package(idx) = selection {
+                        if i != idx {
+                            continue;
+                        }
                     }
-                };
-
-                //=-- Only proceed with file listing if version was available
-                if is_available {
-                    //=-- Get and print files
-                    println!("\n{} ({}) files:", package.name, package.id);
-                    match get_package_files(package) {
-                        Ok(files) => {
-                            let repo_url = if package.repo_url.ends_with('/') {
-                                package.repo_url.clone()
-                            } else {
-                                format!("{}\\", package.repo_url)
-                            };
-                            
-                            let package_dl_dir = dl_dir.join(&package.id);
-                            let package_output_dir = output_root.join(&package.output_path);
-
-                            //=-- Get and check version before downloading files
-                            let version = match get_version(&package.version_url) {
-                                Ok(v) => match Version::parse(&v) {
-                                    Ok(parsed) => parsed,
-                                    Err(e) => {
-                                        println!("Failed to parse version: {}", e);
-                                        continue;
-                                    }
-                                },
-                                Err(e) => {
-                                    println!("Failed to get version: {}", e);
-                                    continue;
-                                }
-                            };
-
-                            //=-- Check current version and prompt if needed
-                            let current_version = match get_current_version(&package_output_dir) {
-                                Ok(v) => v,
-                                Err(e) => {
-                                    println!("Failed to read current version: {}", e);
-                                    None
-                                }
-                            };
-
-                            match should_update_package(current_version.as_ref(), &version) {
-                                Ok(true) => {
-                                    if let Some(current) = &current_version {
-                                        if current > &version {
-                                            println!("Downgrading to version: {}", version.verdate_to_string());
-                                        } else {
-                                            println!("Updating to version: {}", version.verdate_to_string());
-                                        }
-                                    } else {
-                                        println!("Installing version: {}", version.verdate_to_string());
-                                    }
-                                },
-                                Ok(false) => {
-                                    println!("Skipping package update");
-                                    continue;
-                                },
-                                Err(e) => {
-                                    println!("Error checking version: {}", e);
-                                    continue;
-                                }
-                            };
-                            
-                            for file in files {
-                                let file_url = format!("{}{}", repo_url, file);
-                                println!("{}", file_url);
-                                
-                                //=-- Transform filename and download
-                                if let Some(new_filename) = transform_filename(&file) {
-                                    let target_path = package_dl_dir.join(&new_filename);
-                                    match download_file(&file_url, &target_path) {
-                                        Ok(_) => println!("Downloaded as: {}", new_filename),
-                                        Err(e) => println!("Error downloading {}: {}", file, e),
-                                    }
-                                } else {
-                                    println!("Error: Could not transform filename: {}", file);
-                                }
-                            }
 
-                            //=-- Handle output directory before starting extraction attempts
-                            if let Err(e) = handle_output_dir(&package_output_dir, package) {
-                                println!("Error preparing output directory: {}", e);
-                                continue;
-                            }
-
-                            //=-- Prompt for password and handle retries
-                            let mut retry_mode = false;
-                            let mut last_password = String::new();
-                            
-                            loop {
-                                let current_password = if !package.password.is_empty() && !retry_mode {
-                                    &package.password
-                                } else if retry_mode {
-                                    print!("\nEnter password for extraction (press Enter [on a blank entry] to skip this package): ");
-                                    io::stdout().flush().unwrap();
-                                    let mut buffer = String::new();
-                                    io::stdin().read_line(&mut buffer).unwrap();
-                                    let password = buffer.trim();
-                                    if password.is_empty() {
-                                        println!("Skipping package due to empty password");
-                                        break;
-                                    }
-                                    last_password = password.to_string();
-                                    &last_password
-                                } else if !last_password.is_empty() {
-                                    print!("\nEnter password for extraction (press Enter [on a blank entry] to use previous password): ");
-                                    io::stdout().flush().unwrap();
-                                    let mut buffer = String::new();
-                                    io::stdin().read_line(&mut buffer).unwrap();
-                                    let password = buffer.trim();
-                                    if !password.is_empty() {
-                                        last_password = password.to_string();
-                                    }
-                                    &last_password
-                                } else {
-                                    print!("\nEnter password for extraction: ");
-                                    io::stdout().flush().unwrap();
-                                    let mut buffer = String::new();
-                                    io::stdin().read_line(&mut buffer).unwrap();
-                                    let password = buffer.trim();
-                                    if password.is_empty() {
-                                        println!("Skipping package due to empty password");
-                                        break;
-                                    }
-                                    last_password = password.to_string();
-                                    &last_password
-                                };
-
-                                //=-- Extract archives
-                                match extract_archives(&nanazip_path, &package_dl_dir, &package_output_dir, current_password) {
-                                    Ok(_) => {
-                                        println!("Successfully extracted archives");
-                                        break; //=-- Exit password retry loop on success
-                                    },
-                                    Err(e) => {
-                                        println!("Error during extraction: {}", e);
-                                        if !package.password.is_empty() && !retry_mode {
-                                            println!("Password from config failed, falling back to manual entry");
-                                            retry_mode = true;
-                                            last_password.clear(); //=-- Clear last password to force a new prompt
-                                            continue;
-                                        }
-                                        retry_mode = true;
-                                        continue;
-                                    }
-                                }
-                            }
-
-                            //=-- Save version file after all archives are successfully extracted
-                            if let Err(e) = save_version_file(&version, &package_output_dir) {
-                                println!("Warning: Failed to save version file: {}", e);
-                            }
-
-                            //=-- Clean up downloaded files
-                            if let Err(e) = cleanup_package_dir(&package_dl_dir) {
-                                println!("Error cleaning up package directory: {}", e);
-                            }
-                        },
-                        Err(e) => println!("Error fetching file list:\n {}", e),
-                    }
+                    reports.push(process_package(&ctx, package, &VersionRequest::Latest));
+                    println!(); //=-- Add a blank line between packages
                 }
-                
-                println!(); //=-- Add a blank line between packages
             }
 
             //=-- Clean up main download directory
-            if let Err(e) = cleanup_package_dir(&dl_dir) {
-                println!("Error cleaning up download directory: {}", e);
+            if let Err(e) = cleanup_package_dir(&ctx.dl_dir) {
+                error!("Error cleaning up download directory: {}", e);
             }
             println!("Tools loading jobs completed.\nPress Enter to exit, or type \"start\" to restart...");
             let mut buffer = String::new();
-            io::stdin().read_line(&mut buffer).unwrap();
+            io::stdin().read_line(&mut buffer)?;
             
             if !buffer.trim().eq_ignore_ascii_case("start") {
-                break;
+                //=-- The exit code reflects the last round
+                return Ok(ErrorKind::combined(reports.iter().filter_map(|report| report.error_kind)));
             }
             
             println!("\n=== Restarting Program ===\n");
         }
-        Ok(())
     })();
 
     //=-- Clean up temp directory before handling the result
     if let Err(e) = cleanup_temp_dir(&temp_dir) {
-        eprintln!("Failed to clean up temporary directory: {}", e);
+        error!("Failed to clean up temporary directory: {}", e);
     }
 
-    //=-- Now handle any errors from the main operation
+    //=-- Now handle any errors from the main operation; see error::ErrorKind for the exit codes
     match result {
-        Ok(_) => Ok(()),
+        Ok(None) => Ok(()),
+        Ok(Some(kind)) => std::process::exit(kind.exit_code()),
         Err(e) => {
-            eprintln!("Error: {}", e);
-            std::process::exit(1);
+            let kind = ErrorKind::of(e.as_ref());
+            if kind == ErrorKind::Cancelled {
+                info!("{}", e);
+            } else {
+                error!("Error: {}", e);
+            }
+            if cli.json {
+                output::emit_error(cli.command.name(), &e.to_string());
+            }
+            std::process::exit(kind.exit_code());
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,90 @@
+use indexmap::IndexMap;
+use std::fs;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+use crate::error::LoaderError;
+use crate::{fetch, signature, ArchiveFormat, FileEntry, Package, PackageRelease, PublishedList, Version, VersionScheme};
+
+//=-- A package manifest replaces version_url + filelist_url with one JSON document:
+//=--   {
+//=--     "version": "2024-03-01--2",
+//=--     "format": "7z",   (also "zip", "tar.gz", "tar.xz" or "tar.zst")
+//=--     "volumes": [ { "name": "tool--n1.globby", "size": 104857600, "sha256": "..." } ],
+//=--     "metadata": { "released": "2024-03-01", "notes": "..." }
+//=--   }
+//=-- Only "version" and "volumes" (with "name") are required.
+#[derive(Debug, Serialize, Deserialize)]
+struct ManifestDocument {
+    version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    format: Option<ArchiveFormat>,
+    volumes: Vec<ManifestVolume>,
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    metadata: IndexMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ManifestVolume {
+    name: String,
+    #[serde(default)]
+    size: Option<u64>,
+    #[serde(default)]
+    sha256: Option<String>,
+}
+
+pub fn fetch_manifest(package: &Package, url: &str) -> Result<PackageRelease, Box<dyn std::error::Error>> {
+    let content = fetch::fetch_text(url, "Manifest")?;
+    let signature = match package.public_key.trim().is_empty() {
+        true => None,
+        false => Some(signature::verify(&package.public_key, url, &content)?),
+    };
+    let release = read_manifest(package, url, &content)?;
+    Ok(PackageRelease { published: Some(PublishedList { file_name: "manifest.json", content, signature }), ..release })
+}
+
+//=-- Parses a manifest read from `source` (a URL or bundle path)
+pub fn read_manifest(package: &Package, source: &str, content: &str) -> Result<PackageRelease, Box<dyn std::error::Error>> {
+    parse_manifest(content, package.version_scheme)
+        .map_err(|e| LoaderError::Integrity(format!("Invalid manifest {}: {}", source, e)).into())
+}
+
+fn parse_manifest(content: &str, scheme: VersionScheme) -> Result<PackageRelease, Box<dyn std::error::Error>> {
+    let document: ManifestDocument = serde_json::from_str(content)?;
+
+    if document.volumes.is_empty() {
+        return Err(LoaderError::Integrity("no volumes listed".to_string()).into());
+    }
+
+    let mut files = Vec::with_capacity(document.volumes.len());
+    for volume in document.volumes {
+        let sha256 = match volume.sha256 {
+            Some(hash) if hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()) => Some(hash.to_lowercase()),
+            Some(hash) => return Err(LoaderError::Integrity(format!("volume {} has an invalid sha256 \"{}\"", volume.name, hash)).into()),
+            None => None,
+        };
+        files.push(FileEntry { name: volume.name, sha256, size: volume.size });
+    }
+
+    Ok(PackageRelease {
+        version: Version::parse(&document.version, scheme)?,
+        files,
+        format: document.format,
+        metadata: document.metadata,
+        published: None,
+    })
+}
+
+//=-- Writes the manifest for a packed release (see the `pack` command)
+pub fn write_manifest(path: &Path, version: &Version, format: ArchiveFormat, files: &[FileEntry]) -> Result<(), Box<dyn std::error::Error>> {
+    let document = ManifestDocument {
+        version: version.to_string(),
+        format: Some(format),
+        volumes: files.iter()
+            .map(|file| ManifestVolume { name: file.name.clone(), size: file.size, sha256: file.sha256.clone() })
+            .collect(),
+        metadata: IndexMap::new(),
+    };
+    fs::write(path, serde_json::to_string_pretty(&document)?)?;
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use std::path::Path;
+use std::time::{Duration, Instant};
+use reqwest::blocking::Client;
+use serde::Deserialize;
+
+use crate::error::LoaderError;
+use crate::{download_package_file, expand_url, fetch, FileEntry, LoaderContext, Package, Version};
+
+//=-- A global mirror set: any repo_url starting with `prefix` can also be served by
+//=-- each of `urls` with the same remainder, e.g.
+//=--   [mirrors.example]
+//=--   prefix = "https://primary.example.com/tools/"
+//=--   urls = ["https://mirror1.example.com/tools/", "\\\\fileserver\\tools\\"]
+#[derive(Debug, Deserialize, Clone)]
+pub struct MirrorSet {
+    pub prefix: String,
+    #[serde(default)]
+    pub urls: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MirrorStrategy {
+    Order,   //=-- Primary first, then mirrors in the order they are configured
+    Latency, //=-- Fastest responding mirror first, unreachable ones last
+}
+
+impl MirrorStrategy {
+    pub fn from_setting(value: Option<&String>) -> MirrorStrategy {
+        match value.map(|s| s.trim().to_lowercase()) {
+            Some(s) if s == "latency" => MirrorStrategy::Latency,
+            _ => MirrorStrategy::Order,
+        }
+    }
+}
+
+fn with_trailing_slash(url: String) -> String {
+    if url.ends_with('/') || url.ends_with('\\') {
+        url
+    } else {
+        format!("{}/", url)
+    }
+}
+
+//=-- Every base URL that serves the package's volumes: repo_url, then the package's mirrors,
+//=-- then matching global mirror sets. Duplicates are dropped.
+pub fn repo_mirrors(package: &Package, version: &Version, mirror_sets: &[MirrorSet]) -> Vec<String> {
+    let primary = with_trailing_slash(expand_url(&package.repo_url, version));
+    let mut mirrors = vec![primary.clone()];
+    for mirror in &package.mirrors {
+        mirrors.push(with_trailing_slash(expand_url(mirror, version)));
+    }
+    for set in mirror_sets {
+        if let Some(rest) = primary.strip_prefix(set.prefix.as_str()) {
+            for url in &set.urls {
+                mirrors.push(with_trailing_slash(url.clone()) + rest);
+            }
+        }
+    }
+
+    let mut unique: Vec<String> = Vec::new();
+    for mirror in mirrors {
+        if !mirror.trim().is_empty() && !unique.contains(&mirror) {
+            unique.push(mirror);
+        }
+    }
+    unique
+}
+
+//=-- Time to answer a HEAD request for `probe_file`, or None when the mirror doesn't have it
+fn measure_latency(client: &Client, base: &str, probe_file: &str) -> Option<Duration> {
+    let url = format!("{}{}", base, probe_file);
+    let start = Instant::now();
+    if let fetch::Location::Local(path) = fetch::locate(&url) {
+        return path.exists().then(|| start.elapsed());
+    }
+
+    match client.head(&url).timeout(Duration::from_secs(5)).send() {
+        Ok(response) => {
+            debug!("HEAD {} -> {}", url, response.status());
+            response.status().is_success().then(|| start.elapsed())
+        },
+        Err(e) => {
+            debug!("HEAD {} failed: {}", url, e);
+            None
+        },
+    }
+}
+
+pub fn sort_by_latency(mirrors: Vec<String>, probe_file: &str) -> Vec<String> {
+    let client = Client::new();
+    let mut measured: Vec<(String, Option<Duration>)> = mirrors.into_iter()
+        .map(|mirror| {
+            let latency = measure_latency(&client, &mirror, probe_file);
+            match latency {
+                Some(latency) => debug!("Mirror {} answered in {} ms", mirror, latency.as_millis()),
+                None => warn!("Mirror {} is unreachable", mirror),
+            }
+            (mirror, latency)
+        })
+        .collect();
+    //=-- Reachable mirrors by latency, then unreachable ones in their configured order
+    measured.sort_by_key(|(_, latency)| latency.unwrap_or(Duration::MAX));
+    measured.into_iter().map(|(mirror, _)| mirror).collect()
+}
+
+//=-- Downloads one volume, falling back to the next mirror on any failure (including a bad checksum).
+//=-- Returns the mirror that served the file and whether the cache was used.
+pub fn download_from_mirrors(ctx: &LoaderContext, mirrors: &[String], file: &FileEntry, target_path: &Path) -> Result<(String, bool), Box<dyn std::error::Error>> {
+    let mut last_error: Option<Box<dyn std::error::Error>> = None;
+    for mirror in mirrors {
+        let url = format!("{}{}", mirror, file.name);
+        match download_package_file(ctx, &url, file, target_path) {
+            Ok(from_cache) => return Ok((mirror.clone(), from_cache)),
+            Err(e) => {
+                if mirrors.len() > 1 {
+                    warn!("Failed to get {} from {}: {}", file.name, mirror, e);
+                }
+                last_error = Some(e);
+            }
+        }
+    }
+    Err(last_error.unwrap_or_else(|| LoaderError::Config("No repository URL configured".to_string()).into()))
+}
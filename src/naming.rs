@@ -0,0 +1,154 @@
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::Package;
+use crate::error::LoaderError;
+
+//=-- How the volumes in a package's filelist are named on the server. Set per package with `volume_naming`
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum VolumeNamingKind {
+    #[default]
+    Globby, //=-- name--n1.globby, renamed to name.7z.001 for extraction
+    #[serde(rename = "7z")]
+    SevenZip, //=-- name.7z.001, kept as is
+    Zip,    //=-- name.zip.001, kept as is
+    Rar,    //=-- name.part1.rar, kept as is
+    Split,  //=-- any archive split into name.ext.001, name.ext.002, ..., kept as is
+    Single, //=-- one unsplit archive file (e.g. name.tar.gz), kept as is
+    Custom, //=-- volume_pattern / volume_suffix from the package
+}
+
+//=-- A compiled naming rule: `remote` matches filelist entries (capture group 1 is the volume number),
+//=-- `suffix` (if any) replaces the match to form the local name, and `local` finds the base name and
+//=-- volume number again in the download folder. `single` treats every entry as its own one-volume archive.
+//=-- `publish` is the inverse used by `pack`: the suffix a volume gets on the server ({ext} is the archive format).
+pub struct VolumeNaming {
+    description: String,
+    remote: Regex,
+    suffix: Option<String>,
+    local: Regex,
+    single: bool,
+    publish: Option<&'static str>,
+}
+
+//=-- Filelist, manifest and bundle entries become paths on disk, so only plain file names are accepted
+pub fn is_plain_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && !name.contains(['/', '\\', ':'])
+}
+
+//=-- Turns a suffix template like ".7z.{nnn}" into a regex capturing the volume number
+fn suffix_to_regex(suffix: &str) -> String {
+    regex::escape(suffix)
+        .replace(r"\{nnn\}", r"(\d+)")
+        .replace(r"\{n\}", r"(\d+)")
+}
+
+//=-- {n} is the plain volume number, {nnn} the number padded to three digits
+fn render_suffix(suffix: &str, number: u32) -> String {
+    suffix
+        .replace("{nnn}", &format!("{:03}", number))
+        .replace("{n}", &number.to_string())
+}
+
+impl VolumeNaming {
+    fn new(description: &str, remote: &str, suffix: Option<&str>) -> Result<Self, Box<dyn std::error::Error>> {
+        let remote_regex = Regex::new(remote)
+            .map_err(|e| LoaderError::Config(format!("Invalid volume_pattern \"{}\": {}", remote, e)))?;
+        if remote_regex.captures_len() < 2 {
+            return Err(LoaderError::Config(format!("volume_pattern \"{}\" needs a capture group for the volume number", remote)).into());
+        }
+
+        let local = match suffix {
+            Some(suffix) => {
+                if !suffix.contains("{n}") && !suffix.contains("{nnn}") {
+                    return Err(LoaderError::Config(format!("volume_suffix \"{}\" needs {{n}} or {{nnn}} for the volume number", suffix)).into());
+                }
+                format!("^(.+?){}$", suffix_to_regex(suffix))
+            },
+            //=-- Names are kept, so the remote pattern also finds them locally
+            None => format!("^(.+?){}", remote.trim_start_matches('^')),
+        };
+
+        Ok(VolumeNaming {
+            description: description.to_string(),
+            remote: remote_regex,
+            suffix: suffix.map(|s| s.to_string()),
+            local: Regex::new(&local)?,
+            single: false,
+            publish: None,
+        })
+    }
+
+    fn with_publish(self, publish: &'static str) -> Self {
+        VolumeNaming { publish: Some(publish), ..self }
+    }
+
+    pub fn for_package(package: &Package) -> Result<Self, Box<dyn std::error::Error>> {
+        match package.volume_naming {
+            VolumeNamingKind::Globby => Ok(Self::new("<name>--nN.globby", r"--n(\d+)\.globby$", Some(".7z.{nnn}"))?.with_publish("--n{n}.globby")),
+            VolumeNamingKind::SevenZip => Ok(Self::new("<name>.7z.NNN", r"\.7z\.(\d+)$", None)?.with_publish(".7z.{nnn}")),
+            VolumeNamingKind::Zip => Ok(Self::new("<name>.zip.NNN", r"\.zip\.(\d+)$", None)?.with_publish(".zip.{nnn}")),
+            VolumeNamingKind::Rar => Self::new("<name>.partN.rar", r"\.part(\d+)\.rar$", None),
+            VolumeNamingKind::Split => Ok(Self::new("<name>.NNN", r"\.(\d{3,})$", None)?.with_publish(".{ext}.{nnn}")),
+            VolumeNamingKind::Single => {
+                let naming = Self::new("<name> (one file per archive)", r"()$", None)?;
+                Ok(VolumeNaming { single: true, ..naming }.with_publish(".{ext}"))
+            },
+            VolumeNamingKind::Custom => {
+                if package.volume_pattern.trim().is_empty() {
+                    return Err(LoaderError::Config(format!("{} uses custom volume naming but has no volume_pattern", package.name)).into());
+                }
+                let suffix = Some(package.volume_suffix.trim()).filter(|s| !s.is_empty());
+                Self::new(&format!("volume_pattern {}", package.volume_pattern), &package.volume_pattern, suffix)
+            },
+        }
+    }
+
+    //=-- The server name of volume `number` of `count` when packing `base_name`, checked against the install side rule
+    pub fn remote_name(&self, base_name: &str, number: u32, count: u32, extension: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let publish = self.publish
+            .ok_or_else(|| LoaderError::Config(format!("Volumes cannot be named for volume naming {}, pack needs globby, 7z, zip, split or single", self.description)))?;
+        if self.single && count > 1 {
+            return Err(LoaderError::Config("Single volume naming cannot hold a split archive, use a larger --volume-size".to_string()).into());
+        }
+
+        let name = format!("{}{}", base_name, render_suffix(&publish.replace("{ext}", extension), number));
+        self.local_name(&name)?;
+        Ok(name)
+    }
+
+    //=-- The name a filelist entry is saved under for extraction
+    pub fn local_name(&self, remote_name: &str) -> Result<String, Box<dyn std::error::Error>> {
+        if self.single {
+            return Ok(remote_name.to_string());
+        }
+        let caps = self.remote.captures(remote_name)
+            .ok_or_else(|| LoaderError::Config(format!("Filelist entry \"{}\" does not match the volume naming {}", remote_name, self.description)))?;
+        let number = caps.get(1)
+            .and_then(|m| m.as_str().parse::<u32>().ok())
+            .ok_or_else(|| LoaderError::Config(format!("Filelist entry \"{}\" has no valid volume number", remote_name)))?;
+
+        match &self.suffix {
+            Some(suffix) => {
+                let matched = caps.get(0).unwrap();
+                Ok(format!("{}{}", &remote_name[..matched.start()], render_suffix(suffix, number)))
+            },
+            None => Ok(remote_name.to_string()),
+        }
+    }
+
+    //=-- Base name and volume number of a downloaded volume, or None if it isn't one
+    pub fn split_local_name(&self, local_name: &str) -> Option<(String, u32)> {
+        if self.single {
+            return Some((local_name.to_string(), 1));
+        }
+        let caps = self.local.captures(local_name)?;
+        let base = caps.get(1)?.as_str().to_string();
+        let number = caps.get(2)?.as_str().parse::<u32>().ok()?;
+        Some((base, number))
+    }
+}
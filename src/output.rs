@@ -0,0 +1,202 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use serde::Serialize;
+
+use crate::error::ErrorKind;
+use crate::{logging, Package};
+
+//=-- With --json the loader prints exactly one JSON document on stdout and nothing else. Diagnostics go to
+//=-- stderr (and the log file), and prompts are never shown: each one takes its default answer.
+//=--
+//=-- Document format (fields are only ever added, never renamed or removed):
+//=--   { "command": "list" | "status" | "install" | "upgrade", "packages": [ ... ], "error": null | "<why the whole run failed>" }
+//=-- `list` packages are PackageInfo objects, `status` packages are PackageStatus objects and `install` and
+//=-- `upgrade` packages are InstallReport objects.
+
+static JSON: AtomicBool = AtomicBool::new(false);
+//=-- Nobody is there to answer prompts (--json and watch)
+static UNATTENDED: AtomicBool = AtomicBool::new(false);
+//=-- Answers prompts. The library never reads stdin itself: until a front end sets a prompter, every
+//=-- prompt takes its default answer.
+static PROMPTER: Mutex<Option<Prompter>> = Mutex::new(None);
+
+//=-- Given the prompt, returns the trimmed answer ("" for the default)
+pub type Prompter = fn(&str) -> String;
+
+pub fn enable_json() {
+    JSON.store(true, Ordering::Relaxed);
+    set_unattended();
+    logging::console_to_stderr();
+}
+
+pub fn is_json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+pub fn set_unattended() {
+    UNATTENDED.store(true, Ordering::Relaxed);
+}
+
+pub fn set_prompter(prompter: Prompter) {
+    *PROMPTER.lock().unwrap_or_else(|e| e.into_inner()) = Some(prompter);
+}
+
+pub fn is_unattended() -> bool {
+    UNATTENDED.load(Ordering::Relaxed) || PROMPTER.lock().unwrap_or_else(|e| e.into_inner()).is_none()
+}
+
+//=-- Asks the prompter and returns its answer. Unattended nothing is asked and "" (the default) is returned
+pub fn ask(prompt: &str) -> String {
+    let prompter = *PROMPTER.lock().unwrap_or_else(|e| e.into_inner());
+    match prompter {
+        Some(prompter) if !UNATTENDED.load(Ordering::Relaxed) => prompter(prompt),
+        _ => {
+            debug!("Using the default answer for: {}", prompt.trim());
+            String::new()
+        },
+    }
+}
+
+//=-- One configured package, as listed by `list`
+#[derive(Debug, Serialize)]
+pub struct PackageInfo {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub output_dir: String,
+    pub installed_version: Option<String>,
+    pub installed_channel: Option<String>,
+    pub pinned_version: Option<String>,
+}
+
+//=-- How the installed version compares to the one an install would fetch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PackageState {
+    UpToDate,
+    Outdated,
+    NewerLocally,
+    NotInstalled,
+    Unreachable, //=-- The remote version could not be fetched, see error
+}
+
+impl fmt::Display for PackageState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            PackageState::UpToDate => "up to date",
+            PackageState::Outdated => "outdated",
+            PackageState::NewerLocally => "newer locally",
+            PackageState::NotInstalled => "not installed",
+            PackageState::Unreachable => "unreachable",
+        })
+    }
+}
+
+//=-- One package, as shown by `status`
+#[derive(Debug, Serialize)]
+pub struct PackageStatus {
+    pub id: String,
+    pub name: String,
+    pub channel: String,
+    pub installed_version: Option<String>,
+    pub installed_channel: Option<String>,
+    pub remote_version: Option<String>,
+    pub state: PackageState,
+    pub error: Option<String>,
+    pub error_kind: Option<ErrorKind>,
+}
+
+//=-- What an install did to a package
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Installed,   //=-- Nothing was installed before
+    Updated,     //=-- Moved to a newer version
+    Downgraded,  //=-- Moved to an older version
+    Reinstalled, //=-- Same version loaded again
+    Skipped,     //=-- Nothing changed (declined, or the same version)
+    Failed,      //=-- See errors
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Action::Installed => "installed",
+            Action::Updated => "updated",
+            Action::Downgraded => "downgraded",
+            Action::Reinstalled => "reinstalled",
+            Action::Skipped => "skipped",
+            Action::Failed => "failed",
+        })
+    }
+}
+
+//=-- The outcome of installing one package
+#[derive(Debug, Serialize)]
+pub struct InstallReport {
+    pub id: String,
+    pub channel: String,
+    pub installed_version: Option<String>, //=-- Before this run
+    pub remote_version: Option<String>,
+    pub action: Action,
+    pub errors: Vec<String>,
+    pub error_kind: Option<ErrorKind>, //=-- Of the first error, decides the exit code
+}
+
+impl InstallReport {
+    pub fn new(package: &Package) -> InstallReport {
+        InstallReport {
+            id: package.id.clone(),
+            channel: package.channel.clone(),
+            installed_version: None,
+            remote_version: None,
+            action: Action::Skipped,
+            errors: Vec::new(),
+            error_kind: None,
+        }
+    }
+
+    //=-- A package upgrade left alone, from its status
+    pub fn unchanged(status: &PackageStatus) -> InstallReport {
+        InstallReport {
+            id: status.id.clone(),
+            channel: status.channel.clone(),
+            installed_version: status.installed_version.clone(),
+            remote_version: status.remote_version.clone(),
+            action: Action::Skipped,
+            errors: Vec::new(),
+            error_kind: None,
+        }
+    }
+
+    //=-- Records an error (already shown to the user); the package counts as failed
+    pub fn fail(&mut self, error: impl Into<Box<dyn std::error::Error>>) {
+        let error = error.into();
+        self.error_kind.get_or_insert(ErrorKind::of(error.as_ref()));
+        self.errors.push(error.to_string());
+        self.action = Action::Failed;
+    }
+}
+
+#[derive(Serialize)]
+struct Document<'a, T: Serialize> {
+    command: &'a str,
+    packages: &'a [T],
+    error: Option<String>,
+}
+
+pub fn emit<T: Serialize>(command: &str, packages: &[T]) {
+    print_document(&Document { command, packages, error: None });
+}
+
+pub fn emit_error(command: &str, error: &str) {
+    print_document(&Document::<PackageInfo> { command, packages: &[], error: Some(error.to_string()) });
+}
+
+fn print_document<T: Serialize>(document: &Document<T>) {
+    match serde_json::to_string_pretty(document) {
+        Ok(json) => println!("{}", json),
+        Err(e) => error!("Failed to write JSON output: {}", e),
+    }
+}
@@ -0,0 +1,155 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::error::LoaderError;
+use crate::{
+    archive, manifest, redact_secret, run_nanazip, sha256_file, signature,
+    ArchiveFormat, FileEntry, Package, Version, VolumeNaming,
+};
+
+//=-- `pack` is the publisher side of an install: it turns a folder into the files a package repository serves
+//=--   <out-dir>/<id>--n1.globby ...   volumes, named by the package's volume_naming
+//=--   <out-dir>/version.txt           for version_url
+//=--   <out-dir>/filelist.txt          for filelist_url, with the version and a sha256 per volume
+//=--   <out-dir>/manifest.json         for manifest_url, only when the package uses one
+//=--   <out-dir>/*.sig                 with --sign-key, checked by installs that set public_key
+
+//=-- `pack` arguments. Format, naming and version scheme come from the package's config
+#[derive(Clone)]
+pub struct PackOptions {
+    pub id: String,
+    pub source: PathBuf,
+    pub out_dir: PathBuf,
+    pub version: String,
+    pub volume_size_mb: u64,
+    pub encrypt: bool,
+    pub password: String, //=-- For encrypt when the package has no password of its own
+    pub sign_key: Option<PathBuf>,
+}
+
+//=-- NanaZip writes the volumes itself as <archive>.001, <archive>.002, ...
+fn create_7z(nanazip_path: &Path, source_dir: &Path, archive_path: &Path, volume_size_mb: u64, password: &str) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let source_dir = fs::canonicalize(source_dir)?;
+    let mut cmd = Command::new(nanazip_path);
+    cmd.current_dir(&source_dir)
+       .arg("a")
+       .arg("-t7z")
+       .arg("-y")
+       .arg(format!("-v{}m", volume_size_mb));
+    if !password.is_empty() {
+        //=-- -p without a value makes NanaZip ask for the password (twice), and -mhe hides the file names too
+        cmd.arg("-p").arg("-mhe=on");
+    }
+    cmd.arg(archive_path).arg("*");
+
+    let output = run_nanazip(&mut cmd, password, 2).inspect_err(|e| {
+        if e.kind() == io::ErrorKind::NotFound {
+            error!("!==-- The config for NanaZip's location is incorrect. NanaZip executable not found. --==!");
+        }
+    })?;
+    if !output.status.success() {
+        let error_msg = redact_secret(&String::from_utf8_lossy(&output.stderr), password);
+        return Err(LoaderError::Extraction(format!("Failed to create {}: {}", archive_path.display(), error_msg)).into());
+    }
+
+    let invalid_path = || LoaderError::Filesystem(format!("Invalid archive path {}", archive_path.display()));
+    let archive_name = archive_path.file_name().ok_or_else(invalid_path)?.to_string_lossy().into_owned();
+    let directory = archive_path.parent().ok_or_else(invalid_path)?;
+    let mut volumes: Vec<PathBuf> = fs::read_dir(directory)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.file_name().is_some_and(|name| name.to_string_lossy().starts_with(&format!("{}.", archive_name))))
+        .collect();
+    volumes.sort();
+    Ok(volumes)
+}
+
+fn write_text(path: &Path, content: &str) -> Result<(), Box<dyn std::error::Error>> {
+    fs::write(path, content)?;
+    info!("Wrote {}", path.display());
+    Ok(())
+}
+
+fn pack_into(nanazip_path: &Path, package: &Package, options: &PackOptions, version: &Version, staging_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let naming = VolumeNaming::for_package(package)?;
+    //=-- Packages that leave the format to detection are published as 7z, like the original repositories
+    let format = match package.archive_format {
+        ArchiveFormat::Auto => ArchiveFormat::SevenZip,
+        format => format,
+    };
+    let password = match options.encrypt {
+        true if !package.password.is_empty() => package.password.clone(),
+        true if !options.password.is_empty() => options.password.clone(),
+        true => return Err(LoaderError::Config("An empty password cannot be used with --encrypt".to_string()).into()),
+        false => String::new(),
+    };
+
+    info!("Packing {} into {} {} archive volumes of up to {} MB", options.source.display(), package.id, format, options.volume_size_mb);
+    let archive_path = staging_dir.join(format!("{}.{}", package.id, format));
+    let volumes = if format == ArchiveFormat::SevenZip {
+        create_7z(nanazip_path, &options.source, &archive_path, options.volume_size_mb, &password)?
+    } else {
+        archive::create(format, &options.source, &archive_path, &password)?;
+        archive::split_file(&archive_path, options.volume_size_mb * 1024 * 1024)?
+    };
+
+    let count = volumes.len() as u32;
+    let mut files = Vec::with_capacity(volumes.len());
+    for (index, volume) in volumes.iter().enumerate() {
+        let name = naming.remote_name(&package.id, index as u32 + 1, count, &format.to_string())?;
+        let target = options.out_dir.join(&name);
+        fs::rename(volume, &target)?;
+        let size = fs::metadata(&target)?.len();
+        info!("Created {} ({} bytes)", target.display(), size);
+        files.push(FileEntry { name, sha256: Some(sha256_file(&target)?), size: Some(size) });
+    }
+
+    //=-- The version line ties the signed filelist to this release (see get_package_files)
+    let filelist: String = std::iter::once(format!("{}{}\n", crate::FILELIST_VERSION_PREFIX, version))
+        .chain(files.iter().map(|file| format!("{} {}\n", file.name, file.sha256.as_deref().unwrap_or_default())))
+        .collect();
+    let mut signed = vec![options.out_dir.join("filelist.txt")];
+    write_text(&options.out_dir.join("version.txt"), &version.to_string())?;
+    write_text(&signed[0], &filelist)?;
+    if !package.manifest_url.trim().is_empty() {
+        let manifest_path = options.out_dir.join("manifest.json");
+        manifest::write_manifest(&manifest_path, version, format, &files)?;
+        info!("Wrote {}", manifest_path.display());
+        signed.push(manifest_path);
+    }
+
+    if let Some(key_path) = &options.sign_key {
+        let key = signature::load_or_create_key(key_path)?;
+        for path in &signed {
+            info!("Wrote {}", signature::sign_file(&key, path)?.display());
+        }
+        info!("Public key (public_key in the package config): {}", signature::public_key_hex(&key));
+    }
+    Ok(())
+}
+
+//=-- `pack <id> <folder> <out-dir> --version <version>`
+pub fn pack(nanazip_path: &Path, package: &Package, options: &PackOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let version = Version::parse(&options.version, package.version_scheme)?;
+    if !options.source.is_dir() {
+        return Err(LoaderError::Config(format!("{} is not a folder", options.source.display())).into());
+    }
+    fs::create_dir_all(&options.out_dir)?;
+
+    //=-- Volumes are built next to the output so moving them into place is a rename
+    let staging_dir = options.out_dir.join(format!(".{}.pack", package.id));
+    if staging_dir.exists() {
+        fs::remove_dir_all(&staging_dir)?;
+    }
+    fs::create_dir_all(&staging_dir)?;
+    //=-- NanaZip runs inside the source folder, so the archive path has to be absolute
+    let staging_dir = fs::canonicalize(&staging_dir)?;
+
+    let result = pack_into(nanazip_path, package, options, &version, &staging_dir);
+    let _ = fs::remove_dir_all(&staging_dir);
+    result?;
+    info!("Packed {} {}: upload the files above to the package repository", package.name, version);
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::error::LoaderError;
+use crate::{download_file, get_local_version, get_version, sha256_file, Settings, Version, VersionScheme};
+
+//=-- Self-update reads these keys from [main]:
+//=--   release_url = folder holding the release's version.txt, checksums.txt and the loader executable
+//=-- checksums.txt is not signed: it catches a download corrupted on the way, not a release_url host that
+//=-- serves a different executable, so release_url should be a host you trust (HTTPS)
+//=--   self_update = set to false to only report new versions instead of installing them at startup
+
+pub fn is_enabled(settings: &Settings) -> bool {
+    settings.main.get("self_update")
+        .map(|s| !s.trim().eq_ignore_ascii_case("false"))
+        .unwrap_or(true)
+}
+
+pub fn release_url(settings: &Settings) -> Option<String> {
+    settings.main.get("release_url")
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| if s.ends_with('/') { s.to_string() } else { format!("{}/", s) })
+}
+
+//=-- Finds the checksum for a file in a `sha256sum`-style listing ("<hex>  <name>" per line)
+fn find_checksum(checksums: &str, file_name: &str) -> Option<String> {
+    checksums.lines().find_map(|line| {
+        let mut parts = line.split_whitespace();
+        let hash = parts.next()?;
+        let name = parts.next()?.trim_start_matches('*'); //=-- `*` marks binary mode in sha256sum output
+        if name == file_name {
+            Some(hash.to_lowercase())
+        } else {
+            None
+        }
+    })
+}
+
+//=-- Writes the file next to its destination first, so a crash never leaves a half-written version.txt
+fn write_file_atomic(path: &Path, contents: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_path = path.with_extension("txt.new");
+    fs::write(&tmp_path, contents)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+//=-- The running loader's version, from the version.txt next to its executable. update writes the same file,
+//=-- so this is the one place the loader's own version is kept, wherever Config.toml lives.
+pub fn installed_version(exe_path: &Path) -> Option<Version> {
+    get_local_version(exe_path.parent()?).unwrap_or(None)
+}
+
+//=-- The version the release at `release_url` would install
+pub fn release_version(release_url: &str) -> Result<Version, Box<dyn std::error::Error>> {
+    Version::parse(&get_version(&format!("{}version.txt", release_url))?, VersionScheme::DateIteration)
+}
+
+//=-- Downloads, verifies and swaps in the release from `release_url`. `version` is what release_version
+//=-- returned when the caller decided to update; it is recorded as is, version.txt is not fetched again.
+pub fn update(release_url: &str, version: &Version, exe_path: &Path, work_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let exe_dir = exe_path.parent().ok_or("Failed to get executable directory")?;
+    let exe_name = exe_path.file_name()
+        .and_then(|name| name.to_str())
+        .ok_or("Failed to get executable name")?;
+
+    let checksums = get_version(&format!("{}checksums.txt", release_url))
+        .map_err(|e| format!("Release checksums cannot be retrieved: {}", e))?;
+    let expected = find_checksum(&checksums, exe_name)
+        .ok_or_else(|| LoaderError::Integrity(format!("Release checksums do not list {}", exe_name)))?;
+
+    let staged_exe: PathBuf = work_dir.join("self-update").join(exe_name);
+    info!("Downloading {}{}", release_url, exe_name);
+    download_file(&format!("{}{}", release_url, exe_name), &staged_exe)?;
+
+    let actual = sha256_file(&staged_exe)?;
+    if actual != expected {
+        let _ = fs::remove_file(&staged_exe);
+        return Err(LoaderError::Integrity(format!("Checksum mismatch for {} (expected {}, got {})", exe_name, expected, actual)).into());
+    }
+    info!("Verified checksum of {}", exe_name);
+
+    //=-- Replace the executable first: if that fails, version.txt still matches the binary that is running
+    self_replace::self_replace(&staged_exe)
+        .map_err(|e| LoaderError::Filesystem(format!("Failed to replace the loader executable: {}", e)))?;
+    let _ = fs::remove_file(&staged_exe);
+
+    write_file_atomic(&exe_dir.join("version.txt"), &version.to_string())
+        .map_err(|e| LoaderError::Filesystem(format!("Executable was updated, but version.txt could not be written: {}", e)))?;
+
+    Ok(())
+}
+
+//=-- Runs the freshly installed loader with the same arguments and exits with its status. `work_dir` (the temp
+//=-- folder update staged the download in) is removed first, since this process never returns to clean it up.
+pub fn restart(exe_path: &Path, work_dir: &Path) -> ! {
+    info!("\n=== Restarting WarpBits Tools Loader ===\n");
+    if let Err(e) = fs::remove_dir_all(work_dir) {
+        if work_dir.exists() {
+            warn!("Failed to clean up temporary directory {}: {}", work_dir.display(), e);
+        }
+    }
+    match Command::new(exe_path).args(env::args_os().skip(1)).status() {
+        //=-- No exit code means it was killed by a signal, which is not a success
+        Ok(status) => std::process::exit(status.code().unwrap_or(1)),
+        Err(e) => {
+            error!("Failed to restart the loader, please start it again: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
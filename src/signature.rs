@@ -0,0 +1,71 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+
+use crate::fetch;
+use crate::error::{ErrorKind, LoaderError};
+
+//=-- Publishers sign filelist.txt (and manifest.json) with an ed25519 key. The signature is stored next to
+//=-- the signed file as "<file>.sig" holding the hex encoded signature. Packages with a `public_key` (hex)
+//=-- in the config refuse filelists and manifests whose signature is missing or doesn't match. Both name the
+//=-- version they describe, which installs check against the version they resolved.
+
+//=-- `kind` is the kind of error bad input is: Config for keys, Integrity for published signatures
+fn parse_hex<const N: usize>(text: &str, what: &str, kind: ErrorKind) -> Result<[u8; N], Box<dyn std::error::Error>> {
+    let bytes = hex::decode(text.trim()).map_err(|e| LoaderError::with_kind(kind, format!("Invalid {}: {}", what, e)))?;
+    bytes.try_into().map_err(|_| LoaderError::with_kind(kind, format!("Invalid {}: expected {} bytes", what, N)))
+}
+
+//=-- Loads the hex encoded 32 byte secret key, or creates a new one if the file doesn't exist yet
+pub fn load_or_create_key(path: &Path) -> Result<SigningKey, Box<dyn std::error::Error>> {
+    if path.exists() {
+        let secret = parse_hex::<32>(&fs::read_to_string(path)?, "signing key", ErrorKind::Config)
+            .map_err(|e| LoaderError::Config(format!("{} in {}", e, path.display())))?;
+        return Ok(SigningKey::from_bytes(&secret));
+    }
+
+    let mut secret = [0u8; 32];
+    getrandom::getrandom(&mut secret).map_err(|e| LoaderError::Filesystem(format!("Failed to generate a signing key: {}", e)))?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    //=-- Only the owner may read the secret key
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(hex::encode(secret).as_bytes())?;
+    info!("Created new signing key: {}", path.display());
+    Ok(SigningKey::from_bytes(&secret))
+}
+
+pub fn public_key_hex(key: &SigningKey) -> String {
+    hex::encode(key.verifying_key().to_bytes())
+}
+
+//=-- Writes "<file>.sig" next to the file and returns its path
+pub fn sign_file(key: &SigningKey, path: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let signature = key.sign(&fs::read(path)?);
+    let mut signature_path = path.as_os_str().to_owned();
+    signature_path.push(".sig");
+    let signature_path = PathBuf::from(signature_path);
+    fs::write(&signature_path, hex::encode(signature.to_bytes()))?;
+    Ok(signature_path)
+}
+
+//=-- Checks `content` (fetched from `url`) against the signature published at "<url>.sig" and returns that signature
+pub fn verify(public_key: &str, url: &str, content: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let signature_text = fetch::fetch_text(&format!("{}.sig", url), "Signature")?;
+    verify_signature(public_key, url, content, &signature_text)?;
+    Ok(signature_text)
+}
+
+//=-- Checks `content` against a hex signature that is already at hand, e.g. from a bundle. `source` names it in errors.
+pub fn verify_signature(public_key: &str, source: &str, content: &str, signature_text: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let key = VerifyingKey::from_bytes(&parse_hex::<32>(public_key, "public_key", ErrorKind::Config)?)
+        .map_err(|e| LoaderError::Config(format!("Invalid public_key: {}", e)))?;
+    let signature = Signature::from_bytes(&parse_hex::<64>(signature_text, "signature", ErrorKind::Integrity)?);
+    key.verify(content.as_bytes(), &signature)
+        .map_err(|_| LoaderError::Integrity(format!("Signature check failed for {}", source)).into())
+}
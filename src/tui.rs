@@ -0,0 +1,912 @@
+use std::collections::{BTreeSet, HashMap};
+use std::env;
+use std::io::{self, IsTerminal};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use ratatui::crossterm::event::{self, Event as TermEvent, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Flex, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Cell, Clear, Gauge, Paragraph, Row, Table, TableState, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+use wb_toolsloader::cache::format_size;
+use wb_toolsloader::error::ErrorKind;
+use wb_toolsloader::events::{DecisionProvider, Event, Observer, OutputDirAction, Question};
+use wb_toolsloader::logging::{self, Level};
+use wb_toolsloader::{
+    cleanup_package_dir, output, package_status, process_package, upgrade_packages, Action, InstallReport,
+    LoaderContext, Package, PackageState, PackageStatus, VersionRequest,
+};
+
+//=-- The full-screen package menu. Installs run on a worker thread; their progress and questions come back
+//=-- over a channel (through the context's Observer and DecisionProvider) so the screen keeps drawing.
+//=-- Console log lines go to the log panel while it is up.
+
+//=-- Full screen needs a real terminal on both ends, and someone to answer
+pub fn is_supported(disabled: bool) -> bool {
+    !disabled
+        && !output::is_unattended()
+        && io::stdin().is_terminal()
+        && io::stdout().is_terminal()
+        && env::var("TERM").map_or(true, |term| term != "dumb")
+}
+
+static LOG_LINES: Mutex<Vec<(Level, String)>> = Mutex::new(Vec::new());
+const LOG_KEEP: usize = 500;
+
+//=-- logging's console sink while the screen is up (must not log itself)
+fn log_to_panel(level: Level, message: &str) {
+    let mut lines = LOG_LINES.lock().unwrap_or_else(|e| e.into_inner());
+    lines.extend(message.lines().filter(|line| !line.trim().is_empty()).map(|line| (level, line.to_string())));
+}
+
+enum Job {
+    Refresh,
+    Install(Vec<usize>),
+    Upgrade(Vec<usize>),
+}
+
+//=-- A question, owned so it can cross to the UI thread
+enum Prompt {
+    Confirm { lines: Vec<String>, default: bool },
+    OutputDir { path: String },
+    Password { retry: bool },
+}
+
+enum Answer {
+    Confirm(bool),
+    OutputDir(OutputDirAction),
+    Password(Option<String>),
+}
+
+//=-- From the worker to the UI
+enum Update {
+    Status(usize, PackageStatus),
+    Started { name: String },
+    Downloaded { volume: String, bytes: u64, from_cache: bool, done: usize, total: usize },
+    Extracted { archive: String, done: usize, total: usize },
+    Finished { id: String, action: Action, errors: Vec<String> },
+    Ask { package: String, prompt: Prompt, reply: Sender<Answer> },
+    //=-- The reports of an install or upgrade; None after a refresh
+    JobDone(Option<Vec<InstallReport>>),
+}
+
+struct ChannelObserver(Sender<Update>);
+
+impl Observer for ChannelObserver {
+    fn on_event(&self, event: &Event) {
+        let update = match event {
+            Event::PackageStarted { package } => Update::Started { name: package.name.clone() },
+            Event::VolumeDownloaded { volume, bytes, from_cache, done, total, .. } => Update::Downloaded {
+                volume: volume.to_string(), bytes: *bytes, from_cache: *from_cache, done: *done, total: *total,
+            },
+            Event::ExtractionProgress { archive, done, total, .. } => Update::Extracted {
+                archive: archive.to_string(), done: *done, total: *total,
+            },
+            Event::PackageFinished { package, report } => Update::Finished {
+                id: package.id.clone(), action: report.action, errors: report.errors.clone(),
+            },
+            //=-- The question itself arrives through ChannelDecisions
+            Event::PromptNeeded { .. } => return,
+        };
+        let _ = self.0.send(update);
+    }
+}
+
+//=-- Asks the UI thread and waits for the answer. If the screen is gone, the default answer is used.
+struct ChannelDecisions(Sender<Update>);
+
+impl ChannelDecisions {
+    fn ask(&self, package: &Package, prompt: Prompt) -> Option<Answer> {
+        let (reply, answer) = mpsc::channel();
+        self.0.send(Update::Ask { package: package.name.clone(), prompt, reply }).ok()?;
+        answer.recv().ok()
+    }
+}
+
+impl DecisionProvider for ChannelDecisions {
+    fn confirm(&self, package: &Package, question: &Question) -> bool {
+        let (lines, default) = match question {
+            Question::ReloadSameVersion { version } => {
+                (vec![format!("{} is already installed.", version), "Reload it anyway?".to_string()], false)
+            },
+            Question::Downgrade { installed, available } => (vec![
+                format!("The installed version ({}) is newer than the repository's ({}).", installed, available),
+                "Install the older version?".to_string(),
+            ], false),
+            Question::ContinueUpdate { available, notes, .. } => {
+                let mut lines = Vec::new();
+                if notes.is_empty() {
+                    lines.push(format!("No release notes found for {}", available));
+                }
+                for entry in notes.iter() {
+                    lines.push(format!("== {} ==", entry.version));
+                    lines.extend(entry.notes.lines().map(str::to_string));
+                }
+                lines.push(String::new());
+                lines.push(format!("Continue with update to {}?", available));
+                (lines, true)
+            },
+            Question::ExistingOutputDir { .. } | Question::Password { .. } => return false,
+        };
+        match self.ask(package, Prompt::Confirm { lines, default }) {
+            Some(Answer::Confirm(yes)) => yes,
+            _ => default,
+        }
+    }
+
+    fn existing_output_dir(&self, package: &Package, output_dir: &Path) -> OutputDirAction {
+        match self.ask(package, Prompt::OutputDir { path: output_dir.display().to_string() }) {
+            Some(Answer::OutputDir(action)) => action,
+            _ => OutputDirAction::Overwrite,
+        }
+    }
+
+    fn password(&self, package: &Package, retry: bool) -> Option<String> {
+        match self.ask(package, Prompt::Password { retry }) {
+            Some(Answer::Password(password)) => password,
+            _ => None,
+        }
+    }
+}
+
+fn worker(ctx: &LoaderContext, packages: &[(&String, &Package)], jobs: Receiver<Job>, updates: Sender<Update>) {
+    let refresh = || {
+        for (i, (_, package)) in packages.iter().enumerate() {
+            let _ = updates.send(Update::Status(i, package_status(ctx, package)));
+        }
+    };
+    for job in jobs {
+        let reports = match job {
+            Job::Refresh => None,
+            Job::Install(indices) => Some(indices.into_iter()
+                .map(|i| process_package(ctx, packages[i].1, &VersionRequest::Latest))
+                .collect()),
+            Job::Upgrade(indices) => {
+                let selected: Vec<(&String, &Package)> = indices.into_iter().map(|i| packages[i]).collect();
+                Some(upgrade_packages(ctx, &selected))
+            },
+        };
+        if reports.is_some() {
+            if let Err(e) = cleanup_package_dir(&ctx.dl_dir) {
+                error!("Error cleaning up download directory: {}", e);
+            }
+        }
+        refresh();
+        let _ = updates.send(Update::JobDone(reports));
+    }
+}
+
+//=-- Runs the full-screen menu until the user quits. Returns the kind of the last job's failures, like the
+//=-- line menu does for its last round.
+pub fn run(ctx: &mut LoaderContext, packages: &[(&String, &Package)]) -> Result<Option<ErrorKind>, Box<dyn std::error::Error>> {
+    let (job_tx, job_rx) = mpsc::channel();
+    let (update_tx, update_rx) = mpsc::channel();
+    ctx.observer = Box::new(ChannelObserver(update_tx.clone()));
+    ctx.decisions = Box::new(ChannelDecisions(update_tx.clone()));
+    let ctx = &*ctx;
+
+    let mut terminal = ratatui::try_init()?;
+    logging::set_console_sink(Some(log_to_panel));
+    let result = thread::scope(|scope| {
+        scope.spawn(move || worker(ctx, packages, job_rx, update_tx));
+        let mut app = App::new(packages, &ctx.output_root);
+        app.start(&job_tx, Job::Refresh);
+        let result = app.run(&mut terminal, &job_tx, update_rx);
+        //=-- Ends the worker, which has no job running once the app returns
+        drop(job_tx);
+        result
+    });
+    logging::set_console_sink(None);
+    ratatui::restore();
+
+    let reports = result?;
+    Ok(ErrorKind::combined(reports.iter().filter_map(|report| report.error_kind)))
+}
+
+#[derive(Default)]
+struct Progress {
+    package: Option<String>,
+    download: Option<(usize, usize, String)>, //=-- done, total, label
+    extract: Option<(usize, usize, String)>,
+}
+
+struct PendingPrompt {
+    package: String,
+    prompt: Prompt,
+    reply: Sender<Answer>,
+    input: String,
+}
+
+struct App<'a> {
+    packages: &'a [(&'a String, &'a Package)],
+    output_root: &'a Path,
+    statuses: Vec<Option<PackageStatus>>,
+    checked: BTreeSet<usize>,
+    table: TableState,
+    search: String,
+    searching: bool,
+    busy: bool,
+    quitting: bool,
+    progress: Progress,
+    results: HashMap<String, (Action, Vec<String>)>,
+    last_reports: Vec<InstallReport>,
+    log: Vec<(Level, String)>,
+    prompt: Option<PendingPrompt>,
+}
+
+impl<'a> App<'a> {
+    fn new(packages: &'a [(&'a String, &'a Package)], output_root: &'a Path) -> App<'a> {
+        App {
+            packages,
+            output_root,
+            statuses: packages.iter().map(|_| None).collect(),
+            checked: BTreeSet::new(),
+            table: TableState::default().with_selected(Some(0)),
+            search: String::new(),
+            searching: false,
+            busy: false,
+            quitting: false,
+            progress: Progress::default(),
+            results: HashMap::new(),
+            last_reports: Vec::new(),
+            log: Vec::new(),
+            prompt: None,
+        }
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal, jobs: &Sender<Job>, updates: Receiver<Update>) -> io::Result<Vec<InstallReport>> {
+        while !self.quitting || self.busy {
+            while let Ok(update) = updates.try_recv() {
+                self.apply(update);
+            }
+            self.log.append(&mut LOG_LINES.lock().unwrap_or_else(|e| e.into_inner()));
+            let excess = self.log.len().saturating_sub(LOG_KEEP);
+            self.log.drain(..excess);
+
+            terminal.draw(|frame| self.draw(frame))?;
+            if event::poll(Duration::from_millis(100))? {
+                if let TermEvent::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press {
+                        self.on_key(key, jobs);
+                    }
+                }
+            }
+        }
+        Ok(std::mem::take(&mut self.last_reports))
+    }
+
+    fn apply(&mut self, update: Update) {
+        match update {
+            Update::Status(i, status) => self.statuses[i] = Some(status),
+            Update::Started { name } => {
+                self.progress = Progress { package: Some(name), ..Progress::default() };
+            },
+            Update::Downloaded { volume, bytes, from_cache, done, total } => {
+                let source = if from_cache { ", cached" } else { "" };
+                self.progress.download = Some((done, total, format!("{} ({}{})", volume, format_size(bytes), source)));
+            },
+            Update::Extracted { archive, done, total } => self.progress.extract = Some((done, total, archive)),
+            Update::Finished { id, action, errors } => {
+                self.results.insert(id, (action, errors));
+            },
+            Update::Ask { package, prompt, reply } => {
+                self.prompt = Some(PendingPrompt { package, prompt, reply, input: String::new() });
+            },
+            Update::JobDone(reports) => {
+                self.busy = false;
+                self.progress = Progress::default();
+                if let Some(reports) = reports {
+                    self.last_reports = reports;
+                }
+            },
+        }
+    }
+
+    //=-- Indices (into packages) of the rows shown, narrowed by the search
+    fn visible(&self) -> Vec<usize> {
+        let search = self.search.to_lowercase();
+        self.packages.iter().enumerate()
+            .filter(|(_, (_, package))| {
+                search.is_empty()
+                    || package.name.to_lowercase().contains(&search)
+                    || package.id.to_lowercase().contains(&search)
+                    || package.description.to_lowercase().contains(&search)
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    fn current(&self) -> Option<usize> {
+        self.table.selected().and_then(|row| self.visible().get(row).copied())
+    }
+
+    fn start(&mut self, jobs: &Sender<Job>, job: Job) {
+        if jobs.send(job).is_ok() {
+            self.busy = true;
+        }
+    }
+
+    //=-- The checked packages, or the one under the cursor when none are checked
+    fn targets(&self) -> Vec<usize> {
+        if self.checked.is_empty() {
+            self.current().into_iter().collect()
+        } else {
+            self.checked.iter().copied().collect()
+        }
+    }
+
+    fn move_cursor(&mut self, delta: isize) {
+        let count = self.visible().len();
+        if count == 0 {
+            self.table.select(None);
+            return;
+        }
+        let row = self.table.selected().unwrap_or(0) as isize + delta;
+        self.table.select(Some(row.clamp(0, count as isize - 1) as usize));
+    }
+
+    fn on_key(&mut self, key: KeyEvent, jobs: &Sender<Job>) {
+        if self.prompt.is_some() {
+            self.on_prompt_key(key);
+            return;
+        }
+        if self.searching {
+            match key.code {
+                KeyCode::Enter => self.searching = false,
+                KeyCode::Esc => {
+                    self.searching = false;
+                    self.search.clear();
+                },
+                KeyCode::Backspace => {
+                    self.search.pop();
+                },
+                KeyCode::Char(c) => self.search.push(c),
+                _ => {},
+            }
+            self.move_cursor(0);
+            return;
+        }
+
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => self.quitting = true,
+            KeyCode::Char('q') => self.quitting = true,
+            KeyCode::Esc if !self.search.is_empty() => {
+                self.search.clear();
+                self.move_cursor(0);
+            },
+            KeyCode::Up | KeyCode::Char('k') => self.move_cursor(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_cursor(1),
+            KeyCode::PageUp => self.move_cursor(-10),
+            KeyCode::PageDown => self.move_cursor(10),
+            KeyCode::Home => self.move_cursor(isize::MIN / 2),
+            KeyCode::End => self.move_cursor(isize::MAX / 2),
+            KeyCode::Char(' ') => {
+                if let Some(i) = self.current() {
+                    if !self.checked.remove(&i) {
+                        self.checked.insert(i);
+                    }
+                }
+                self.move_cursor(1);
+            },
+            KeyCode::Char('a') => {
+                let visible = self.visible();
+                if visible.iter().all(|i| self.checked.contains(i)) {
+                    self.checked.clear();
+                } else {
+                    self.checked.extend(visible);
+                }
+            },
+            KeyCode::Char('/') => self.searching = true,
+            _ if self.busy => {},
+            KeyCode::Enter | KeyCode::Char('i') => {
+                let targets = self.targets();
+                if !targets.is_empty() {
+                    self.start(jobs, Job::Install(targets));
+                }
+            },
+            KeyCode::Char('u') => {
+                let targets = if self.checked.is_empty() {
+                    (0..self.packages.len()).collect()
+                } else {
+                    self.checked.iter().copied().collect()
+                };
+                self.start(jobs, Job::Upgrade(targets));
+            },
+            KeyCode::Char('r') => self.start(jobs, Job::Refresh),
+            _ => {},
+        }
+    }
+
+    fn on_prompt_key(&mut self, key: KeyEvent) {
+        let Some(pending) = self.prompt.as_mut() else {
+            return;
+        };
+        let answer = match &pending.prompt {
+            Prompt::Confirm { default, .. } => match key.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => Answer::Confirm(true),
+                KeyCode::Char('n') | KeyCode::Char('N') => Answer::Confirm(false),
+                KeyCode::Enter | KeyCode::Esc => Answer::Confirm(*default),
+                _ => return,
+            },
+            Prompt::OutputDir { .. } => match key.code {
+                KeyCode::Char('d') | KeyCode::Char('D') => Answer::OutputDir(OutputDirAction::Delete),
+                KeyCode::Char('o') | KeyCode::Char('O') | KeyCode::Enter | KeyCode::Esc => Answer::OutputDir(OutputDirAction::Overwrite),
+                _ => return,
+            },
+            Prompt::Password { .. } => match key.code {
+                KeyCode::Enter => Answer::Password(Some(std::mem::take(&mut pending.input)).filter(|input| !input.is_empty())),
+                KeyCode::Esc => Answer::Password(None),
+                KeyCode::Backspace => {
+                    pending.input.pop();
+                    return;
+                },
+                KeyCode::Char(c) => {
+                    pending.input.push(c);
+                    return;
+                },
+                _ => return,
+            },
+        };
+        if let Some(pending) = self.prompt.take() {
+            let _ = pending.reply.send(answer);
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, progress, log, footer] = Layout::vertical([
+            Constraint::Min(6),
+            Constraint::Length(5),
+            Constraint::Length(8),
+            Constraint::Length(1),
+        ]).areas(frame.area());
+        let [list, details] = Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(main);
+
+        self.draw_list(frame, list);
+        self.draw_details(frame, details);
+        self.draw_progress(frame, progress);
+        self.draw_log(frame, log);
+        self.draw_footer(frame, footer);
+        if self.prompt.is_some() {
+            self.draw_prompt(frame);
+        }
+    }
+
+    fn draw_list(&mut self, frame: &mut Frame, area: Rect) {
+        let rows: Vec<Row> = self.visible().into_iter().map(|i| {
+            let (_, package) = self.packages[i];
+            let check = if self.checked.contains(&i) { "[x]" } else { "[ ]" };
+            let (installed, available, state) = match &self.statuses[i] {
+                Some(status) => (
+                    status.installed_version.clone().unwrap_or_else(|| "-".to_string()),
+                    status.remote_version.clone().unwrap_or_else(|| "-".to_string()),
+                    Span::styled(status.state.to_string(), state_style(status.state)),
+                ),
+                None => ("...".to_string(), "...".to_string(), Span::raw("checking")),
+            };
+            Row::new(vec![Cell::from(check), Cell::from(package.name.clone()), Cell::from(installed), Cell::from(available), Cell::from(state)])
+        }).collect();
+
+        let title = match self.checked.len() {
+            0 => " Packages ".to_string(),
+            n => format!(" Packages ({} selected) ", n),
+        };
+        let table = Table::new(rows, [
+            Constraint::Length(3),
+            Constraint::Fill(1),
+            Constraint::Length(15),
+            Constraint::Length(15),
+            Constraint::Length(13),
+        ])
+            .header(Row::new(vec!["", "Package", "Installed", "Available", "State"]).bold())
+            .block(Block::bordered().title(title))
+            .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, area, &mut self.table);
+    }
+
+    fn draw_details(&self, frame: &mut Frame, area: Rect) {
+        let mut lines = Vec::new();
+        if let Some(i) = self.current() {
+            let (_, package) = self.packages[i];
+            let field = |name: &str, value: String| Line::from(vec![Span::raw(format!("{}: ", name)).bold(), Span::raw(value)]);
+            lines.push(Line::from(package.name.clone()).bold());
+            lines.push(Line::from(package.description.clone()));
+            lines.push(Line::default());
+            lines.push(field("Id", package.id.clone()));
+            lines.push(field("Output", self.output_root.join(&package.output_path).display().to_string()));
+            if !package.pinned_version.trim().is_empty() {
+                lines.push(field("Pinned", package.pinned_version.trim().to_string()));
+            }
+            if let Some(status) = &self.statuses[i] {
+                lines.push(field("Channel", status.channel.clone()));
+                let installed = match (&status.installed_version, &status.installed_channel) {
+                    (Some(version), Some(channel)) => format!("{} ({})", version, channel),
+                    _ => "not installed".to_string(),
+                };
+                lines.push(field("Installed", installed));
+                lines.push(field("Available", status.remote_version.clone().unwrap_or_else(|| "-".to_string())));
+                lines.push(field("State", status.state.to_string()));
+                if let Some(error) = &status.error {
+                    lines.push(Line::from(error.clone()).red());
+                }
+            }
+            if let Some((action, errors)) = self.results.get(&package.id) {
+                lines.push(Line::default());
+                lines.push(field("Last run", action.to_string()));
+                lines.extend(errors.iter().map(|error| Line::from(error.clone()).red()));
+            }
+        }
+        let details = Paragraph::new(lines).wrap(Wrap { trim: false }).block(Block::bordered().title(" Details "));
+        frame.render_widget(details, area);
+    }
+
+    fn draw_progress(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::bordered().title(" Progress ");
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+        let [status, download, extract] = Layout::vertical([Constraint::Length(1); 3]).areas(inner);
+
+        let text = match (&self.progress.package, self.busy) {
+            (Some(name), true) => format!("Working on {}", name),
+            (None, true) => "Checking packages...".to_string(),
+            (_, false) if self.last_reports.is_empty() => "Idle".to_string(),
+            (_, false) => {
+                let failed = self.last_reports.iter().filter(|report| report.action == Action::Failed).count();
+                let changed = self.last_reports.iter().filter(|report| !matches!(report.action, Action::Skipped | Action::Failed)).count();
+                format!("Idle. Last run: {} changed, {} failed", changed, failed)
+            },
+        };
+        frame.render_widget(Paragraph::new(text), status);
+        for ((done, total, label), area, name) in [
+            (&self.progress.download, download, "Download"),
+            (&self.progress.extract, extract, "Extract"),
+        ].into_iter().filter_map(|(progress, area, name)| progress.as_ref().map(|progress| (progress, area, name))) {
+            let gauge = Gauge::default()
+                .ratio(*done as f64 / (*total).max(1) as f64)
+                .label(format!("{} {}/{}: {}", name, done, total, label))
+                .gauge_style(Style::new().fg(Color::Cyan));
+            frame.render_widget(gauge, area);
+        }
+    }
+
+    fn draw_log(&self, frame: &mut Frame, area: Rect) {
+        let height = area.height.saturating_sub(2) as usize;
+        let lines: Vec<Line> = self.log[self.log.len().saturating_sub(height)..].iter()
+            .map(|(level, line)| Line::styled(line.clone(), level_style(*level)))
+            .collect();
+        frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(" Log ")), area);
+    }
+
+    fn draw_footer(&self, frame: &mut Frame, area: Rect) {
+        let text = if self.searching {
+            format!("Search: {}_  (Enter keep, Esc clear)", self.search)
+        } else if self.quitting {
+            "Quitting after the current job...".to_string()
+        } else {
+            let search = if self.search.is_empty() { String::new() } else { format!("[search: {}]  ", self.search) };
+            format!("{}Up/Down move  Space select  a all  / search  Enter install  u upgrade  r refresh  q quit", search)
+        };
+        frame.render_widget(Paragraph::new(text).reversed(), area);
+    }
+
+    fn draw_prompt(&self, frame: &mut Frame) {
+        let Some(pending) = &self.prompt else {
+            return;
+        };
+        let (mut lines, hint) = match &pending.prompt {
+            Prompt::Confirm { lines, default } => (
+                lines.iter().map(|line| Line::from(line.clone())).collect::<Vec<Line>>(),
+                if *default { "(Y)es / (N)o, Enter = yes" } else { "(Y)es / (N)o, Enter = no" },
+            ),
+            Prompt::OutputDir { path } => (
+                vec![Line::from(format!("The output folder already exists: {}", path))],
+                "(O)verwrite or (D)elete it, Enter = overwrite",
+            ),
+            Prompt::Password { retry } => {
+                let mut lines = Vec::new();
+                if *retry {
+                    lines.push(Line::from("That password did not work.").red());
+                }
+                lines.push(Line::from(format!("Password: {}", "*".repeat(pending.input.chars().count()))));
+                (lines, "Enter to extract, Enter on a blank entry or Esc skips this package")
+            },
+        };
+        lines.push(Line::default());
+        lines.push(Line::from(hint).italic());
+
+        let area = frame.area();
+        let height = (lines.len() as u16 + 2).min(area.height);
+        let [popup] = Layout::vertical([Constraint::Length(height)]).flex(Flex::Center).areas(area);
+        let [popup] = Layout::horizontal([Constraint::Percentage(70)]).flex(Flex::Center).areas(popup);
+        frame.render_widget(Clear, popup);
+        let prompt = Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .block(Block::bordered().title(format!(" {} ", pending.package)).border_style(Style::new().fg(Color::Yellow)));
+        frame.render_widget(prompt, popup);
+    }
+}
+
+fn state_style(state: PackageState) -> Style {
+    match state {
+        PackageState::UpToDate => Style::new().fg(Color::Green),
+        PackageState::Outdated => Style::new().fg(Color::Yellow),
+        PackageState::NewerLocally => Style::new().fg(Color::Cyan),
+        PackageState::NotInstalled => Style::new(),
+        PackageState::Unreachable => Style::new().fg(Color::Red),
+    }
+}
+
+fn level_style(level: Level) -> Style {
+    match level {
+        Level::Error => Style::new().fg(Color::Red),
+        Level::Warn => Style::new().fg(Color::Yellow),
+        Level::Info => Style::new(),
+        Level::Debug | Level::Trace => Style::new().fg(Color::DarkGray),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(id: &str, name: &str, description: &str) -> Package {
+        serde_json::from_value(serde_json::json!({
+            "id": id, "name": name, "description": description, "repo_url": "", "output_path": id,
+            "password": "", "is_root": false,
+        })).unwrap()
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn fixture() -> (Vec<String>, Vec<Package>) {
+        let packages = vec![
+            package("editor", "Map Editor", "Edits maps"),
+            package("viewer", "Model Viewer", "Shows models"),
+            package("tools", "Shared Tools", "Used by the MAP editor"),
+        ];
+        (packages.iter().map(|package| package.id.clone()).collect(), packages)
+    }
+
+    //=-- Runs `test` on an app over the fixture packages with a job channel it can read back
+    fn with_app(test: impl FnOnce(&mut App, &Sender<Job>, &Receiver<Job>)) {
+        let (keys, packages) = fixture();
+        let entries: Vec<(&String, &Package)> = keys.iter().zip(packages.iter()).collect();
+        let (jobs, received) = mpsc::channel();
+        let mut app = App::new(&entries, Path::new("out"));
+        test(&mut app, &jobs, &received);
+    }
+
+    fn prompt(app: &mut App, prompt: Prompt) -> Receiver<Answer> {
+        let (reply, answer) = mpsc::channel();
+        app.prompt = Some(PendingPrompt { package: "Map Editor".to_string(), prompt, reply, input: String::new() });
+        answer
+    }
+
+    fn type_text(app: &mut App, jobs: &Sender<Job>, text: &str) {
+        for c in text.chars() {
+            app.on_key(key(KeyCode::Char(c)), jobs);
+        }
+    }
+
+    #[test]
+    fn search_matches_name_id_and_description_ignoring_case() {
+        with_app(|app, _, _| {
+            assert_eq!(app.visible(), vec![0, 1, 2]);
+            app.search = "map".to_string();
+            assert_eq!(app.visible(), vec![0, 2]);
+            app.search = "VIEWER".to_string();
+            assert_eq!(app.visible(), vec![1]);
+            app.search = "nothing".to_string();
+            assert!(app.visible().is_empty());
+        });
+    }
+
+    #[test]
+    fn targets_are_the_checked_packages_or_the_cursor_row() {
+        with_app(|app, _, _| {
+            app.table.select(Some(1));
+            assert_eq!(app.targets(), vec![1]);
+
+            //=-- The cursor row is a row of the filtered list
+            app.search = "tools".to_string();
+            app.table.select(Some(0));
+            assert_eq!(app.targets(), vec![2]);
+
+            app.checked.extend([2, 0]);
+            assert_eq!(app.targets(), vec![0, 2]);
+        });
+    }
+
+    #[test]
+    fn space_toggles_the_row_and_moves_down() {
+        with_app(|app, jobs, _| {
+            app.on_key(key(KeyCode::Char(' ')), jobs);
+            assert!(app.checked.contains(&0));
+            assert_eq!(app.table.selected(), Some(1));
+
+            app.on_key(key(KeyCode::Up), jobs);
+            app.on_key(key(KeyCode::Char(' ')), jobs);
+            assert!(app.checked.is_empty());
+        });
+    }
+
+    #[test]
+    fn a_checks_every_visible_row_then_clears() {
+        with_app(|app, jobs, _| {
+            app.search = "map".to_string();
+            app.on_key(key(KeyCode::Char('a')), jobs);
+            assert_eq!(app.checked.iter().copied().collect::<Vec<_>>(), vec![0, 2]);
+
+            app.on_key(key(KeyCode::Char('a')), jobs);
+            assert!(app.checked.is_empty());
+        });
+    }
+
+    #[test]
+    fn cursor_stays_within_the_visible_rows() {
+        with_app(|app, jobs, _| {
+            app.on_key(key(KeyCode::End), jobs);
+            assert_eq!(app.table.selected(), Some(2));
+            app.on_key(key(KeyCode::Down), jobs);
+            assert_eq!(app.table.selected(), Some(2));
+
+            app.on_key(key(KeyCode::Char('/')), jobs);
+            type_text(app, jobs, "viewer");
+            assert_eq!(app.table.selected(), Some(0));
+            app.on_key(key(KeyCode::Home), jobs);
+            assert_eq!(app.table.selected(), Some(0));
+        });
+    }
+
+    #[test]
+    fn search_mode_takes_typed_keys_until_enter_or_esc() {
+        with_app(|app, jobs, received| {
+            app.on_key(key(KeyCode::Char('/')), jobs);
+            assert!(app.searching);
+            //=-- 'q' and 'u' are search text here, not commands
+            type_text(app, jobs, "quxx");
+            app.on_key(key(KeyCode::Backspace), jobs);
+            assert_eq!(app.search, "qux");
+            assert!(!app.quitting);
+            assert!(received.try_recv().is_err());
+
+            app.on_key(key(KeyCode::Enter), jobs);
+            assert!(!app.searching);
+            assert_eq!(app.search, "qux");
+
+            app.on_key(key(KeyCode::Char('/')), jobs);
+            app.on_key(key(KeyCode::Esc), jobs);
+            assert!(!app.searching);
+            assert!(app.search.is_empty());
+        });
+    }
+
+    #[test]
+    fn enter_installs_the_targets() {
+        with_app(|app, jobs, received| {
+            app.checked.extend([1, 2]);
+            app.on_key(key(KeyCode::Enter), jobs);
+
+            assert!(app.busy);
+            assert!(matches!(received.try_recv(), Ok(Job::Install(targets)) if targets == vec![1, 2]));
+        });
+    }
+
+    #[test]
+    fn u_upgrades_every_package_when_none_are_checked() {
+        with_app(|app, jobs, received| {
+            app.on_key(key(KeyCode::Char('u')), jobs);
+
+            assert!(matches!(received.try_recv(), Ok(Job::Upgrade(targets)) if targets == vec![0, 1, 2]));
+        });
+    }
+
+    #[test]
+    fn no_job_starts_while_one_is_running() {
+        with_app(|app, jobs, received| {
+            app.busy = true;
+            for code in [KeyCode::Enter, KeyCode::Char('i'), KeyCode::Char('u'), KeyCode::Char('r')] {
+                app.on_key(key(code), jobs);
+            }
+            assert!(received.try_recv().is_err());
+
+            //=-- Moving and selecting still work
+            app.on_key(key(KeyCode::Char(' ')), jobs);
+            assert!(app.checked.contains(&0));
+        });
+    }
+
+    #[test]
+    fn q_and_ctrl_c_quit() {
+        with_app(|app, jobs, _| {
+            app.on_key(key(KeyCode::Char('q')), jobs);
+            assert!(app.quitting);
+        });
+        with_app(|app, jobs, _| {
+            app.on_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL), jobs);
+            assert!(app.quitting);
+        });
+    }
+
+    #[test]
+    fn keys_go_to_an_open_prompt() {
+        with_app(|app, jobs, received| {
+            let answer = prompt(app, Prompt::Confirm { lines: Vec::new(), default: false });
+            app.on_key(key(KeyCode::Char('q')), jobs);
+            app.on_key(key(KeyCode::Char('u')), jobs);
+
+            assert!(!app.quitting);
+            assert!(received.try_recv().is_err());
+            assert!(app.prompt.is_some());
+            assert!(answer.try_recv().is_err());
+        });
+    }
+
+    #[test]
+    fn confirm_answers_yes_no_or_the_default() {
+        with_app(|app, _, _| {
+            for (code, default, expected) in [
+                (KeyCode::Char('y'), false, true),
+                (KeyCode::Char('N'), true, false),
+                (KeyCode::Enter, true, true),
+                (KeyCode::Esc, false, false),
+            ] {
+                let answer = prompt(app, Prompt::Confirm { lines: Vec::new(), default });
+                app.on_prompt_key(key(code));
+                assert!(matches!(answer.try_recv(), Ok(Answer::Confirm(yes)) if yes == expected), "{:?}", code);
+                assert!(app.prompt.is_none());
+            }
+        });
+    }
+
+    #[test]
+    fn output_dir_prompt_deletes_only_on_d() {
+        with_app(|app, _, _| {
+            let answer = prompt(app, Prompt::OutputDir { path: "out".to_string() });
+            app.on_prompt_key(key(KeyCode::Char('x')));
+            assert!(app.prompt.is_some());
+            app.on_prompt_key(key(KeyCode::Char('d')));
+            assert!(matches!(answer.try_recv(), Ok(Answer::OutputDir(OutputDirAction::Delete))));
+
+            let answer = prompt(app, Prompt::OutputDir { path: "out".to_string() });
+            app.on_prompt_key(key(KeyCode::Enter));
+            assert!(matches!(answer.try_recv(), Ok(Answer::OutputDir(OutputDirAction::Overwrite))));
+        });
+    }
+
+    #[test]
+    fn password_is_typed_then_sent_on_enter() {
+        with_app(|app, _, _| {
+            let answer = prompt(app, Prompt::Password { retry: false });
+            for c in "secrett".chars() {
+                app.on_prompt_key(key(KeyCode::Char(c)));
+            }
+            app.on_prompt_key(key(KeyCode::Backspace));
+            assert!(answer.try_recv().is_err());
+
+            app.on_prompt_key(key(KeyCode::Enter));
+            assert!(matches!(answer.try_recv(), Ok(Answer::Password(Some(password))) if password == "secret"));
+        });
+    }
+
+    #[test]
+    fn blank_password_or_esc_skips_the_package() {
+        with_app(|app, _, _| {
+            let answer = prompt(app, Prompt::Password { retry: true });
+            app.on_prompt_key(key(KeyCode::Enter));
+            assert!(matches!(answer.try_recv(), Ok(Answer::Password(None))));
+
+            let answer = prompt(app, Prompt::Password { retry: true });
+            app.on_prompt_key(key(KeyCode::Char('x')));
+            app.on_prompt_key(key(KeyCode::Esc));
+            assert!(matches!(answer.try_recv(), Ok(Answer::Password(None))));
+        });
+    }
+}
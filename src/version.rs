@@ -0,0 +1,221 @@
+use std::cmp::Ordering;
+use std::fmt;
+use serde::{Deserialize, Serialize};
+
+use crate::error::LoaderError;
+
+//=-- How a package's version.txt is interpreted. Set per package with `version_scheme`
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum VersionScheme {
+    #[default]
+    DateIteration, //=-- YYYY-MM-DD--N
+    Semver,        //=-- 1.2.3, 1.2.3-beta.1
+    Integer,       //=-- 42
+}
+
+impl fmt::Display for VersionScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VersionScheme::DateIteration => write!(f, "date-iteration"),
+            VersionScheme::Semver => write!(f, "semver"),
+            VersionScheme::Integer => write!(f, "integer"),
+        }
+    }
+}
+
+//=-- Versions are only ever compared within one scheme, since each package declares a single scheme
+#[derive(Debug, Clone)]
+pub enum Version {
+    //=-- `date` is validated as zero-padded YYYY-MM-DD, so comparing it as a string is chronological
+    DateIteration { date: String, iteration: u32 },
+    Semver(semver::Version),
+    Integer(u64),
+}
+
+impl Version {
+    pub fn parse(version_str: &str, scheme: VersionScheme) -> Result<Self, Box<dyn std::error::Error>> {
+        let trimmed = version_str.trim();
+        let result = match scheme {
+            VersionScheme::DateIteration => parse_date_iteration(trimmed),
+            VersionScheme::Semver => semver::Version::parse(trimmed)
+                .map(Version::Semver)
+                .map_err(|e| format!("expected a semantic version like 1.2.3 ({})", e)),
+            VersionScheme::Integer => trimmed.parse::<u64>()
+                .map(Version::Integer)
+                .map_err(|_| "expected a non-negative integer".to_string()),
+        };
+        //=-- A Config error: a well-formed published version that doesn't parse means version_scheme is wrong
+        result.map_err(|e| LoaderError::Config(format!("Invalid {} version \"{}\": {}", scheme, trimmed, e)).into())
+    }
+
+    fn scheme_rank(&self) -> u8 {
+        match self {
+            Version::DateIteration { .. } => 0,
+            Version::Semver(_) => 1,
+            Version::Integer(_) => 2,
+        }
+    }
+
+    //=-- The date part of a date-iteration version, used for "latest as of" lookups
+    pub fn date(&self) -> Option<&str> {
+        match self {
+            Version::DateIteration { date, .. } => Some(date),
+            _ => None,
+        }
+    }
+}
+
+//=-- Semver versions compare by precedence: build metadata (1.2.3+build.5) doesn't make a version newer or different
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Version::DateIteration { date, iteration }, Version::DateIteration { date: other_date, iteration: other_iteration }) => {
+                (date, iteration).cmp(&(other_date, other_iteration))
+            },
+            (Version::Semver(version), Version::Semver(other)) => version.cmp_precedence(other),
+            (Version::Integer(number), Version::Integer(other)) => number.cmp(other),
+            //=-- Mixed schemes never meet in practice; any fixed order will do
+            _ => self.scheme_rank().cmp(&other.scheme_rank()),
+        }
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Version {}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Version::DateIteration { date, iteration } => write!(f, "{}--{}", date, iteration),
+            Version::Semver(version) => write!(f, "{}", version),
+            Version::Integer(number) => write!(f, "{}", number),
+        }
+    }
+}
+
+fn parse_date_iteration(version_str: &str) -> Result<Version, String> {
+    let (date, iteration) = version_str.split_once("--")
+        .ok_or("expected YYYY-MM-DD--N")?;
+    validate_date(date)?;
+    let iteration = iteration.parse::<u32>()
+        .map_err(|_| format!("iteration \"{}\" is not a non-negative integer", iteration))?;
+    Ok(Version::DateIteration { date: date.to_string(), iteration })
+}
+
+//=-- Accepts only real calendar dates written as zero-padded YYYY-MM-DD
+pub fn validate_date(date: &str) -> Result<(), String> {
+    let parts: Vec<&str> = date.split('-').collect();
+    let well_formed = parts.len() == 3
+        && parts[0].len() == 4
+        && parts[1].len() == 2
+        && parts[2].len() == 2
+        && parts.iter().all(|part| part.bytes().all(|b| b.is_ascii_digit()));
+    if !well_formed {
+        return Err(format!("date \"{}\" is not in YYYY-MM-DD format", date));
+    }
+
+    let year: u32 = parts[0].parse().unwrap();
+    let month: u32 = parts[1].parse().unwrap();
+    let day: u32 = parts[2].parse().unwrap();
+    let is_leap = (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400);
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if is_leap => 29,
+        2 => 28,
+        _ => return Err(format!("date \"{}\" has an invalid month", date)),
+    };
+    if day == 0 || day > days_in_month {
+        return Err(format!("date \"{}\" has an invalid day", date));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date_iteration(version: &str) -> Result<Version, Box<dyn std::error::Error>> {
+        Version::parse(version, VersionScheme::DateIteration)
+    }
+
+    fn semver(version: &str) -> Version {
+        Version::parse(version, VersionScheme::Semver).unwrap()
+    }
+
+    #[test]
+    fn leap_days_follow_the_gregorian_calendar() {
+        assert!(date_iteration("2024-02-29--1").is_ok());
+        assert!(date_iteration("2000-02-29--1").is_ok());
+        assert!(date_iteration("2023-02-29--1").is_err());
+        assert!(date_iteration("1900-02-29--1").is_err());
+    }
+
+    #[test]
+    fn impossible_months_and_days_are_rejected() {
+        assert!(validate_date("2024-13-01").unwrap_err().contains("invalid month"));
+        assert!(validate_date("2024-00-10").unwrap_err().contains("invalid month"));
+        assert!(validate_date("2024-01-32").unwrap_err().contains("invalid day"));
+        assert!(validate_date("2024-04-31").unwrap_err().contains("invalid day"));
+        assert!(validate_date("2024-01-00").unwrap_err().contains("invalid day"));
+    }
+
+    #[test]
+    fn dates_must_be_zero_padded() {
+        assert!(validate_date("2024-1-05").is_err());
+        assert!(validate_date("24-01-05").is_err());
+        assert!(validate_date("2024-01-05-").is_err());
+        assert!(date_iteration("2024-01-05").is_err());
+        assert!(date_iteration("2024-01-05--x").is_err());
+    }
+
+    #[test]
+    fn date_iteration_orders_by_date_then_iteration() {
+        assert!(date_iteration("2024-01-31--9").unwrap() < date_iteration("2024-02-01--1").unwrap());
+        assert!(date_iteration("2024-02-01--2").unwrap() < date_iteration("2024-02-01--10").unwrap());
+    }
+
+    #[test]
+    fn pre_releases_come_before_the_release() {
+        let ordered = ["1.0.0-alpha", "1.0.0-alpha.1", "1.0.0-alpha.beta", "1.0.0-beta", "1.0.0-beta.2", "1.0.0-beta.11", "1.0.0-rc.1", "1.0.0", "1.0.1-alpha"];
+        for pair in ordered.windows(2) {
+            assert!(semver(pair[0]) < semver(pair[1]), "{} < {}", pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    fn numeric_pre_release_identifiers_sort_before_alphanumeric_ones() {
+        assert!(semver("1.0.0-1") < semver("1.0.0-alpha"));
+        assert!(semver("1.0.0-alpha.9") < semver("1.0.0-alpha.a"));
+        //=-- Numeric identifiers compare as numbers, not text
+        assert!(semver("1.0.0-rc.2") < semver("1.0.0-rc.10"));
+    }
+
+    #[test]
+    fn build_metadata_is_ignored() {
+        assert_eq!(semver("1.2.3+build.1"), semver("1.2.3+build.2"));
+        assert_eq!(semver("1.2.3"), semver("1.2.3+build.1"));
+        assert_eq!(semver("1.2.3-beta+a").cmp(&semver("1.2.3-beta+b")), Ordering::Equal);
+        assert!(semver("1.2.3+build.9") < semver("1.2.4"));
+        assert!(semver("1.2.3-beta+zzz") < semver("1.2.3"));
+    }
+
+    #[test]
+    fn integers_compare_numerically() {
+        let parse = |version| Version::parse(version, VersionScheme::Integer).unwrap();
+        assert!(parse("9") < parse("10"));
+        assert!(Version::parse("-1", VersionScheme::Integer).is_err());
+    }
+}
@@ -0,0 +1,148 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use serde::Deserialize;
+
+use crate::error::ErrorKind;
+use crate::output::{Action, PackageState};
+use crate::{
+    check_package, cleanup_package_dir, logging, process_checked_package, sorted_packages,
+    LoaderContext, Settings,
+};
+
+const DEFAULT_INTERVAL_MINUTES: u64 = 60;
+const DEFAULT_JITTER_MINUTES: u64 = 5;
+
+//=-- What `watch` does when a package has a newer version. Set per package with `update_policy`
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum UpdatePolicy {
+    #[default]
+    Auto,    //=-- Install updates of installed packages
+    Install, //=-- Like auto, and also install packages that are not installed yet
+    Notify,  //=-- Only log that an update is available
+    Off,     //=-- Not checked at all
+}
+
+//=-- Set by the signal handler; the current package is finished before the watch stops
+static STOP: AtomicBool = AtomicBool::new(false);
+
+fn minutes_setting(settings: &Settings, key: &str, default: u64) -> u64 {
+    settings.main.get(key)
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .unwrap_or(default)
+}
+
+//=-- Random extra wait in 0..=max_secs, so machines sharing a repository don't all check at once
+fn jitter_secs(max_secs: u64) -> u64 {
+    let mut bytes = [0u8; 8];
+    if max_secs == 0 || getrandom::getrandom(&mut bytes).is_err() {
+        return 0;
+    }
+    u64::from_le_bytes(bytes) % (max_secs + 1)
+}
+
+//=-- Sleeps in short steps so a signal is noticed quickly. Returns false when the watch should stop.
+fn sleep_until(deadline: Instant) -> bool {
+    while Instant::now() < deadline {
+        if STOP.load(Ordering::SeqCst) {
+            return false;
+        }
+        thread::sleep(Duration::from_millis(250).min(deadline.saturating_duration_since(Instant::now())));
+    }
+    !STOP.load(Ordering::SeqCst)
+}
+
+//=-- One pass over every package, following each package's update_policy. Returns the kind of the
+//=-- failures, if any package failed.
+fn check_packages(ctx: &LoaderContext, settings: &Settings) -> Option<ErrorKind> {
+    let (mut updated, mut available) = (0, 0);
+    let mut failures = Vec::new();
+    for (_, package) in sorted_packages(settings) {
+        if STOP.load(Ordering::SeqCst) {
+            break;
+        }
+        if package.update_policy == UpdatePolicy::Off {
+            continue;
+        }
+
+        let (status, remote) = check_package(ctx, package);
+        let wanted = match status.state {
+            PackageState::Outdated => true,
+            PackageState::NotInstalled => package.update_policy == UpdatePolicy::Install,
+            PackageState::Unreachable => {
+                warn!("{} is not available: {}", package.name, status.error.as_deref().unwrap_or_default());
+                failures.push(status.error_kind.unwrap_or(ErrorKind::Other));
+                false
+            },
+            PackageState::UpToDate | PackageState::NewerLocally => false,
+        };
+        if !wanted {
+            debug!("{}: {}", package.name, status.state);
+            continue;
+        }
+        if package.update_policy == UpdatePolicy::Notify {
+            info!("{}: update available, {} -> {}", package.name,
+                status.installed_version.as_deref().unwrap_or("-"), status.remote_version.as_deref().unwrap_or("-"));
+            available += 1;
+            continue;
+        }
+
+        let Some(remote) = remote else { continue };
+        let report = process_checked_package(ctx, package, remote);
+        match report.action {
+            Action::Failed => {
+                error!("{}: update failed: {}", package.name, report.errors.join("; "));
+                failures.extend(report.error_kind);
+            },
+            Action::Skipped => {},
+            action => {
+                info!("{}: {} {}", package.name, action, report.remote_version.as_deref().unwrap_or("-"));
+                updated += 1;
+            },
+        }
+    }
+
+    if let Err(e) = cleanup_package_dir(&ctx.dl_dir) {
+        error!("Error cleaning up download directory: {}", e);
+    }
+    info!("Check finished: {} updated, {} failed, {} waiting for a manual update", updated, failures.len(), available);
+    ErrorKind::combined(failures)
+}
+
+//=-- `watch [--once]`: checks every package on [main] watch_interval_minutes (plus up to
+//=-- watch_jitter_minutes) and applies updates without prompting, until SIGINT/SIGTERM (Ctrl+C on Windows).
+//=-- With --once, returns the kind of the failures of the single check.
+pub fn run(ctx: &LoaderContext, settings: &Settings, config_dir: &Path, once: bool) -> Result<Option<ErrorKind>, Box<dyn std::error::Error>> {
+    ctrlc::set_handler(|| {
+        if STOP.swap(true, Ordering::SeqCst) {
+            //=-- A second signal doesn't wait for the current package
+            std::process::exit(130);
+        }
+        warn!("Stopping after the current package (signal again to stop right away)");
+    }).map_err(|e| format!("Failed to install the signal handler: {}", e))?;
+
+    let interval = Duration::from_secs(minutes_setting(settings, "watch_interval_minutes", DEFAULT_INTERVAL_MINUTES).max(1) * 60);
+    let jitter_max = minutes_setting(settings, "watch_jitter_minutes", DEFAULT_JITTER_MINUTES) * 60;
+    info!("Watching {} package(s), checking every {} min", settings.packages.len(), interval.as_secs() / 60);
+
+    loop {
+        let failures = check_packages(ctx, settings);
+        if once {
+            return Ok(failures);
+        }
+
+        let wait = interval + Duration::from_secs(jitter_secs(jitter_max));
+        info!("Next check in {} min {} s", wait.as_secs() / 60, wait.as_secs() % 60);
+        if !sleep_until(Instant::now() + wait) {
+            break;
+        }
+        //=-- Rotate the log if the last checks filled it
+        if let Err(e) = logging::open_file(settings, config_dir) {
+            warn!("Log file cannot be opened: {}", e);
+        }
+    }
+    info!("Watch stopped");
+    Ok(None)
+}
@@ -0,0 +1,112 @@
+//=-- zip, tar.gz, tar.xz and tar.zst volumes are extracted in-process, their format detected from the magic bytes
+mod common;
+
+use common::{setup, Route, RunResult, Sandbox, V1};
+
+const FILES: &[(&str, &str)] = &[("readme.txt", "first release"), ("bin/tool.cfg", "mode=1")];
+const PASSWORD: &str = "zip-secret";
+
+fn assert_installed(sandbox: &Sandbox, result: &RunResult) {
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert!(result.output.contains("Successfully extracted archives"), "{}", result.output);
+    assert_eq!(sandbox.installed_version("tool").as_deref(), Some(V1));
+    assert_eq!(sandbox.read_output("tool", "readme.txt").as_deref(), Some("first release"));
+    assert_eq!(sandbox.read_output("tool", "bin/tool.cfg").as_deref(), Some("mode=1"));
+}
+
+#[test]
+fn zip_is_detected_and_extracted() {
+    let (server, sandbox) = setup("zip", &["tool"], "");
+    server.publish_archive("tool", V1, &common::zip(FILES, ""), 1);
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_installed(&sandbox, &result);
+    assert!(result.output.contains("(zip)"), "{}", result.output);
+}
+
+#[test]
+fn split_zip_is_joined_and_extracted() {
+    let (server, sandbox) = setup("zip-split", &["tool"], "");
+    server.publish_archive("tool", V1, &common::zip(FILES, ""), 3);
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_installed(&sandbox, &result);
+    assert!(result.output.contains("(zip)"), "{}", result.output);
+}
+
+#[test]
+fn tar_xz_is_detected_and_extracted() {
+    let (server, sandbox) = setup("tar-xz", &["tool"], "");
+    server.publish_archive("tool", V1, &common::tar_xz(FILES), 2);
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_installed(&sandbox, &result);
+    assert!(result.output.contains("(tar.xz)"), "{}", result.output);
+}
+
+#[test]
+fn tar_zst_is_detected_and_extracted() {
+    let (server, sandbox) = setup("tar-zst", &["tool"], "");
+    server.publish_archive("tool", V1, &common::tar_zst(FILES), 2);
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_installed(&sandbox, &result);
+    assert!(result.output.contains("(tar.zst)"), "{}", result.output);
+}
+
+#[test]
+fn unknown_magic_bytes_are_an_extraction_error() {
+    let (server, sandbox) = setup("unknown-format", &["tool"], "");
+    let volume = b"not an archive".to_vec();
+    server.set_text("/tool/version.txt", V1);
+    server.set_text("/tool/filelist.txt", &format!("tool--n1.globby {}\n", common::sha256(&volume)));
+    server.set("/tool/tool--n1.globby", Route::Body(volume));
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_eq!(result.status, Some(6), "{}", result.output);
+    assert!(result.output.contains("Cannot detect the archive format"), "{}", result.output);
+    assert!(sandbox.installed_version("tool").is_none());
+}
+
+#[test]
+fn encrypted_split_zip_uses_the_configured_password() {
+    let (server, sandbox) = setup("zip-aes", &["tool"], "");
+    let config = std::fs::read_to_string(sandbox.dir.join("Config.toml")).unwrap();
+    std::fs::write(sandbox.dir.join("Config.toml"), config.replace("password = \"\"", &format!("password = \"{}\"", PASSWORD))).unwrap();
+    server.publish_archive("tool", V1, &common::zip(FILES, PASSWORD), 2);
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_installed(&sandbox, &result);
+    assert!(!result.output.contains("Enter password"), "{}", result.output);
+}
+
+#[test]
+fn wrong_zip_password_is_asked_again() {
+    let (server, sandbox) = setup("zip-retry", &["tool"], "");
+    server.publish_archive("tool", V1, &common::zip(FILES, PASSWORD), 1);
+
+    let result = sandbox.run(&["install", "tool"], &["not-it", PASSWORD]);
+
+    assert_installed(&sandbox, &result);
+    assert!(result.output.contains("Enter password for extraction: "), "{}", result.output);
+    assert!(result.output.contains("Wrong password"), "{}", result.output);
+    assert!(result.output.contains("press Enter [on a blank entry] to skip this package"), "{}", result.output);
+    assert!(!result.output.contains(PASSWORD), "{}", result.output);
+}
+
+#[test]
+fn skipping_the_password_retry_fails_with_wrong_password() {
+    let (server, sandbox) = setup("zip-skip", &["tool"], "");
+    server.publish_archive("tool", V1, &common::zip(FILES, PASSWORD), 1);
+
+    let result = sandbox.run(&["install", "tool"], &["not-it", ""]);
+
+    assert_eq!(result.status, Some(7), "{}", result.output);
+    assert!(sandbox.installed_version("tool").is_none());
+}
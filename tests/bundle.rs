@@ -0,0 +1,248 @@
+//=-- export-bundle / import-bundle: a bundle made online installs on a machine without network access
+mod common;
+
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use common::{file_url, local_package_config, package_config, setup, RepoServer, Sandbox, V1, V2};
+
+#[test]
+fn exported_bundle_imports_offline() {
+    let (server, online) = setup("bundle-export", &["tool"], "");
+    server.publish("tool", V1, &[("readme.txt", "first release")], 2);
+    let bundle = online.dir.join("tools.bundle");
+
+    let exported = online.run(&["export-bundle", bundle.to_str().unwrap(), "tool"], &[]);
+    assert_eq!(exported.status, Some(0), "{}", exported.output);
+
+    //=-- Neither the packages' nor the loader's own version_url can be reached
+    let (offline_server, offline) = setup("bundle-import", &["tool"], "");
+    offline.go_offline(&offline_server);
+
+    let imported = offline.run(&["import-bundle", bundle.to_str().unwrap()], &[]);
+
+    assert_eq!(imported.status, Some(0), "{}", imported.output);
+    assert_eq!(offline.installed_version("tool").as_deref(), Some(V1));
+    assert_eq!(offline.read_output("tool", "readme.txt").as_deref(), Some("first release"));
+}
+
+#[test]
+fn bundle_only_installs_the_selected_packages() {
+    let (server, online) = setup("bundle-select", &["tool", "other"], "");
+    server.publish("tool", V1, &[("readme.txt", "tool release")], 1);
+    server.publish("other", V1, &[("readme.txt", "other release")], 1);
+    let bundle = online.dir.join("tools.bundle");
+    let exported = online.run(&["export-bundle", bundle.to_str().unwrap()], &[]);
+    assert_eq!(exported.status, Some(0), "{}", exported.output);
+
+    let (_server, target) = setup("bundle-select-import", &["tool", "other"], "");
+    let imported = target.run(&["import-bundle", bundle.to_str().unwrap(), "other"], &[]);
+
+    assert_eq!(imported.status, Some(0), "{}", imported.output);
+    assert_eq!(target.installed_version("other").as_deref(), Some(V1));
+    assert_eq!(target.installed_version("tool"), None);
+}
+
+#[test]
+fn bundle_keeps_the_format_from_the_manifest() {
+    //=-- archive_format in the config is wrong on purpose: the manifest's format has to win, also from a bundle
+    let server = RepoServer::start();
+    let config = |sandbox: &Sandbox| {
+        let extra = format!("manifest_url = \"{}\"\narchive_format = \"zip\"", server.url("/tool/manifest.json"));
+        sandbox.write_config(&server, "", &package_config(&server, "tool", &extra));
+    };
+    let online = Sandbox::new("bundle-format-export");
+    config(&online);
+    let volumes = server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+    server.set_text("/tool/manifest.json", &format!(
+        "{{\"version\": \"{}\", \"format\": \"tar.gz\", \"volumes\": [{{\"name\": \"{}\"}}]}}",
+        V1, volumes[0]
+    ));
+    let bundle = online.dir.join("tools.bundle");
+    let exported = online.run(&["export-bundle", bundle.to_str().unwrap()], &[]);
+    assert_eq!(exported.status, Some(0), "{}", exported.output);
+
+    let offline = Sandbox::new("bundle-format-import");
+    config(&offline);
+    offline.go_offline(&server);
+    let imported = offline.run(&["import-bundle", bundle.to_str().unwrap()], &[]);
+
+    assert_eq!(imported.status, Some(0), "{}", imported.output);
+    assert_eq!(offline.read_output("tool", "readme.txt").as_deref(), Some("first release"));
+}
+
+#[test]
+fn partial_export_writes_the_bundle_and_exits_with_the_failure() {
+    let (server, online) = setup("bundle-partial", &["tool", "other"], "");
+    server.publish("tool", V1, &[("readme.txt", "tool release")], 1);
+    server.publish("other", V1, &[("readme.txt", "other release")], 1);
+    server.remove("/other/version.txt");
+    let bundle = online.dir.join("tools.bundle");
+
+    let exported = online.run(&["export-bundle", bundle.to_str().unwrap()], &[]);
+
+    assert_eq!(exported.status, Some(4), "{}", exported.output);
+    assert!(exported.output.contains("Package other was not added to the bundle"), "{}", exported.output);
+    assert!(bundle.exists());
+}
+
+#[test]
+fn package_with_a_missing_channel_is_left_out_of_the_bundle() {
+    let server = RepoServer::start();
+    let online = Sandbox::new("bundle-channel");
+    let packages = [package_config(&server, "tool", ""), package_config(&server, "other", "channel = \"nightly\"")];
+    online.write_config(&server, "", &packages.join("\n"));
+    server.publish("tool", V1, &[("readme.txt", "tool release")], 1);
+    let bundle = online.dir.join("tools.bundle");
+
+    let exported = online.run(&["export-bundle", bundle.to_str().unwrap()], &[]);
+
+    assert_eq!(exported.status, Some(3), "{}", exported.output);
+    assert!(exported.output.contains("has no \"nightly\" channel"), "{}", exported.output);
+    assert!(bundle.exists());
+    assert!(!online.dir.join("tools.bundle.partial").exists());
+}
+
+//=-- Packs and signs a release into a file:// repository and exports it. Returns the exporting sandbox, the bundle
+//=-- and the public_key line for the importing config.
+fn signed_bundle(name: &str) -> (Sandbox, PathBuf, String) {
+    let server = RepoServer::start();
+    let online = Sandbox::new(name);
+    let repo = online.dir.join("repo");
+    let source = online.dir.join("source");
+    fs::create_dir_all(&source).unwrap();
+    fs::write(source.join("readme.txt"), "signed release").unwrap();
+    let key = online.dir.join("signing.key");
+    online.write_config(&server, "", &local_package_config("tool", &format!("{}/", file_url(&repo)), "archive_format = \"tar.gz\""));
+    let packed = online.run(&[
+        "pack", "tool", source.to_str().unwrap(), repo.to_str().unwrap(),
+        "--version", V1, "--sign-key", key.to_str().unwrap(),
+    ], &[]);
+    assert_eq!(packed.status, Some(0), "{}", packed.output);
+    let public_key = packed.output.lines()
+        .find_map(|line| line.split("(public_key in the package config): ").nth(1))
+        .unwrap_or_else(|| panic!("no public key printed:\n{}", packed.output))
+        .trim()
+        .to_string();
+    let key_line = format!("archive_format = \"tar.gz\"\npublic_key = \"{}\"", public_key);
+    online.write_config(&server, "", &local_package_config("tool", &format!("{}/", file_url(&repo)), &key_line));
+
+    let bundle = online.dir.join("tools.bundle");
+    let exported = online.run(&["export-bundle", bundle.to_str().unwrap()], &[]);
+    assert_eq!(exported.status, Some(0), "{}", exported.output);
+    (online, bundle, key_line)
+}
+
+//=-- An offline sandbox with the package configured under `extra`, e.g. its public_key
+fn offline_target(name: &str, extra: &str) -> Sandbox {
+    let (server, target) = setup(name, &["tool"], extra);
+    target.go_offline(&server);
+    target
+}
+
+//=-- Rewrites one file inside a bundle, leaving the rest as exported
+fn rewrite_bundle(bundle: &Path, path: &str, mut change: impl FnMut(Vec<u8>) -> Vec<u8>) {
+    let mut archive = tar::Archive::new(fs::File::open(bundle).unwrap());
+    let mut builder = tar::Builder::new(Vec::new());
+    for entry in archive.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        let entry_path = entry.path().unwrap().to_string_lossy().replace('\\', "/");
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content).unwrap();
+        if entry_path == path {
+            content = change(content);
+        }
+        let mut header = entry.header().clone();
+        header.set_size(content.len() as u64);
+        header.set_cksum();
+        builder.append_data(&mut header, &entry_path, content.as_slice()).unwrap();
+    }
+    fs::write(bundle, builder.into_inner().unwrap()).unwrap();
+}
+
+#[test]
+fn signed_package_is_verified_from_the_bundle() {
+    let (_online, bundle, key_line) = signed_bundle("bundle-signed");
+    let target = offline_target("bundle-signed-import", &key_line);
+
+    let imported = target.run(&["import-bundle", bundle.to_str().unwrap()], &[]);
+
+    assert_eq!(imported.status, Some(0), "{}", imported.output);
+    assert_eq!(target.installed_version("tool").as_deref(), Some(V1));
+    assert_eq!(target.read_output("tool", "readme.txt").as_deref(), Some("signed release"));
+}
+
+#[test]
+fn bundle_with_a_changed_signed_filelist_is_rejected() {
+    let (_online, bundle, key_line) = signed_bundle("bundle-signed-changed");
+    rewrite_bundle(&bundle, "tool/signed/filelist.txt", |mut content| {
+        content.extend_from_slice(b"extra--n9.globby\n");
+        content
+    });
+    let target = offline_target("bundle-signed-changed-import", &key_line);
+
+    let imported = target.run(&["import-bundle", bundle.to_str().unwrap()], &[]);
+
+    assert_eq!(imported.status, Some(5), "{}", imported.output);
+    assert!(imported.output.contains("Signature check failed for tool/signed/filelist.txt"), "{}", imported.output);
+    assert!(target.installed_version("tool").is_none());
+}
+
+#[test]
+fn bundle_volumes_must_match_the_signed_filelist() {
+    //=-- bundle.json and the volume agree with each other, but not with what was signed
+    let (_online, bundle, key_line) = signed_bundle("bundle-signed-volume");
+    let forged = common::tar_gz(&[("readme.txt", "forged release")]);
+    let mut volume = String::new();
+    rewrite_bundle(&bundle, "bundle.json", |content| {
+        let mut manifest: serde_json::Value = serde_json::from_slice(&content).unwrap();
+        let file = &mut manifest["packages"][0]["files"][0];
+        volume = file["name"].as_str().unwrap().to_string();
+        file["sha256"] = common::sha256(&forged).into();
+        file["size"] = forged.len().into();
+        serde_json::to_vec(&manifest).unwrap()
+    });
+    rewrite_bundle(&bundle, &format!("tool/{}", volume), |_| forged.clone());
+    let target = offline_target("bundle-signed-volume-import", &key_line);
+
+    let imported = target.run(&["import-bundle", bundle.to_str().unwrap()], &[]);
+
+    assert_eq!(imported.status, Some(5), "{}", imported.output);
+    assert!(imported.output.contains("Bundled volumes of tool do not match its signed filelist.txt"), "{}", imported.output);
+    assert!(target.installed_version("tool").is_none());
+}
+
+#[test]
+fn signed_package_bundled_without_its_signature_is_rejected() {
+    //=-- Exported by a machine whose config has no public_key, so the bundle carries no signed list
+    let (server, online) = setup("bundle-unsigned", &["tool"], "");
+    server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+    let bundle = online.dir.join("tools.bundle");
+    let exported = online.run(&["export-bundle", bundle.to_str().unwrap()], &[]);
+    assert_eq!(exported.status, Some(0), "{}", exported.output);
+    let (_signed, _, key_line) = signed_bundle("bundle-unsigned-key");
+    let target = offline_target("bundle-unsigned-import", &key_line);
+
+    let imported = target.run(&["import-bundle", bundle.to_str().unwrap()], &[]);
+
+    assert_eq!(imported.status, Some(5), "{}", imported.output);
+    assert!(imported.output.contains("Bundle has no signed filelist or manifest for tool"), "{}", imported.output);
+    assert!(target.installed_version("tool").is_none());
+}
+
+#[test]
+fn bundle_with_another_version_than_the_pin_is_skipped() {
+    let (server, online) = setup("bundle-pinned", &["tool"], "");
+    server.publish("tool", V2, &[("readme.txt", "second release")], 1);
+    let bundle = online.dir.join("tools.bundle");
+    let exported = online.run(&["export-bundle", bundle.to_str().unwrap()], &[]);
+    assert_eq!(exported.status, Some(0), "{}", exported.output);
+    let target = offline_target("bundle-pinned-import", &format!("pinned_version = \"{}\"", V1));
+
+    let imported = target.run(&["import-bundle", bundle.to_str().unwrap()], &[]);
+
+    assert_eq!(imported.status, Some(0), "{}", imported.output);
+    assert!(imported.output.contains(&format!("it is pinned to {}, the bundle has {}", V1, V2)), "{}", imported.output);
+    assert!(target.installed_version("tool").is_none());
+}
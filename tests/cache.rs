@@ -0,0 +1,144 @@
+//=-- The download cache: hits without network access, ETag revalidation, eviction and the `cache` command
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+use common::{package_config, RepoServer, Route, Sandbox, V1};
+
+const CACHE_ON: &str = "cache_max_mb = 64";
+
+fn cached(name: &str, ids: &[&str], main_extra: &str) -> (RepoServer, Sandbox) {
+    let server = RepoServer::start();
+    let sandbox = Sandbox::new(name);
+    let packages: Vec<String> = ids.iter().map(|id| package_config(&server, id, "")).collect();
+    sandbox.write_config(&server, main_extra, &packages.join("\n"));
+    (server, sandbox)
+}
+
+fn objects_dir(sandbox: &Sandbox) -> PathBuf {
+    sandbox.dir.join("cache").join("objects")
+}
+
+fn object_names(sandbox: &Sandbox) -> Vec<String> {
+    fs::read_dir(objects_dir(sandbox))
+        .map(|entries| entries.flatten().map(|entry| entry.file_name().to_string_lossy().into_owned()).collect())
+        .unwrap_or_default()
+}
+
+//=-- Hex text of a simple LCG stream: about half its length survives gzip, unlike repeated text
+fn incompressible(len: usize) -> String {
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    (0..len / 2)
+        .map(|_| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            format!("{:02x}", (state >> 56) as u8)
+        })
+        .collect()
+}
+
+#[test]
+fn cached_volume_is_reused_without_downloading_it_again() {
+    let (server, sandbox) = cached("cache-hit", &["tool"], CACHE_ON);
+    let volumes = server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+    sandbox.run(&["install", "tool"], &[]);
+
+    //=-- The repository can no longer serve the volume, only the cache can
+    server.set(&format!("/tool/{}", volumes[0]), Route::Status(500));
+    let result = sandbox.run(&["install", "tool"], &["Y"]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert!(result.output.contains("Using cached copy of"), "{}", result.output);
+    assert_eq!(server.hits(&format!("/tool/{}", volumes[0])), 1);
+    assert_eq!(sandbox.read_output("tool", "readme.txt").as_deref(), Some("first release"));
+}
+
+#[test]
+fn corrupt_cached_volume_is_downloaded_again() {
+    let (server, sandbox) = cached("cache-corrupt", &["tool"], CACHE_ON);
+    let volumes = server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+    sandbox.run(&["install", "tool"], &[]);
+    let objects = object_names(&sandbox);
+    assert_eq!(objects.len(), 1);
+    fs::write(objects_dir(&sandbox).join(&objects[0]), b"changed on disk").unwrap();
+
+    let result = sandbox.run(&["install", "tool"], &["Y"]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert!(!result.output.contains("Using cached copy of"), "{}", result.output);
+    assert_eq!(server.hits(&format!("/tool/{}", volumes[0])), 2);
+    assert_eq!(sandbox.read_output("tool", "readme.txt").as_deref(), Some("first release"));
+    assert_eq!(common::sha256(&fs::read(objects_dir(&sandbox).join(&objects[0])).unwrap()), objects[0]);
+}
+
+#[test]
+fn volume_without_hash_is_revalidated_with_its_etag() {
+    let (server, sandbox) = cached("cache-etag", &["tool"], CACHE_ON);
+    let archive = common::tar_gz(&[("readme.txt", "first release")]);
+    server.set_text("/tool/version.txt", V1);
+    server.set_text("/tool/filelist.txt", "tool--n1.globby\n");
+    server.set("/tool/tool--n1.globby", Route::Tagged { body: archive, etag: "\"v1\"".to_string() });
+    sandbox.run(&["install", "tool"], &[]);
+    assert_eq!(server.not_modified_hits("/tool/tool--n1.globby"), 0);
+
+    let result = sandbox.run(&["install", "tool"], &["Y"]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert!(result.output.contains("Using cached copy of"), "{}", result.output);
+    assert_eq!(server.hits("/tool/tool--n1.globby"), 2);
+    assert_eq!(server.not_modified_hits("/tool/tool--n1.globby"), 1);
+    assert_eq!(sandbox.read_output("tool", "readme.txt").as_deref(), Some("first release"));
+}
+
+#[test]
+fn least_recently_used_volume_is_evicted_over_the_size_limit() {
+    //=-- Each volume is about 0.7 MB, so the 1 MB cache only holds one of them
+    let (server, sandbox) = cached("cache-evict", &["first", "second"], "cache_max_mb = 1");
+    server.publish("first", V1, &[("data.txt", &incompressible(1_400_000))], 1);
+    server.publish("second", V1, &[("data.txt", &incompressible(1_500_000))], 1);
+
+    sandbox.run(&["install", "first"], &[]);
+    assert_eq!(object_names(&sandbox).len(), 1);
+    sandbox.run(&["install", "second"], &[]);
+
+    let list = sandbox.run(&["cache", "list"], &[]);
+    assert_eq!(list.status, Some(0), "{}", list.output);
+    assert!(list.output.contains("1 object(s)"), "{}", list.output);
+    assert!(list.output.contains(&server.url("/second/second--n1.globby")), "{}", list.output);
+    assert!(!list.output.contains(&server.url("/first/first--n1.globby")), "{}", list.output);
+    assert_eq!(object_names(&sandbox).len(), 1);
+}
+
+#[test]
+fn cache_list_and_clear() {
+    let (server, sandbox) = cached("cache-command", &["tool"], CACHE_ON);
+    let volumes = server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+    sandbox.run(&["install", "tool"], &[]);
+    let objects = object_names(&sandbox);
+
+    let list = sandbox.run(&["cache", "list"], &[]);
+    assert_eq!(list.status, Some(0), "{}", list.output);
+    assert!(list.output.contains(&objects[0][..12]), "{}", list.output);
+    assert!(list.output.contains(&server.url(&format!("/tool/{}", volumes[0]))), "{}", list.output);
+    assert!(list.output.contains("1 object(s)"), "{}", list.output);
+
+    let clear = sandbox.run(&["cache", "clear"], &[]);
+    assert_eq!(clear.status, Some(0), "{}", clear.output);
+    assert!(!sandbox.dir.join("cache").exists());
+
+    let empty = sandbox.run(&["cache", "list"], &[]);
+    assert!(empty.output.contains("(empty)"), "{}", empty.output);
+}
+
+#[test]
+fn interrupted_download_is_a_network_error_and_leaves_nothing_in_the_cache() {
+    let (server, sandbox) = cached("cache-truncated", &["tool"], CACHE_ON);
+    let archive = common::tar_gz(&[("readme.txt", "first release")]);
+    let volumes = server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+    server.set(&format!("/tool/{}", volumes[0]), Route::Truncated { sent: archive.len() / 2, body: archive });
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_eq!(result.status, Some(4), "{}", result.output);
+    assert!(object_names(&sandbox).is_empty(), "{:?}", object_names(&sandbox));
+}
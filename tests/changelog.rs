@@ -0,0 +1,70 @@
+//=-- `changelog <id>`: the release notes between the installed and the available version
+mod common;
+
+use common::{package_config, RepoServer, Sandbox, V1, V2};
+
+fn with_changelog(name: &str) -> (RepoServer, Sandbox) {
+    let server = RepoServer::start();
+    let sandbox = Sandbox::new(name);
+    let changelog_url = format!("changelog_url = \"{}\"", server.url("/tool/CHANGELOG.md"));
+    sandbox.write_config(&server, "", &package_config(&server, "tool", &changelog_url));
+    server.set_text("/tool/CHANGELOG.md", &format!("## {}\nFaster startup\n\n## {}\nFirst release\n", V2, V1));
+    (server, sandbox)
+}
+
+#[test]
+fn installed_package_shows_the_notes_since_its_version() {
+    let (server, sandbox) = with_changelog("changelog-installed");
+    server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+    sandbox.run(&["install", "tool"], &[]);
+    server.publish("tool", V2, &[("readme.txt", "second release")], 1);
+
+    let result = sandbox.run(&["changelog", "tool"], &[]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert!(result.output.contains(&format!("Package tool [stable]: installed {}, available {}", V1, V2)), "{}", result.output);
+    assert!(result.output.contains(&format!("== {} ==\nFaster startup", V2)), "{}", result.output);
+    assert!(!result.output.contains("First release"), "{}", result.output);
+    //=-- Showing the notes installs nothing
+    assert_eq!(sandbox.installed_version("tool").as_deref(), Some(V1));
+}
+
+#[test]
+fn up_to_date_package_has_no_notes_to_show() {
+    let (server, sandbox) = with_changelog("changelog-current");
+    server.publish("tool", V2, &[("readme.txt", "second release")], 1);
+    sandbox.run(&["install", "tool"], &[]);
+
+    let result = sandbox.run(&["changelog", "tool"], &[]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert!(result.output.contains("No release notes between the installed and the available version"), "{}", result.output);
+}
+
+#[test]
+fn package_that_is_not_installed_shows_every_release() {
+    let (server, sandbox) = with_changelog("changelog-missing");
+    server.publish("tool", V2, &[("readme.txt", "second release")], 1);
+
+    let result = sandbox.run(&["changelog", "tool"], &[]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert!(result.output.contains(&format!("Package tool [stable]: not installed, available {}", V2)), "{}", result.output);
+    assert!(result.output.contains("Faster startup"), "{}", result.output);
+    assert!(result.output.contains("First release"), "{}", result.output);
+    assert!(sandbox.installed_version("tool").is_none());
+}
+
+#[test]
+fn package_without_changelog_url_is_a_config_error() {
+    let server = RepoServer::start();
+    let sandbox = Sandbox::new("changelog-none");
+    sandbox.write_config(&server, "", &package_config(&server, "tool", ""));
+    server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+
+    let result = sandbox.run(&["changelog", "tool"], &[]);
+
+    assert_eq!(result.status, Some(3), "{}", result.output);
+    assert!(result.output.contains("Package tool has no changelog_url in config"), "{}", result.output);
+    assert_eq!(server.hits("/tool/version.txt"), 0);
+}
@@ -0,0 +1,132 @@
+//=-- Release channels: --channel, then the package's channel, then [main] channel, then stable
+mod common;
+
+use common::{package_config, RepoServer, Sandbox, V1};
+
+//=-- A package whose URLs all contain {channel}: /<id>/<channel>/...
+fn channel_package(server: &RepoServer, id: &str, extra: &str) -> String {
+    format!(
+        "[packages.{id}]\nid = \"{id}\"\nname = \"Package {id}\"\ndescription = \"test package\"\nversion_url = \"{base}version.txt\"\nfilelist_url = \"{base}filelist.txt\"\nrepo_url = \"{base}\"\noutput_path = \"{id}\"\npassword = \"\"\nis_root = false\n{extra}\n",
+        id = id,
+        base = server.url(&format!("/{}/{{channel}}/", id)),
+        extra = extra,
+    )
+}
+
+//=-- Serves a release of `id` on `channel` whose readme.txt names the channel
+fn publish_channel(server: &RepoServer, id: &str, channel: &str) {
+    let readme = format!("{} build", channel);
+    server.publish_as(&format!("{}/{}", id, channel), V1, &[("readme.txt", &readme)], &[&format!("{}--n1.globby", id)]);
+}
+
+fn templated(name: &str, main_extra: &str, package_extra: &str) -> (RepoServer, Sandbox) {
+    let server = RepoServer::start();
+    let sandbox = Sandbox::new(name);
+    sandbox.write_config(&server, main_extra, &channel_package(&server, "tool", package_extra));
+    for channel in ["stable", "beta", "nightly"] {
+        publish_channel(&server, "tool", channel);
+    }
+    (server, sandbox)
+}
+
+#[test]
+fn default_channel_is_stable() {
+    let (_server, sandbox) = templated("channel-default", "", "");
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert_eq!(sandbox.read_output("tool", "readme.txt").as_deref(), Some("stable build"));
+    assert_eq!(sandbox.read_output("tool", "channel.txt").as_deref(), Some("stable"));
+}
+
+#[test]
+fn channel_is_filled_into_url_templates() {
+    let (server, sandbox) = templated("channel-template", "", "channel = \"beta\"");
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert_eq!(sandbox.read_output("tool", "readme.txt").as_deref(), Some("beta build"));
+    assert_eq!(sandbox.read_output("tool", "channel.txt").as_deref(), Some("beta"));
+    assert_eq!(server.hits("/tool/beta/tool--n1.globby"), 1);
+    assert_eq!(server.hits("/tool/stable/version.txt"), 0);
+}
+
+#[test]
+fn channel_table_overrides_the_package_urls() {
+    let server = RepoServer::start();
+    let sandbox = Sandbox::new("channel-table");
+    let overrides = format!(
+        "[packages.tool.channels.beta]\nversion_url = \"{}\"\nfilelist_url = \"{}\"\nrepo_url = \"{}\"\n",
+        server.url("/tool-beta/version.txt"), server.url("/tool-beta/filelist.txt"), server.url("/tool-beta/"),
+    );
+    sandbox.write_config(&server, "", &format!("{}{}", package_config(&server, "tool", "channel = \"beta\""), overrides));
+    server.publish("tool", V1, &[("readme.txt", "stable build")], 1);
+    server.publish("tool-beta", V1, &[("readme.txt", "beta build")], 1);
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert_eq!(sandbox.read_output("tool", "readme.txt").as_deref(), Some("beta build"));
+    assert_eq!(server.hits("/tool/version.txt"), 0);
+}
+
+#[test]
+fn command_line_channel_wins_over_package_and_main_channel() {
+    let (_server, sandbox) = templated("channel-cli", "channel = \"beta\"", "channel = \"stable\"");
+
+    let result = sandbox.run(&["--channel", "nightly", "install", "tool"], &[]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert_eq!(sandbox.read_output("tool", "readme.txt").as_deref(), Some("nightly build"));
+    assert_eq!(sandbox.read_output("tool", "channel.txt").as_deref(), Some("nightly"));
+}
+
+#[test]
+fn package_channel_wins_over_main_channel() {
+    let (_server, sandbox) = templated("channel-package", "channel = \"beta\"", "channel = \"stable\"");
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert_eq!(sandbox.read_output("tool", "readme.txt").as_deref(), Some("stable build"));
+}
+
+#[test]
+fn main_channel_applies_to_packages_without_one() {
+    let (_server, sandbox) = templated("channel-main", "channel = \"beta\"", "");
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert_eq!(sandbox.read_output("tool", "readme.txt").as_deref(), Some("beta build"));
+}
+
+#[test]
+fn package_without_the_main_channel_falls_back_to_stable() {
+    let server = RepoServer::start();
+    let sandbox = Sandbox::new("channel-fallback");
+    sandbox.write_config(&server, "channel = \"beta\"", &package_config(&server, "tool", ""));
+    server.publish("tool", V1, &[("readme.txt", "stable build")], 1);
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert!(result.output.contains("has no \"beta\" channel, using \"stable\""), "{}", result.output);
+    assert_eq!(sandbox.read_output("tool", "channel.txt").as_deref(), Some("stable"));
+}
+
+#[test]
+fn package_naming_a_missing_channel_is_a_config_error() {
+    let server = RepoServer::start();
+    let sandbox = Sandbox::new("channel-missing");
+    sandbox.write_config(&server, "", &package_config(&server, "tool", "channel = \"beta\""));
+    server.publish("tool", V1, &[("readme.txt", "stable build")], 1);
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_eq!(result.status, Some(3), "{}", result.output);
+    assert!(result.output.contains("has no \"beta\" channel"), "{}", result.output);
+    assert!(sandbox.installed_version("tool").is_none());
+}
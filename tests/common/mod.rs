@@ -0,0 +1,432 @@
+//=-- Shared harness for the end-to-end tests: a stand-in package repository served over HTTP from
+//=-- this process, and a scratch folder with a Config.toml to run the loader binary against.
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use sha2::{Digest, Sha256};
+
+pub const LOADER_VERSION: &str = "2024-01-01--1";
+//=-- Package versions the tests publish, oldest first
+pub const V1: &str = "2024-02-01--1";
+pub const V2: &str = "2024-03-01--1";
+
+//=-- What the server answers for one path
+#[derive(Clone)]
+pub enum Route {
+    Body(Vec<u8>),
+    Status(u16),
+    //=-- Announces the full length but closes the connection after `sent` bytes
+    Truncated { body: Vec<u8>, sent: usize },
+    //=-- Sends an ETag and answers 304 Not Modified when the request's If-None-Match matches it
+    Tagged { body: Vec<u8>, etag: String },
+}
+
+pub struct RepoServer {
+    pub base_url: String,
+    routes: Arc<Mutex<HashMap<String, Route>>>,
+    requests: Arc<Mutex<Vec<String>>>,
+    not_modified: Arc<Mutex<Vec<String>>>,
+}
+
+fn respond(mut stream: TcpStream, routes: &Mutex<HashMap<String, Route>>, requests: &Mutex<Vec<String>>, not_modified: &Mutex<Vec<String>>) {
+    let mut reader = BufReader::new(match stream.try_clone() {
+        Ok(clone) => clone,
+        Err(_) => return,
+    });
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+    //=-- Headers are read and ignored, except If-None-Match
+    let mut header = String::new();
+    let mut if_none_match = None;
+    while reader.read_line(&mut header).map(|n| n > 2).unwrap_or(false) {
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("if-none-match") {
+                if_none_match = Some(value.trim().to_string());
+            }
+        }
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+    requests.lock().unwrap().push(format!("{} {}", method, path));
+
+    let route = routes.lock().unwrap().get(&path).cloned().unwrap_or(Route::Status(404));
+    let mut extra_headers = String::new();
+    let (status, body, sent) = match route {
+        Route::Body(body) => {
+            let len = body.len();
+            (200, body, len)
+        },
+        Route::Status(status) => (status, format!("status {}", status).into_bytes(), 10),
+        Route::Truncated { body, sent } => (200, body, sent),
+        Route::Tagged { etag, .. } if if_none_match.as_deref() == Some(etag.as_str()) => {
+            not_modified.lock().unwrap().push(path.clone());
+            extra_headers = format!("ETag: {}\r\n", etag);
+            (304, Vec::new(), 0)
+        },
+        Route::Tagged { body, etag } => {
+            let len = body.len();
+            extra_headers = format!("ETag: {}\r\n", etag);
+            (200, body, len)
+        },
+    };
+    let reason = match status {
+        200 => "OK",
+        304 => "Not Modified",
+        404 => "Not Found",
+        500 => "Internal Server Error",
+        _ => "Status",
+    };
+    let head = format!("HTTP/1.1 {} {}\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n", status, reason, body.len(), extra_headers);
+    let _ = stream.write_all(head.as_bytes());
+    if method != "HEAD" {
+        let _ = stream.write_all(&body[..sent.min(body.len())]);
+    }
+    let _ = stream.flush();
+}
+
+impl RepoServer {
+    pub fn start() -> RepoServer {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind test server");
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let routes: Arc<Mutex<HashMap<String, Route>>> = Arc::default();
+        let requests: Arc<Mutex<Vec<String>>> = Arc::default();
+        let not_modified: Arc<Mutex<Vec<String>>> = Arc::default();
+
+        let (server_routes, server_requests, server_not_modified) = (routes.clone(), requests.clone(), not_modified.clone());
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let (routes, requests, not_modified) = (server_routes.clone(), server_requests.clone(), server_not_modified.clone());
+                thread::spawn(move || respond(stream, &routes, &requests, &not_modified));
+            }
+        });
+        RepoServer { base_url, routes, requests, not_modified }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    pub fn set(&self, path: &str, route: Route) {
+        self.routes.lock().unwrap().insert(path.to_string(), route);
+    }
+
+    pub fn set_text(&self, path: &str, text: &str) {
+        self.set(path, Route::Body(text.as_bytes().to_vec()));
+    }
+
+    pub fn remove(&self, path: &str) {
+        self.routes.lock().unwrap().remove(path);
+    }
+
+    //=-- Number of requests for `path` so far (any method)
+    pub fn hits(&self, path: &str) -> usize {
+        self.requests.lock().unwrap().iter().filter(|request| request.ends_with(&format!(" {}", path))).count()
+    }
+
+    //=-- Number of requests for `path` answered with 304 Not Modified (see Route::Tagged)
+    pub fn not_modified_hits(&self, path: &str) -> usize {
+        self.not_modified.lock().unwrap().iter().filter(|request| *request == path).count()
+    }
+
+    //=-- Publishes `files` as a package under /<id>/: version.txt, filelist.txt (with hashes) and
+    //=-- the archive as tar.gz volumes named <id>--nN.globby
+    pub fn publish(&self, id: &str, version: &str, files: &[(&str, &str)], volumes: usize) -> Vec<String> {
+        self.publish_archive(id, version, &tar_gz(files), volumes)
+    }
+
+    //=-- Like publish, for an archive that is already built (see tar_xz, tar_zst and zip)
+    pub fn publish_archive(&self, id: &str, version: &str, archive: &[u8], volumes: usize) -> Vec<String> {
+        let chunk = archive.len().div_ceil(volumes);
+        let mut filelist = String::new();
+        let mut names = Vec::new();
+        for (index, part) in archive.chunks(chunk).enumerate() {
+            let name = format!("{}--n{}.globby", id, index + 1);
+            filelist.push_str(&format!("{} {}\n", name, sha256(part)));
+            self.set(&format!("/{}/{}", id, name), Route::Body(part.to_vec()));
+            names.push(name);
+        }
+        self.set_text(&format!("/{}/version.txt", id), version);
+        self.set_text(&format!("/{}/filelist.txt", id), &filelist);
+        names
+    }
+}
+
+impl RepoServer {
+    //=-- Like publish, but the tar.gz archive is split into one volume per name in `names`
+    pub fn publish_as(&self, id: &str, version: &str, files: &[(&str, &str)], names: &[&str]) {
+        let archive = tar_gz(files);
+        let chunk = archive.len().div_ceil(names.len());
+        let mut filelist = String::new();
+        for (name, part) in names.iter().zip(archive.chunks(chunk)) {
+            filelist.push_str(&format!("{} {}\n", name, sha256(part)));
+            self.set(&format!("/{}/{}", id, name), Route::Body(part.to_vec()));
+        }
+        self.set_text(&format!("/{}/version.txt", id), version);
+        self.set_text(&format!("/{}/filelist.txt", id), &filelist);
+    }
+
+    //=-- Publishes `files` under /<id>/<version>/ for a versioned repository layout (see versioned_package_config),
+    //=-- adds the version to /<id>/versions.txt and points /<id>/version.txt at it
+    pub fn publish_version(&self, id: &str, version: &str, files: &[(&str, &str)]) {
+        let archive = tar_gz(files);
+        let name = format!("{}--n1.globby", id);
+        self.set_text(&format!("/{}/{}/filelist.txt", id, version), &format!("{} {}\n", name, sha256(&archive)));
+        self.set(&format!("/{}/{}/{}", id, version, name), Route::Body(archive));
+
+        let versions_path = format!("/{}/versions.txt", id);
+        let mut versions = match self.routes.lock().unwrap().get(&versions_path) {
+            Some(Route::Body(body)) => String::from_utf8_lossy(body).into_owned(),
+            _ => String::new(),
+        };
+        versions.push_str(&format!("{}\n", version));
+        self.set_text(&versions_path, &versions);
+        self.set_text(&format!("/{}/version.txt", id), version);
+    }
+}
+
+pub fn sha256(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+fn tar(files: &[(&str, &str)]) -> Vec<u8> {
+    let mut builder = tar::Builder::new(Vec::new());
+    for (name, content) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, name, content.as_bytes()).unwrap();
+    }
+    builder.into_inner().unwrap()
+}
+
+pub fn tar_gz(files: &[(&str, &str)]) -> Vec<u8> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(&tar(files)).unwrap();
+    encoder.finish().unwrap()
+}
+
+pub fn tar_xz(files: &[(&str, &str)]) -> Vec<u8> {
+    let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+    encoder.write_all(&tar(files)).unwrap();
+    encoder.finish().unwrap()
+}
+
+pub fn tar_zst(files: &[(&str, &str)]) -> Vec<u8> {
+    zstd::stream::encode_all(tar(files).as_slice(), 3).unwrap()
+}
+
+//=-- A zip of `files`, AES-256 encrypted when `password` isn't empty
+pub fn zip(files: &[(&str, &str)], password: &str) -> Vec<u8> {
+    let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for (name, content) in files {
+        let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        let options = if password.is_empty() { options } else { options.with_aes_encryption(zip::AesMode::Aes256, password) };
+        writer.start_file(*name, options).unwrap();
+        writer.write_all(content.as_bytes()).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+pub struct RunResult {
+    pub status: Option<i32>,
+    pub output: String,
+}
+
+//=-- A scratch folder with its own Config.toml, output root and temp folder
+pub struct Sandbox {
+    pub dir: PathBuf,
+}
+
+static SANDBOX_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+impl Sandbox {
+    pub fn new(name: &str) -> Sandbox {
+        let dir = std::env::temp_dir().join(format!(
+            "wbtl-e2e-{}-{}-{}",
+            name,
+            std::process::id(),
+            SANDBOX_COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        for sub in ["out", "tmp"] {
+            fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        //=-- The loader reads its own version.txt next to its executable, so every sandbox runs its own link to it
+        let exe = dir.join(loader_exe_name());
+        if fs::hard_link(env!("CARGO_BIN_EXE_wb-toolsloader"), &exe).is_err() {
+            fs::copy(env!("CARGO_BIN_EXE_wb-toolsloader"), &exe).unwrap();
+        }
+        fs::write(dir.join("version.txt"), LOADER_VERSION).unwrap();
+        Sandbox { dir }
+    }
+
+    //=-- This sandbox's loader executable, with version.txt (LOADER_VERSION) next to it
+    pub fn exe(&self) -> PathBuf {
+        self.dir.join(loader_exe_name())
+    }
+
+    pub fn output_dir(&self, package: &str) -> PathBuf {
+        self.dir.join("out").join(package)
+    }
+
+    pub fn read_output(&self, package: &str, file: &str) -> Option<String> {
+        fs::read_to_string(self.output_dir(package).join(file)).ok()
+    }
+
+    pub fn installed_version(&self, package: &str) -> Option<String> {
+        self.read_output(package, "version.txt")
+    }
+
+    //=-- Points every URL of `server` in Config.toml at a port nobody listens on, as if the network were down
+    pub fn go_offline(&self, server: &RepoServer) {
+        let config = fs::read_to_string(self.dir.join("Config.toml")).unwrap();
+        fs::write(self.dir.join("Config.toml"), config.replace(&server.base_url, "http://127.0.0.1:9")).unwrap();
+    }
+
+    //=-- Writes Config.toml: [main] pointing at the server, then the given package sections
+    pub fn write_config(&self, server: &RepoServer, main_extra: &str, packages: &str) {
+        server.set_text("/loader/version.txt", LOADER_VERSION);
+        let config = format!(
+            "[main]\nversion_url = \"{}\"\noutput_root = \"{}\"\n{}{}\n\n[archive]\nnanazip_exe = \"nanazip-not-needed\"\n\n{}\n",
+            server.url("/loader/version.txt"),
+            toml_path(&self.dir.join("out")),
+            //=-- The cache stays off unless a test turns it on through main_extra
+            if main_extra.contains("cache_max_mb") { "" } else { "cache_max_mb = 0\n" },
+            main_extra,
+            packages,
+        );
+        fs::write(self.dir.join("Config.toml"), config).unwrap();
+    }
+
+    //=-- The loader with this sandbox's config and temp folder, stdout going to stdout.txt
+    pub fn command(&self, args: &[&str]) -> Command {
+        self.command_for(&self.exe(), args)
+    }
+
+    //=-- Like command, but runs `exe` (e.g. a copy of the loader in another folder)
+    pub fn command_for(&self, exe: &Path, args: &[&str]) -> Command {
+        let mut command = Command::new(exe);
+        command
+            .arg("--config")
+            .arg(self.dir.join("Config.toml"))
+            .args(args)
+            .env("TMPDIR", self.dir.join("tmp"))
+            .env("TMP", self.dir.join("tmp"))
+            .env("TEMP", self.dir.join("tmp"))
+            .stdin(Stdio::piped())
+            .stdout(fs::File::create(self.dir.join("stdout.txt")).unwrap())
+            .stderr(Stdio::inherit());
+        command
+    }
+
+    //=-- Runs the loader with `args`, answering its prompts with `answers` (one line each, then EOF)
+    pub fn run(&self, args: &[&str], answers: &[&str]) -> RunResult {
+        self.run_command(self.command(args), answers)
+    }
+
+    pub fn run_command(&self, mut command: Command, answers: &[&str]) -> RunResult {
+        let stdout_path = self.dir.join("stdout.txt");
+        let mut child = command.spawn().expect("start loader");
+
+        let mut stdin = child.stdin.take().unwrap();
+        for answer in answers {
+            let _ = writeln!(stdin, "{}", answer);
+        }
+        drop(stdin);
+
+        //=-- Never let a stuck prompt hang the test run
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let status = loop {
+            if let Some(status) = child.try_wait().unwrap() {
+                break status.code();
+            }
+            if Instant::now() > deadline {
+                let _ = child.kill();
+                panic!("loader did not finish:\n{}", fs::read_to_string(&stdout_path).unwrap_or_default());
+            }
+            thread::sleep(Duration::from_millis(20));
+        };
+        RunResult { status, output: fs::read_to_string(&stdout_path).unwrap_or_default() }
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+//=-- File name of the loader executable, e.g. wb-toolsloader.exe on Windows
+pub fn loader_exe_name() -> String {
+    Path::new(env!("CARGO_BIN_EXE_wb-toolsloader")).file_name().unwrap().to_string_lossy().into_owned()
+}
+
+pub fn toml_path(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+//=-- file:///tmp/repo on Unix, file:///C:/repo on Windows
+pub fn file_url(path: &Path) -> String {
+    format!("file:///{}", toml_path(path).trim_start_matches('/'))
+}
+
+//=-- A fresh server and a sandbox whose config has one package section per id, each with `extra` appended
+pub fn setup(name: &str, ids: &[&str], extra: &str) -> (RepoServer, Sandbox) {
+    let server = RepoServer::start();
+    let sandbox = Sandbox::new(name);
+    let packages: Vec<String> = ids.iter().map(|id| package_config(&server, id, extra)).collect();
+    sandbox.write_config(&server, "", &packages.join("\n"));
+    (server, sandbox)
+}
+
+//=-- A package section serving /<id>/ from the test server
+pub fn package_config(server: &RepoServer, id: &str, extra: &str) -> String {
+    format!(
+        "[packages.{id}]\nid = \"{id}\"\nname = \"Package {id}\"\ndescription = \"test package\"\nversion_url = \"{version}\"\nfilelist_url = \"{filelist}\"\nrepo_url = \"{repo}\"\noutput_path = \"{id}\"\npassword = \"\"\nis_root = false\n{extra}\n",
+        id = id,
+        version = server.url(&format!("/{}/version.txt", id)),
+        filelist = server.url(&format!("/{}/filelist.txt", id)),
+        repo = server.url(&format!("/{}/", id)),
+        extra = extra,
+    )
+}
+
+//=-- A package section with a versioned repository layout: /<id>/<version>/ holds each published version,
+//=-- /<id>/versions.txt lists them
+pub fn versioned_package_config(server: &RepoServer, id: &str, extra: &str) -> String {
+    format!(
+        "[packages.{id}]\nid = \"{id}\"\nname = \"Package {id}\"\ndescription = \"test package\"\nversion_url = \"{version}\"\nfilelist_url = \"{filelist}\"\nrepo_url = \"{repo}\"\nversions_url = \"{versions}\"\noutput_path = \"{id}\"\npassword = \"\"\nis_root = false\n{extra}\n",
+        id = id,
+        version = server.url(&format!("/{}/version.txt", id)),
+        filelist = server.url(&format!("/{}/{{version}}/filelist.txt", id)),
+        repo = server.url(&format!("/{}/{{version}}/", id)),
+        versions = server.url(&format!("/{}/versions.txt", id)),
+        extra = extra,
+    )
+}
+
+//=-- A package section served from a local repository: `base` is a folder path or file:// URL ending in '/'
+pub fn local_package_config(id: &str, base: &str, extra: &str) -> String {
+    format!(
+        "[packages.{id}]\nid = \"{id}\"\nname = \"Package {id}\"\ndescription = \"test package\"\nversion_url = \"{base}version.txt\"\nfilelist_url = \"{base}filelist.txt\"\nrepo_url = \"{base}\"\noutput_path = \"{id}\"\npassword = \"\"\nis_root = false\n{extra}\n",
+        id = id,
+        base = base,
+        extra = extra,
+    )
+}
@@ -0,0 +1,96 @@
+//=-- The process exit code tells what kind of failure stopped the run or failed its packages
+mod common;
+
+use common::{setup, Route, V1, V2};
+
+#[test]
+fn successful_install_exits_with_zero() {
+    let (server, sandbox) = setup("exit-ok", &["tool"], "");
+    server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+}
+
+#[test]
+fn unknown_package_is_a_config_error() {
+    let (_server, sandbox) = setup("exit-config", &["tool"], "");
+
+    let result = sandbox.run(&["install", "missing"], &[]);
+
+    assert_eq!(result.status, Some(3), "{}", result.output);
+}
+
+#[test]
+fn unreachable_version_is_a_network_error() {
+    let (server, sandbox) = setup("exit-network", &["tool"], "");
+    server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+    server.remove("/tool/version.txt");
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_eq!(result.status, Some(4), "{}", result.output);
+}
+
+#[test]
+fn checksum_mismatch_is_an_integrity_error() {
+    let (server, sandbox) = setup("exit-integrity", &["tool"], "");
+    let volumes = server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+    server.set(&format!("/tool/{}", volumes[0]), Route::Body(common::tar_gz(&[("readme.txt", "tampered")])));
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_eq!(result.status, Some(5), "{}", result.output);
+}
+
+#[test]
+fn broken_archive_is_an_extraction_error_and_keeps_the_old_version() {
+    let (server, sandbox) = setup("exit-extraction", &["tool"], "");
+    server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+    sandbox.run(&["install", "tool"], &[]);
+
+    //=-- The volume matches its published hash, it just isn't a valid archive
+    let volumes = server.publish("tool", V2, &[("readme.txt", "second release")], 1);
+    let broken = b"\x1f\x8b\x08\x00 not really gzip".to_vec();
+    server.set(&format!("/tool/{}", volumes[0]), Route::Body(broken.clone()));
+    server.set_text("/tool/filelist.txt", &format!("{} {}\n", volumes[0], common::sha256(&broken)));
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_eq!(result.status, Some(6), "{}", result.output);
+    assert_eq!(sandbox.installed_version("tool").as_deref(), Some(V1));
+}
+
+#[test]
+fn upgrade_with_different_failures_is_mixed() {
+    let (server, sandbox) = setup("exit-mixed", &["gone", "tampered"], "");
+    let volumes = server.publish("tampered", V1, &[("readme.txt", "first release")], 1);
+    server.set(&format!("/tampered/{}", volumes[0]), Route::Body(common::tar_gz(&[("readme.txt", "tampered")])));
+
+    let result = sandbox.run(&["upgrade"], &[]);
+
+    assert_eq!(result.status, Some(10), "{}", result.output);
+    assert!(result.output.contains("gone: failed"), "{}", result.output);
+    assert!(result.output.contains("tampered: failed"), "{}", result.output);
+}
+
+#[test]
+fn failing_hook_is_a_hook_error() {
+    let (server, sandbox) = setup("exit-hook", &["tool"], "pre_install = 'exit 3'");
+    server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_eq!(result.status, Some(11), "{}", result.output);
+}
+
+#[test]
+fn version_that_does_not_match_the_scheme_is_a_config_error() {
+    let (server, sandbox) = setup("exit-scheme", &["tool"], "version_scheme = \"integer\"");
+    server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_eq!(result.status, Some(3), "{}", result.output);
+}
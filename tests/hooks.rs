@@ -0,0 +1,117 @@
+//=-- pre/post install and uninstall hooks: environment, failures, timeouts and rollback
+#![cfg(unix)]
+mod common;
+
+use std::fs;
+
+use common::{package_config, setup, Route, V1, V2};
+
+#[test]
+fn post_install_hook_sees_the_package() {
+    let (server, sandbox) = setup("hook-env", &["tool"], r#"post_install = 'echo "$WBTL_HOOK $WBTL_PACKAGE_ID $WBTL_PREVIOUS_VERSION>$WBTL_VERSION" > hook.txt; echo registered'"#);
+    server.publish("tool", V1, &[("readme.txt", "v1")], 1);
+    sandbox.run(&["install", "tool"], &[]);
+    server.publish("tool", V2, &[("readme.txt", "v2")], 1);
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert!(result.output.contains("[post_install] registered"), "{}", result.output);
+    let hook_output = fs::read_to_string(sandbox.dir.join("hook.txt")).unwrap();
+    assert_eq!(hook_output.trim(), format!("post_install tool {}>{}", V1, V2));
+}
+
+#[test]
+fn failing_pre_install_hook_stops_the_install() {
+    let (server, sandbox) = setup("hook-pre", &["tool"], "pre_install = 'exit 3'");
+    server.publish("tool", V1, &[("readme.txt", "v1")], 1);
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert!(result.output.contains("pre_install hook failed"), "{}", result.output);
+    assert!(sandbox.read_output("tool", "readme.txt").is_none());
+    assert!(sandbox.installed_version("tool").is_none());
+}
+
+#[test]
+fn slow_hook_is_stopped_after_its_timeout() {
+    let (server, sandbox) = setup("hook-timeout", &["tool"], "pre_install = 'sleep 30'\nhook_timeout_secs = 1");
+    server.publish("tool", V1, &[("readme.txt", "v1")], 1);
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert!(result.output.contains("pre_install hook timed out after 1 s"), "{}", result.output);
+    assert!(sandbox.read_output("tool", "readme.txt").is_none());
+}
+
+#[test]
+fn failing_post_install_hook_rolls_back() {
+    let (server, sandbox) = setup("hook-rollback", &["tool"], "");
+    server.publish("tool", V1, &[("readme.txt", "v1")], 1);
+    sandbox.run(&["install", "tool"], &[]);
+    sandbox.write_config(&server, "", &package_config(&server, "tool", "post_install = 'exit 1'\nrollback_on_hook_failure = true"));
+    server.publish("tool", V2, &[("readme.txt", "v2")], 1);
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert!(result.output.contains("post_install hook failed"), "{}", result.output);
+    assert!(result.output.contains("Rolled back"), "{}", result.output);
+    assert_eq!(sandbox.read_output("tool", "readme.txt").as_deref(), Some("v1"));
+    assert_eq!(sandbox.installed_version("tool").as_deref(), Some(V1));
+}
+
+#[test]
+fn failed_extraction_rolls_back() {
+    let (server, sandbox) = setup("hook-rollback-extraction", &["tool"], "rollback_on_hook_failure = true");
+    server.publish("tool", V1, &[("readme.txt", "v1")], 1);
+    sandbox.run(&["install", "tool"], &[]);
+    let volumes = server.publish("tool", V2, &[("readme.txt", "v2")], 1);
+    let broken = b"\x1f\x8b\x08\x00 not really gzip".to_vec();
+    server.set(&format!("/tool/{}", volumes[0]), Route::Body(broken.clone()));
+    server.set_text("/tool/filelist.txt", &format!("{} {}\n", volumes[0], common::sha256(&broken)));
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_eq!(result.status, Some(6), "{}", result.output);
+    assert!(result.output.contains("Rolled back"), "{}", result.output);
+    assert_eq!(sandbox.read_output("tool", "readme.txt").as_deref(), Some("v1"));
+    assert_eq!(sandbox.installed_version("tool").as_deref(), Some(V1));
+}
+
+//=-- A process is gone once it no longer exists or only waits to be reaped
+#[cfg(target_os = "linux")]
+fn is_running(pid: &str) -> bool {
+    fs::read_to_string(format!("/proc/{}/stat", pid))
+        .is_ok_and(|stat| stat.rsplit(')').next().is_some_and(|rest| !rest.trim_start().starts_with('Z')))
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn timed_out_hook_is_stopped_with_everything_it_started() {
+    let (server, sandbox) = setup("hook-timeout-tree", &["tool"], "pre_install = 'sleep 30 & echo $! > sleep.pid; wait'\nhook_timeout_secs = 1");
+    server.publish("tool", V1, &[("readme.txt", "v1")], 1);
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert!(result.output.contains("pre_install hook timed out after 1 s"), "{}", result.output);
+    let pid = fs::read_to_string(sandbox.dir.join("sleep.pid")).unwrap();
+    assert!(!is_running(pid.trim()), "the hook's sleep {} is still running", pid.trim());
+}
+
+#[test]
+fn uninstall_runs_its_hooks_and_removes_the_package() {
+    let hooks = "pre_uninstall = 'test -f \"$WBTL_OUTPUT_DIR/readme.txt\" && echo $WBTL_VERSION > pre.txt'\npost_uninstall = 'test ! -e \"$WBTL_OUTPUT_DIR\" && touch post.txt'";
+    let (server, sandbox) = setup("hook-uninstall", &["tool"], hooks);
+    server.publish("tool", V1, &[("readme.txt", "v1")], 1);
+    sandbox.run(&["install", "tool"], &[]);
+
+    let declined = sandbox.run(&["uninstall", "tool"], &["N"]);
+    assert!(declined.output.contains("Uninstall cancelled"), "{}", declined.output);
+    assert!(sandbox.output_dir("tool").exists());
+
+    let result = sandbox.run(&["uninstall", "tool"], &["Y"]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert!(!sandbox.output_dir("tool").exists());
+    assert_eq!(fs::read_to_string(sandbox.dir.join("pre.txt")).unwrap().trim(), V1);
+    assert!(sandbox.dir.join("post.txt").exists(), "{}", result.output);
+}
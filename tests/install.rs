@@ -0,0 +1,185 @@
+//=-- End-to-end install/update/downgrade scenarios against a stand-in repository (see common/mod.rs)
+mod common;
+
+use common::{package_config, setup, RepoServer, Route, Sandbox, V1, V2};
+
+#[test]
+fn fresh_install_extracts_volumes_and_records_version() {
+    let (server, sandbox) = setup("fresh", &["tool"], "");
+    server.publish("tool", V1, &[("readme.txt", "first release"), ("bin/tool.cfg", "mode=1")], 3);
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert!(result.output.contains("Successfully extracted archives"), "{}", result.output);
+    assert_eq!(sandbox.read_output("tool", "readme.txt").as_deref(), Some("first release"));
+    assert_eq!(sandbox.read_output("tool", "bin/tool.cfg").as_deref(), Some("mode=1"));
+    assert_eq!(sandbox.installed_version("tool").as_deref(), Some(V1));
+    assert_eq!(sandbox.read_output("tool", "channel.txt").as_deref(), Some("stable"));
+}
+
+#[test]
+fn newer_version_is_installed_over_the_old_one() {
+    let (server, sandbox) = setup("update", &["tool"], "");
+    server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+    sandbox.run(&["install", "tool"], &[]);
+
+    server.publish("tool", V2, &[("readme.txt", "second release")], 2);
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert!(result.output.contains(&format!("Updating to version: {}", V2)), "{}", result.output);
+    assert_eq!(sandbox.read_output("tool", "readme.txt").as_deref(), Some("second release"));
+    assert_eq!(sandbox.installed_version("tool").as_deref(), Some(V2));
+}
+
+#[test]
+fn same_version_is_only_reloaded_when_confirmed() {
+    let (server, sandbox) = setup("same", &["tool"], "");
+    server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+    sandbox.run(&["install", "tool"], &[]);
+    std::fs::write(sandbox.output_dir("tool").join("readme.txt"), "edited locally").unwrap();
+
+    let declined = sandbox.run(&["install", "tool"], &["N"]);
+    assert!(declined.output.contains("Package version is the same"), "{}", declined.output);
+    assert_eq!(sandbox.read_output("tool", "readme.txt").as_deref(), Some("edited locally"));
+
+    let confirmed = sandbox.run(&["install", "tool"], &["Y"]);
+    assert!(confirmed.output.contains("Successfully extracted archives"), "{}", confirmed.output);
+    assert_eq!(sandbox.read_output("tool", "readme.txt").as_deref(), Some("first release"));
+}
+
+#[test]
+fn update_shows_release_notes_before_asking() {
+    let server = RepoServer::start();
+    let sandbox = Sandbox::new("notes");
+    let changelog_url = format!("changelog_url = \"{}\"", server.url("/tool/CHANGELOG.md"));
+    sandbox.write_config(&server, "", &package_config(&server, "tool", &changelog_url));
+    server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+    sandbox.run(&["install", "tool"], &[]);
+    server.set_text("/tool/CHANGELOG.md", &format!("## {}\nFaster startup\n\n## {}\nFirst release\n", V2, V1));
+    server.publish("tool", V2, &[("readme.txt", "second release")], 1);
+
+    let result = sandbox.run(&["install", "tool"], &["N"]);
+
+    assert!(result.output.contains(&format!("Release notes for Package tool:\n\n== {} ==\nFaster startup", V2)), "{}", result.output);
+    assert!(!result.output.contains("First release"), "{}", result.output);
+    assert_eq!(sandbox.installed_version("tool").as_deref(), Some(V1));
+}
+
+#[test]
+fn downgrade_needs_confirmation() {
+    let (server, sandbox) = setup("downgrade", &["tool"], "");
+    server.publish("tool", V2, &[("readme.txt", "second release")], 1);
+    sandbox.run(&["install", "tool"], &[]);
+
+    server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+    let declined = sandbox.run(&["install", "tool"], &[]);
+    assert!(declined.output.contains("is newer than repository version"), "{}", declined.output);
+    assert_eq!(sandbox.installed_version("tool").as_deref(), Some(V2));
+
+    let confirmed = sandbox.run(&["install", "tool"], &["Y"]);
+    assert!(confirmed.output.contains(&format!("Downgrading to version: {}", V1)), "{}", confirmed.output);
+    assert_eq!(sandbox.installed_version("tool").as_deref(), Some(V1));
+    assert_eq!(sandbox.read_output("tool", "readme.txt").as_deref(), Some("first release"));
+}
+
+#[test]
+fn missing_version_file_makes_package_unavailable() {
+    let (server, sandbox) = setup("missing-version", &["tool"], "");
+    server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+    server.remove("/tool/version.txt");
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert!(result.output.contains("is not available"), "{}", result.output);
+    assert!(result.output.contains("404 Not Found"), "{}", result.output);
+    assert!(!sandbox.output_dir("tool").exists());
+    assert_eq!(server.hits("/tool/filelist.txt"), 0);
+}
+
+#[test]
+fn version_is_fetched_once_per_install() {
+    let (server, sandbox) = setup("single-fetch", &["tool"], "");
+    server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+
+    sandbox.run(&["install", "tool"], &[]);
+
+    assert_eq!(server.hits("/tool/version.txt"), 1);
+    assert_eq!(server.hits("/tool/filelist.txt"), 1);
+}
+
+#[test]
+fn server_error_on_a_volume_is_reported() {
+    let (server, sandbox) = setup("volume-500", &["tool"], "");
+    let volumes = server.publish("tool", V1, &[("readme.txt", "first release")], 2);
+    server.set(&format!("/tool/{}", volumes[1]), Route::Status(500));
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert!(result.output.contains(&format!("Error downloading {}", volumes[1])), "{}", result.output);
+    assert!(result.output.contains("500 Internal Server Error"), "{}", result.output);
+    assert!(sandbox.read_output("tool", "readme.txt").is_none());
+}
+
+#[test]
+fn truncated_volume_is_not_extracted() {
+    let (server, sandbox) = setup("truncated", &["tool"], "");
+    let archive = common::tar_gz(&[("readme.txt", "first release")]);
+    let volumes = server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+    server.set(&format!("/tool/{}", volumes[0]), Route::Truncated { sent: archive.len() / 2, body: archive });
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert!(result.output.contains(&format!("Error downloading {}", volumes[0])), "{}", result.output);
+    assert!(sandbox.read_output("tool", "readme.txt").is_none());
+}
+
+#[test]
+fn checksum_mismatch_is_rejected() {
+    let (server, sandbox) = setup("checksum", &["tool"], "");
+    let volumes = server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+    server.set(&format!("/tool/{}", volumes[0]), Route::Body(common::tar_gz(&[("readme.txt", "tampered")])));
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert!(result.output.contains("Checksum mismatch"), "{}", result.output);
+    assert!(sandbox.read_output("tool", "readme.txt").is_none());
+}
+
+#[test]
+fn failed_volume_falls_back_to_a_mirror() {
+    let server = RepoServer::start();
+    let sandbox = Sandbox::new("mirror");
+    let extra = format!("mirrors = [\"{}\"]", server.url("/mirror/"));
+    sandbox.write_config(&server, "", &package_config(&server, "tool", &extra));
+    let volumes = server.publish("tool", V1, &[("readme.txt", "first release")], 2);
+    //=-- The mirror serves the same volumes under the same names
+    for (index, volume) in volumes.iter().enumerate() {
+        let part = common::tar_gz(&[("readme.txt", "first release")]);
+        let chunk = part.len().div_ceil(2);
+        server.set(&format!("/mirror/{}", volume), Route::Body(part.chunks(chunk).nth(index).unwrap().to_vec()));
+    }
+    server.set(&format!("/tool/{}", volumes[0]), Route::Status(500));
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert!(result.output.contains(&format!("Downloaded from {} as:", server.url("/mirror/"))), "{}", result.output);
+    assert_eq!(sandbox.read_output("tool", "readme.txt").as_deref(), Some("first release"));
+}
+
+#[test]
+fn filelist_entry_outside_the_download_folder_is_rejected() {
+    let (server, sandbox) = setup("path-escape", &["tool"], "volume_naming = \"single\"");
+    let archive = common::tar_gz(&[("readme.txt", "first release")]);
+    server.set_text("/tool/version.txt", V1);
+    server.set_text("/tool/filelist.txt", &format!("../../escape.tar.gz {}\n", common::sha256(&archive)));
+    server.set("/escape.tar.gz", Route::Body(archive));
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_eq!(result.status, Some(5), "{}", result.output);
+    assert!(result.output.contains("\"../../escape.tar.gz\" is not a plain file name"), "{}", result.output);
+    assert_eq!(server.hits("/escape.tar.gz"), 0);
+    assert!(!sandbox.dir.join("escape.tar.gz").exists());
+    assert!(sandbox.installed_version("tool").is_none());
+}
@@ -0,0 +1,88 @@
+//=-- --json output: stdout holds exactly one JSON document and prompts are never shown
+mod common;
+
+use common::{package_config, setup, RepoServer, Sandbox, V1, V2};
+use serde_json::Value;
+
+fn parse(output: &str) -> Value {
+    serde_json::from_str(output).unwrap_or_else(|e| panic!("stdout is not one JSON document ({}):\n{}", e, output))
+}
+
+#[test]
+fn install_reports_action_and_versions() {
+    let (server, sandbox) = setup("json-install", &["tool"], "");
+    server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+    sandbox.run(&["install", "tool"], &[]);
+    server.publish("tool", V2, &[("readme.txt", "second release")], 1);
+
+    let result = sandbox.run(&["--json", "install", "tool"], &[]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    let document = parse(&result.output);
+    assert_eq!(document["command"], "install");
+    assert_eq!(document["error"], Value::Null);
+    let report = &document["packages"][0];
+    assert_eq!(report["id"], "tool");
+    assert_eq!(report["installed_version"], V1);
+    assert_eq!(report["remote_version"], V2);
+    assert_eq!(report["action"], "updated");
+    assert_eq!(report["errors"], Value::Array(Vec::new()));
+    assert_eq!(sandbox.installed_version("tool").as_deref(), Some(V2));
+}
+
+#[test]
+fn same_version_is_skipped_without_prompting() {
+    let (server, sandbox) = setup("json-same", &["tool"], "");
+    server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+    sandbox.run(&["install", "tool"], &[]);
+
+    let result = sandbox.run(&["--json", "install", "tool"], &[]);
+
+    assert!(!result.output.contains("Reload anyway"), "{}", result.output);
+    assert_eq!(parse(&result.output)["packages"][0]["action"], "skipped");
+}
+
+#[test]
+fn failed_install_lists_its_errors() {
+    let (server, sandbox) = setup("json-failed", &["tool"], "");
+    server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+    server.remove("/tool/version.txt");
+
+    let result = sandbox.run(&["--json", "install", "tool"], &[]);
+
+    let report = &parse(&result.output)["packages"][0];
+    assert_eq!(report["action"], "failed");
+    assert_eq!(report["error_kind"], "network");
+    assert!(report["errors"][0].as_str().unwrap().contains("404 Not Found"), "{}", result.output);
+}
+
+#[test]
+fn list_shows_installed_packages() {
+    let server = RepoServer::start();
+    let sandbox = Sandbox::new("json-list");
+    let packages = format!("{}\n{}", package_config(&server, "tool", ""), package_config(&server, "other", ""));
+    sandbox.write_config(&server, "", &packages);
+    server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+    sandbox.run(&["install", "tool"], &[]);
+
+    let result = sandbox.run(&["list", "--json"], &[]);
+
+    let document = parse(&result.output);
+    assert_eq!(document["command"], "list");
+    let packages = document["packages"].as_array().unwrap();
+    let find = |id: &str| packages.iter().find(|package| package["id"] == id).unwrap();
+    assert_eq!(find("tool")["installed_version"], V1);
+    assert_eq!(find("tool")["installed_channel"], "stable");
+    assert_eq!(find("other")["installed_version"], Value::Null);
+}
+
+#[test]
+fn run_errors_are_reported_as_json() {
+    let (_server, sandbox) = setup("json-error", &["tool"], "");
+
+    let result = sandbox.run(&["--json", "install", "missing"], &[]);
+
+    assert_eq!(result.status, Some(3));
+    let document = parse(&result.output);
+    assert!(document["error"].as_str().unwrap().contains("Package not found"), "{}", result.output);
+}
@@ -0,0 +1,111 @@
+//=-- The library API drives installs without the binary and without ever reading stdin
+mod common;
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use common::{setup, Sandbox, V1};
+use wb_toolsloader::events::{DecisionProvider, Event, Observer, OutputDirAction, Question};
+use wb_toolsloader::{
+    find_package, list_packages, load_settings, process_package, uninstall_package, Action, LoaderContext, Package,
+    VersionRequest,
+};
+
+fn context(sandbox: &Sandbox) -> (wb_toolsloader::Settings, LoaderContext) {
+    let settings = load_settings(&sandbox.dir.join("Config.toml")).unwrap();
+    let ctx = LoaderContext::new(&settings, &sandbox.dir, sandbox.dir.join("out"), sandbox.dir.join("tmp").join("dl")).unwrap();
+    (settings, ctx)
+}
+
+#[test]
+fn install_and_list_through_the_library() {
+    let (server, sandbox) = setup("library-install", &["tool"], "");
+    server.publish("tool", V1, &[("readme.txt", "first release")], 2);
+    let (settings, ctx) = context(&sandbox);
+    let package = find_package(&settings, "tool").unwrap();
+
+    let report = process_package(&ctx, package, &VersionRequest::Latest);
+
+    assert_eq!(report.action, Action::Installed, "{:?}", report.errors);
+    assert_eq!(sandbox.read_output("tool", "readme.txt").as_deref(), Some("first release"));
+    let packages = list_packages(&settings, &ctx.output_root);
+    assert_eq!(packages[0].installed_version.as_deref(), Some(V1));
+
+    //=-- Without a prompter "Reload anyway?" takes its default answer (no) instead of waiting for stdin
+    let again = process_package(&ctx, package, &VersionRequest::Latest);
+    assert_eq!(again.action, Action::Skipped);
+}
+
+#[test]
+fn uninstall_through_the_library() {
+    let (server, sandbox) = setup("library-uninstall", &["tool"], "");
+    server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+    let (settings, ctx) = context(&sandbox);
+    let package = find_package(&settings, "tool").unwrap();
+    process_package(&ctx, package, &VersionRequest::Latest);
+
+    let removed = uninstall_package(&ctx, package).unwrap();
+
+    assert_eq!(removed.to_string(), V1);
+    assert!(!sandbox.output_dir("tool").exists());
+    assert!(uninstall_package(&ctx, package).is_err());
+}
+
+//=-- Writes down every event as a short line
+struct Recorder(Arc<Mutex<Vec<String>>>);
+
+impl Observer for Recorder {
+    fn on_event(&self, event: &Event) {
+        let line = match event {
+            Event::PackageStarted { package } => format!("started {}", package.id),
+            Event::VolumeDownloaded { done, total, .. } => format!("downloaded {}/{}", done, total),
+            Event::ExtractionProgress { done, total, .. } => format!("extracted {}/{}", done, total),
+            Event::PromptNeeded { question: Question::ReloadSameVersion { .. }, .. } => "asked reload".to_string(),
+            Event::PromptNeeded { question: Question::ExistingOutputDir { .. }, .. } => "asked output dir".to_string(),
+            Event::PromptNeeded { .. } => "asked something else".to_string(),
+            Event::PackageFinished { report, .. } => format!("finished {}", report.action),
+        };
+        self.0.lock().unwrap().push(line);
+    }
+}
+
+//=-- Says yes to everything and deletes existing output folders
+struct AlwaysYes;
+
+impl DecisionProvider for AlwaysYes {
+    fn confirm(&self, _package: &Package, _question: &Question) -> bool {
+        true
+    }
+
+    fn existing_output_dir(&self, _package: &Package, _output_dir: &Path) -> OutputDirAction {
+        OutputDirAction::Delete
+    }
+
+    fn password(&self, _package: &Package, _retry: bool) -> Option<String> {
+        None
+    }
+}
+
+#[test]
+fn observer_and_decision_provider_drive_a_reinstall() {
+    let (server, sandbox) = setup("library-events", &["tool"], "");
+    server.publish("tool", V1, &[("readme.txt", "first release")], 2);
+    let (settings, mut ctx) = context(&sandbox);
+    let package = find_package(&settings, "tool").unwrap();
+    process_package(&ctx, package, &VersionRequest::Latest);
+    std::fs::write(sandbox.output_dir("tool").join("stale.txt"), "left over").unwrap();
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    ctx.observer = Box::new(Recorder(events.clone()));
+    ctx.decisions = Box::new(AlwaysYes);
+    let report = process_package(&ctx, package, &VersionRequest::Latest);
+
+    assert_eq!(report.action, Action::Reinstalled, "{:?}", report.errors);
+    assert_eq!(*events.lock().unwrap(), [
+        "started tool", "asked reload", "downloaded 1/2", "downloaded 2/2", "asked output dir", "extracted 1/1",
+        "finished reinstalled",
+    ]);
+    //=-- "Delete" emptied the output folder before extracting
+    assert!(!sandbox.output_dir("tool").join("stale.txt").exists());
+    assert_eq!(sandbox.read_output("tool", "readme.txt").as_deref(), Some("first release"));
+}
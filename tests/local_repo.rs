@@ -0,0 +1,70 @@
+//=-- Repositories on disk: file:// URLs and plain (absolute or relative) paths, read without the download cache
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use common::{file_url, local_package_config, toml_path, RepoServer, Sandbox, V1};
+
+//=-- Writes version.txt, filelist.txt and two volumes into `dir`, the layout a web repository serves
+fn write_repo(dir: &Path) {
+    fs::create_dir_all(dir).unwrap();
+    let archive = common::tar_gz(&[("readme.txt", "local release")]);
+    let mut filelist = String::new();
+    for (index, part) in archive.chunks(archive.len().div_ceil(2)).enumerate() {
+        let name = format!("tool--n{}.globby", index + 1);
+        filelist.push_str(&format!("{} {}\n", name, common::sha256(part)));
+        fs::write(dir.join(name), part).unwrap();
+    }
+    fs::write(dir.join("version.txt"), V1).unwrap();
+    fs::write(dir.join("filelist.txt"), filelist).unwrap();
+}
+
+fn local(name: &str, base: impl Fn(&Sandbox) -> String) -> Sandbox {
+    let server = RepoServer::start();
+    let sandbox = Sandbox::new(name);
+    write_repo(&sandbox.dir.join("repo"));
+    //=-- The cache is on, so the tests also show local repositories bypass it
+    sandbox.write_config(&server, "cache_max_mb = 64", &local_package_config("tool", &base(&sandbox), ""));
+    sandbox
+}
+
+fn assert_installed_without_cache(sandbox: &Sandbox) {
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert_eq!(sandbox.installed_version("tool").as_deref(), Some(V1));
+    assert_eq!(sandbox.read_output("tool", "readme.txt").as_deref(), Some("local release"));
+    assert!(!sandbox.dir.join("cache").join("objects").exists());
+}
+
+#[test]
+fn file_url_repository_is_installed_from() {
+    let sandbox = local("local-file-url", |sandbox| format!("{}/", file_url(&sandbox.dir.join("repo"))));
+    assert_installed_without_cache(&sandbox);
+}
+
+#[test]
+fn absolute_path_repository_is_installed_from() {
+    let sandbox = local("local-absolute", |sandbox| format!("{}/", toml_path(&sandbox.dir.join("repo"))));
+    assert_installed_without_cache(&sandbox);
+}
+
+#[test]
+fn relative_path_repository_starts_at_the_config_folder() {
+    let sandbox = local("local-relative", |_| "repo/".to_string());
+    assert_installed_without_cache(&sandbox);
+}
+
+#[test]
+fn missing_local_volume_is_reported() {
+    let sandbox = local("local-missing", |sandbox| format!("{}/", file_url(&sandbox.dir.join("repo"))));
+    fs::remove_file(sandbox.dir.join("repo").join("tool--n2.globby")).unwrap();
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_eq!(result.status, Some(4), "{}", result.output);
+    assert!(result.output.contains("Error downloading tool--n2.globby"), "{}", result.output);
+    assert!(result.output.contains("not found"), "{}", result.output);
+    assert!(sandbox.installed_version("tool").is_none());
+}
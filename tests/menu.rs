@@ -0,0 +1,29 @@
+//=-- Without a terminal (or with --no-tui) the interactive menu is the numbered line menu
+mod common;
+
+use common::{setup, V1};
+
+#[test]
+fn redirected_input_uses_the_line_menu() {
+    let (server, sandbox) = setup("menu-lines", &["tool"], "");
+    server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+
+    let result = sandbox.run(&[], &["1", ""]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert!(result.output.contains("1. Package tool: test package"), "{}", result.output);
+    assert!(result.output.contains("Tools loading jobs completed."), "{}", result.output);
+    assert_eq!(sandbox.installed_version("tool").as_deref(), Some(V1));
+}
+
+#[test]
+fn no_tui_option_is_accepted() {
+    let (server, sandbox) = setup("menu-no-tui", &["tool"], "");
+    server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+
+    let result = sandbox.run(&["--no-tui"], &["E"]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert!(result.output.contains("E. Exit"), "{}", result.output);
+    assert_eq!(sandbox.installed_version("tool"), None);
+}
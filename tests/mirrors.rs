@@ -0,0 +1,78 @@
+//=-- Where volumes come from besides repo_url: package mirrors, global [mirrors.*] prefix sets and mirror_strategy
+mod common;
+
+use std::net::TcpListener;
+
+use common::{package_config, RepoServer, Route, Sandbox, V1};
+
+const FILES: &[(&str, &str)] = &[("readme.txt", "first release")];
+const VOLUME: &str = "tool--n1.globby";
+
+//=-- Publishes the package with its only volume failing at repo_url and served under `mirror_path` instead
+fn publish_on_mirror(server: &RepoServer, mirror_path: &str) {
+    server.publish("tool", V1, FILES, 1);
+    server.set(&format!("{}{}", mirror_path, VOLUME), Route::Body(common::tar_gz(FILES)));
+    server.set(&format!("/tool/{}", VOLUME), Route::Status(500));
+}
+
+//=-- A URL on a port nothing listens on
+fn unreachable_url() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    format!("http://{}/dead/", listener.local_addr().unwrap())
+}
+
+#[test]
+fn latency_strategy_tries_unreachable_mirrors_last() {
+    let server = RepoServer::start();
+    let sandbox = Sandbox::new("mirror-latency");
+    let dead = unreachable_url();
+    let extra = format!("mirrors = [\"{}\", \"{}\"]", dead, server.url("/mirror/"));
+    sandbox.write_config(&server, "mirror_strategy = \"latency\"", &package_config(&server, "tool", &extra));
+    publish_on_mirror(&server, "/mirror/");
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert!(result.output.contains(&format!("Mirror {} is unreachable", dead)), "{}", result.output);
+    assert!(result.output.contains(&format!("Mirror {} is unreachable", server.url("/tool/"))), "{}", result.output);
+    //=-- The reachable mirror went first, so nothing failed before it
+    assert!(!result.output.contains("Failed to get"), "{}", result.output);
+    assert!(result.output.contains(&format!("Downloaded from {} as:", server.url("/mirror/"))), "{}", result.output);
+    assert_eq!(server.hits(&format!("/tool/{}", VOLUME)), 1);
+    assert_eq!(sandbox.read_output("tool", "readme.txt").as_deref(), Some("first release"));
+}
+
+#[test]
+fn order_strategy_tries_repo_url_first() {
+    let server = RepoServer::start();
+    let sandbox = Sandbox::new("mirror-order");
+    let extra = format!("mirrors = [\"{}\"]", server.url("/mirror/"));
+    sandbox.write_config(&server, "mirror_strategy = \"order\"", &package_config(&server, "tool", &extra));
+    publish_on_mirror(&server, "/mirror/");
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert!(result.output.contains(&format!("Failed to get {} from {}", VOLUME, server.url("/tool/"))), "{}", result.output);
+    assert!(result.output.contains(&format!("Downloaded from {} as:", server.url("/mirror/"))), "{}", result.output);
+}
+
+#[test]
+fn global_mirror_set_rewrites_the_repo_url_prefix() {
+    let server = RepoServer::start();
+    let sandbox = Sandbox::new("mirror-prefix");
+    let mirror_sets = format!(
+        "[mirrors.backup]\nprefix = \"{}\"\nurls = [\"{}\"]\n\n[mirrors.unrelated]\nprefix = \"{}\"\nurls = [\"{}\"]\n",
+        server.url("/"), server.url("/backup"), server.url("/elsewhere/"), server.url("/wrong/"),
+    );
+    sandbox.write_config(&server, "", &format!("{}\n{}", package_config(&server, "tool", ""), mirror_sets));
+    //=-- repo_url is <server>/tool/, so the backup set serves it from <server>/backup/tool/
+    publish_on_mirror(&server, "/backup/tool/");
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert!(result.output.contains(&format!("Downloaded from {} as:", server.url("/backup/tool/"))), "{}", result.output);
+    assert_eq!(server.hits(&format!("/wrong/tool/{}", VOLUME)), 0);
+    assert_eq!(sandbox.read_output("tool", "readme.txt").as_deref(), Some("first release"));
+}
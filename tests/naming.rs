@@ -0,0 +1,72 @@
+//=-- volume_naming: how filelist entries are matched and renamed for extraction
+mod common;
+
+use common::{setup, RunResult, Sandbox, V1};
+
+const FILES: &[(&str, &str)] = &[("readme.txt", "first release"), ("bin/tool.cfg", "mode=1")];
+
+fn assert_installed(sandbox: &Sandbox, result: &RunResult) {
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert_eq!(sandbox.installed_version("tool").as_deref(), Some(V1));
+    assert_eq!(sandbox.read_output("tool", "readme.txt").as_deref(), Some("first release"));
+    assert_eq!(sandbox.read_output("tool", "bin/tool.cfg").as_deref(), Some("mode=1"));
+}
+
+#[test]
+fn seven_zip_volumes_keep_their_names() {
+    let (server, sandbox) = setup("naming-7z", &["tool"], "volume_naming = \"7z\"");
+    server.publish_as("tool", V1, FILES, &["tool.7z.001", "tool.7z.002"]);
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_installed(&sandbox, &result);
+    assert!(result.output.contains("as: tool.7z.002"), "{}", result.output);
+}
+
+#[test]
+fn zip_volumes_keep_their_names() {
+    let (server, sandbox) = setup("naming-zip", &["tool"], "volume_naming = \"zip\"");
+    server.publish_as("tool", V1, FILES, &["tool.zip.001", "tool.zip.002", "tool.zip.003"]);
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_installed(&sandbox, &result);
+    assert!(result.output.contains("as: tool.zip.003"), "{}", result.output);
+}
+
+#[test]
+fn single_archive_is_installed_as_is() {
+    let (server, sandbox) = setup("naming-single", &["tool"], "volume_naming = \"single\"");
+    server.publish_as("tool", V1, FILES, &["tool.tar.gz"]);
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_installed(&sandbox, &result);
+    assert!(result.output.contains("as: tool.tar.gz"), "{}", result.output);
+}
+
+#[test]
+fn custom_pattern_renames_volumes_with_its_suffix() {
+    let extra = "volume_naming = \"custom\"\nvolume_pattern = '-part(\\d+)\\.bin$'\nvolume_suffix = \".tar.gz.{nnn}\"";
+    let (server, sandbox) = setup("naming-custom", &["tool"], extra);
+    server.publish_as("tool", V1, FILES, &["tool-part1.bin", "tool-part2.bin"]);
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_installed(&sandbox, &result);
+    assert!(result.output.contains("as: tool.tar.gz.002"), "{}", result.output);
+}
+
+#[test]
+fn entry_that_does_not_match_the_rule_is_rejected_before_downloading() {
+    let (server, sandbox) = setup("naming-mismatch", &["tool"], "volume_naming = \"7z\"");
+    server.publish_as("tool", V1, FILES, &["tool.7z.001", "tool--n2.globby"]);
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_eq!(result.status, Some(3), "{}", result.output);
+    assert!(result.output.contains("\"tool--n2.globby\" does not match the volume naming <name>.7z.NNN"), "{}", result.output);
+    assert_eq!(server.hits("/tool/tool.7z.001"), 0);
+    assert_eq!(server.hits("/tool/tool--n2.globby"), 0);
+    assert!(sandbox.installed_version("tool").is_none());
+}
@@ -0,0 +1,76 @@
+//=-- 7z archives go through NanaZip, which gets the archive password on stdin, never on its command line
+#![cfg(unix)]
+mod common;
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+
+use common::{setup, RepoServer, Route, Sandbox, V1};
+
+const PASSWORD: &str = "hunter2-secret";
+
+//=-- Stands in for NanaZip: records its arguments and stdin next to itself, then "extracts" one file
+fn fake_nanazip(sandbox: &Sandbox) {
+    let script = "#!/bin/sh\n\
+        printf '%s\\n' \"$@\" > \"$0.argv\"\n\
+        cat > \"$0.stdin\"\n\
+        for arg; do case \"$arg\" in -o*) out=\"${arg#-o}\";; esac; done\n\
+        mkdir -p \"$out\" && echo extracted > \"$out/readme.txt\"\n";
+    let path = sandbox.dir.join("nanazip-not-needed");
+    fs::write(&path, script).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+fn publish_7z(server: &RepoServer) {
+    let volume = [[0x37, 0x7A, 0xBC, 0xAF, 0x27, 0x1C].as_slice(), b"7z volume data"].concat();
+    server.set_text("/tool/filelist.txt", &format!("tool--n1.globby {}\n", common::sha256(&volume)));
+    server.set("/tool/tool--n1.globby", Route::Body(volume));
+    server.set_text("/tool/version.txt", V1);
+}
+
+#[test]
+fn password_reaches_nanazip_on_stdin_only() {
+    let (server, sandbox) = setup("nanazip-password", &["tool"], "");
+    fake_nanazip(&sandbox);
+    publish_7z(&server);
+
+    let result = sandbox.run(&["--trace", "install", "tool"], &[PASSWORD]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert_eq!(sandbox.read_output("tool", "readme.txt").as_deref(), Some("extracted\n"));
+    let argv = fs::read_to_string(sandbox.dir.join("nanazip-not-needed.argv")).unwrap();
+    assert!(argv.starts_with("x\n"), "{}", argv);
+    assert!(!argv.contains(PASSWORD), "{}", argv);
+    let stdin = fs::read_to_string(sandbox.dir.join("nanazip-not-needed.stdin")).unwrap();
+    assert_eq!(stdin.trim(), PASSWORD);
+    let log = fs::read_to_string(sandbox.dir.join("logs").join("wb-toolsloader.log")).unwrap();
+    assert!(log.contains("Running"), "{}", log);
+    assert!(!log.contains(PASSWORD), "{}", log);
+    assert!(!result.output.contains(PASSWORD), "{}", result.output);
+}
+
+#[test]
+fn rar_volumes_are_detected_and_handed_to_nanazip_from_the_first_volume() {
+    let (server, sandbox) = setup("rar", &["tool"], "volume_naming = \"rar\"");
+    fake_nanazip(&sandbox);
+    let mut filelist = String::new();
+    for index in 1..=2 {
+        let name = format!("tool.part{}.rar", index);
+        let volume = [b"Rar!\x1a\x07\x01\x00".as_slice(), b"volume data"].concat();
+        filelist.push_str(&format!("{} {}\n", name, common::sha256(&volume)));
+        server.set(&format!("/tool/{}", name), Route::Body(volume));
+    }
+    server.set_text("/tool/version.txt", V1);
+    server.set_text("/tool/filelist.txt", &filelist);
+
+    let result = sandbox.run(&["install", "tool"], &[PASSWORD]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    //=-- RAR goes down the same NanaZip path as 7z
+    assert!(result.output.contains("tool.part1.rar (7z)"), "{}", result.output);
+    assert_eq!(sandbox.read_output("tool", "readme.txt").as_deref(), Some("extracted\n"));
+    //=-- NanaZip finds the other volumes itself, so only the first one is passed
+    let argv = fs::read_to_string(sandbox.dir.join("nanazip-not-needed.argv")).unwrap();
+    assert!(argv.lines().any(|arg| arg.ends_with("tool.part1.rar")), "{}", argv);
+    assert!(!argv.contains("tool.part2.rar"), "{}", argv);
+}
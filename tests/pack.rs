@@ -0,0 +1,109 @@
+//=-- `pack` builds a package's repository files from a folder
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+use common::{file_url, local_package_config, setup, RepoServer, Sandbox, V1, V2};
+
+#[cfg(unix)]
+#[test]
+fn new_signing_key_is_only_readable_by_its_owner() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let (_server, sandbox) = setup("pack-key", &["tool"], "archive_format = \"tar.gz\"");
+    let source = sandbox.dir.join("source");
+    fs::create_dir_all(&source).unwrap();
+    fs::write(source.join("readme.txt"), "packed").unwrap();
+    let out_dir = sandbox.dir.join("repo");
+    let key = sandbox.dir.join("keys").join("signing.key");
+
+    let result = sandbox.run(&[
+        "pack", "tool", source.to_str().unwrap(), out_dir.to_str().unwrap(),
+        "--version", V1, "--sign-key", key.to_str().unwrap(),
+    ], &[]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert!(out_dir.join("filelist.txt.sig").exists(), "{}", result.output);
+    assert_eq!(fs::metadata(&key).unwrap().permissions().mode() & 0o777, 0o600);
+}
+
+//=-- Packs a signed tar.gz release into a folder and points the package at it as a file:// repository
+fn signed_release(name: &str) -> (RepoServer, Sandbox, PathBuf) {
+    let server = RepoServer::start();
+    let sandbox = Sandbox::new(name);
+    let repo = sandbox.dir.join("repo");
+    let package = |extra: &str| local_package_config("tool", &format!("{}/", file_url(&repo)), &format!("archive_format = \"tar.gz\"\n{}", extra));
+    sandbox.write_config(&server, "", &package(""));
+
+    let source = sandbox.dir.join("source");
+    fs::create_dir_all(source.join("bin")).unwrap();
+    fs::write(source.join("readme.txt"), "packed").unwrap();
+    fs::write(source.join("bin").join("tool.cfg"), "mode=1").unwrap();
+    let key = sandbox.dir.join("signing.key");
+    let packed = sandbox.run(&[
+        "pack", "tool", source.to_str().unwrap(), repo.to_str().unwrap(),
+        "--version", V1, "--sign-key", key.to_str().unwrap(),
+    ], &[]);
+    assert_eq!(packed.status, Some(0), "{}", packed.output);
+
+    let public_key = packed.output.lines()
+        .find_map(|line| line.split("(public_key in the package config): ").nth(1))
+        .unwrap_or_else(|| panic!("no public key printed:\n{}", packed.output))
+        .trim()
+        .to_string();
+    sandbox.write_config(&server, "", &package(&format!("public_key = \"{}\"", public_key)));
+    (server, sandbox, repo)
+}
+
+#[test]
+fn packed_release_installs_and_passes_verification() {
+    let (_server, sandbox, repo) = signed_release("pack-roundtrip");
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert!(fs::read_to_string(repo.join("filelist.txt")).unwrap().starts_with(&format!("# version {}\n", V1)));
+    assert_eq!(sandbox.installed_version("tool").as_deref(), Some(V1));
+    assert_eq!(sandbox.read_output("tool", "readme.txt").as_deref(), Some("packed"));
+    assert_eq!(sandbox.read_output("tool", "bin/tool.cfg").as_deref(), Some("mode=1"));
+}
+
+#[test]
+fn changed_volume_is_rejected() {
+    let (_server, sandbox, repo) = signed_release("pack-volume");
+    fs::write(repo.join("tool--n1.globby"), common::tar_gz(&[("readme.txt", "tampered")])).unwrap();
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_eq!(result.status, Some(5), "{}", result.output);
+    assert!(result.output.contains("Checksum mismatch"), "{}", result.output);
+    assert!(sandbox.installed_version("tool").is_none());
+}
+
+#[test]
+fn changed_signature_is_rejected() {
+    let (_server, sandbox, repo) = signed_release("pack-signature");
+    let signature = fs::read_to_string(repo.join("filelist.txt.sig")).unwrap();
+    let flipped = if signature.starts_with('0') { "1" } else { "0" };
+    fs::write(repo.join("filelist.txt.sig"), format!("{}{}", flipped, &signature[1..])).unwrap();
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_eq!(result.status, Some(5), "{}", result.output);
+    assert!(result.output.contains("Signature check failed"), "{}", result.output);
+    assert!(sandbox.installed_version("tool").is_none());
+}
+
+#[test]
+fn signed_filelist_of_another_version_is_rejected() {
+    let (_server, sandbox, repo) = signed_release("pack-replay");
+    //=-- An older release's signed filelist and volumes served under a newer version.txt
+    fs::write(repo.join("version.txt"), V2).unwrap();
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_eq!(result.status, Some(5), "{}", result.output);
+    assert!(result.output.contains(&format!("is for version {}, not {}", V1, V2)), "{}", result.output);
+    assert!(sandbox.installed_version("tool").is_none());
+}
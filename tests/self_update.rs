@@ -0,0 +1,77 @@
+//=-- The startup version check only self-updates into a release that is newer than the running loader
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use common::{loader_exe_name, package_config, RepoServer, Route, Sandbox, LOADER_VERSION, V1};
+
+#[test]
+fn stale_release_is_not_installed_or_restarted() {
+    let server = RepoServer::start();
+    let sandbox = Sandbox::new("self-update-stale");
+    let release_url = server.url("/release/");
+    sandbox.write_config(&server, &format!("release_url = \"{}\"", release_url), &package_config(&server, "tool", ""));
+    //=-- version_url already announces a newer loader, but the release folder still holds the running one
+    server.set_text("/loader/version.txt", "2024-06-01--1");
+    server.set_text("/release/version.txt", LOADER_VERSION);
+    server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+
+    let result = sandbox.run(&["install", "tool"], &["C"]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert!(result.output.contains(&format!("release_url still offers version {}", LOADER_VERSION)), "{}", result.output);
+    assert!(!result.output.contains("Restarting"), "{}", result.output);
+    assert_eq!(server.hits("/release/checksums.txt"), 0);
+    assert_eq!(sandbox.installed_version("tool").as_deref(), Some(V1));
+}
+
+const RELEASE: &str = "2024-06-01--1";
+
+//=-- Serves the loader itself as a newer release and runs `exe` (with its version.txt) until it has updated and restarted
+fn update_and_restart(sandbox: &Sandbox, exe: &Path) {
+    let server = RepoServer::start();
+    let release_url = server.url("/release/");
+    sandbox.write_config(&server, &format!("release_url = \"{}\"", release_url), &package_config(&server, "tool", ""));
+    server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+
+    let loader = fs::read(env!("CARGO_BIN_EXE_wb-toolsloader")).unwrap();
+    let exe_name = loader_exe_name();
+    server.set_text("/loader/version.txt", RELEASE);
+    server.set_text("/release/version.txt", RELEASE);
+    server.set_text("/release/checksums.txt", &format!("{}  {}\n", common::sha256(&loader), exe_name));
+    server.set(&format!("/release/{}", exe_name), Route::Body(loader));
+
+    let result = sandbox.run_command(sandbox.command_for(exe, &["install", "tool"]), &[]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert!(result.output.contains(&format!("updating to version: {}", RELEASE)), "{}", result.output);
+    assert_eq!(result.output.matches("Restarting WarpBits Tools Loader").count(), 1, "{}", result.output);
+    assert!(result.output.contains(&format!("up to date, running version: {}", RELEASE)), "{}", result.output);
+    assert_eq!(fs::read_to_string(exe.with_file_name("version.txt")).unwrap(), RELEASE);
+    assert_eq!(server.hits(&format!("/release/{}", exe_name)), 1);
+    assert_eq!(sandbox.installed_version("tool").as_deref(), Some(V1));
+    assert!(!sandbox.dir.join("tmp").join("wb-toolsloader").exists());
+}
+
+#[test]
+fn newer_release_is_installed_and_restarted_into() {
+    //=-- The sandbox runs its own link to the loader, so replacing it never touches the one other tests run
+    let sandbox = Sandbox::new("self-update-newer");
+    update_and_restart(&sandbox, &sandbox.exe());
+}
+
+#[test]
+fn update_records_the_version_next_to_the_exe_when_the_config_is_elsewhere() {
+    let sandbox = Sandbox::new("self-update-elsewhere");
+    let bin = sandbox.dir.join("bin");
+    fs::create_dir_all(&bin).unwrap();
+    let exe = bin.join(loader_exe_name());
+    fs::copy(sandbox.exe(), &exe).unwrap();
+    fs::write(bin.join("version.txt"), LOADER_VERSION).unwrap();
+
+    update_and_restart(&sandbox, &exe);
+
+    //=-- The version.txt next to Config.toml is not the loader's and stays as it was
+    assert_eq!(fs::read_to_string(sandbox.dir.join("version.txt")).unwrap(), LOADER_VERSION);
+}
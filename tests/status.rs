@@ -0,0 +1,132 @@
+//=-- `status` compares installed and available versions without installing anything; `upgrade` acts on it
+mod common;
+
+use common::{package_config, setup, RepoServer, Sandbox, V1, V2};
+use serde_json::Value;
+
+fn state_of(document: &Value, id: &str) -> String {
+    document["packages"].as_array().unwrap().iter()
+        .find(|package| package["id"] == id)
+        .map(|package| package["state"].as_str().unwrap().to_string())
+        .unwrap()
+}
+
+#[test]
+fn status_reports_every_state() {
+    let server = RepoServer::start();
+    let sandbox = Sandbox::new("status");
+    let packages: Vec<String> = ["current", "old", "ahead", "fresh", "gone"].iter()
+        .map(|id| package_config(&server, id, ""))
+        .collect();
+    sandbox.write_config(&server, "", &packages.join("\n"));
+    for id in ["current", "old", "gone"] {
+        server.publish(id, V1, &[("readme.txt", id)], 1);
+    }
+    server.publish("ahead", V2, &[("readme.txt", "ahead")], 1);
+    for id in ["current", "old", "ahead", "gone"] {
+        sandbox.run(&["install", id], &[]);
+    }
+    server.publish("old", V2, &[("readme.txt", "old")], 1);
+    server.publish("ahead", V1, &[("readme.txt", "ahead")], 1);
+    server.publish("fresh", V1, &[("readme.txt", "fresh")], 1);
+    server.remove("/gone/version.txt");
+
+    let result = sandbox.run(&["status", "--json"], &[]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    let document: Value = serde_json::from_str(&result.output).unwrap();
+    assert_eq!(state_of(&document, "current"), "up_to_date");
+    assert_eq!(state_of(&document, "old"), "outdated");
+    assert_eq!(state_of(&document, "ahead"), "newer_locally");
+    assert_eq!(state_of(&document, "fresh"), "not_installed");
+    assert_eq!(state_of(&document, "gone"), "unreachable");
+    //=-- Nothing is downloaded or changed
+    assert_eq!(server.hits("/old/filelist.txt"), 1);
+    assert_eq!(sandbox.installed_version("old").as_deref(), Some(V1));
+}
+
+#[test]
+fn status_text_shows_installed_and_available() {
+    let (server, sandbox) = setup("status-text", &["tool"], "");
+    server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+    sandbox.run(&["install", "tool"], &[]);
+    server.publish("tool", V2, &[("readme.txt", "second release")], 1);
+
+    let result = sandbox.run(&["status"], &[]);
+
+    let line = result.output.lines().find(|line| line.starts_with("Package tool")).unwrap_or_default();
+    assert!(line.contains(&format!("{} (stable)", V1)), "{}", result.output);
+    assert!(line.contains(&format!("{} (stable)", V2)), "{}", result.output);
+    assert!(line.ends_with("outdated"), "{}", result.output);
+}
+
+#[test]
+fn upgrade_installs_only_outdated_and_missing_packages() {
+    let server = RepoServer::start();
+    let sandbox = Sandbox::new("upgrade");
+    let packages: Vec<String> = ["current", "old", "fresh"].iter()
+        .map(|id| package_config(&server, id, ""))
+        .collect();
+    sandbox.write_config(&server, "", &packages.join("\n"));
+    for id in ["current", "old"] {
+        server.publish(id, V1, &[("readme.txt", id)], 1);
+        sandbox.run(&["install", id], &[]);
+    }
+    server.publish("old", V2, &[("readme.txt", "old v2")], 1);
+    server.publish("fresh", V1, &[("readme.txt", "fresh")], 1);
+
+    //=-- No answers: an unchanged package must not prompt "Reload anyway?"
+    let result = sandbox.run(&["upgrade"], &[]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert!(!result.output.contains("Reload anyway"), "{}", result.output);
+    assert!(result.output.contains(&format!("old: updated {} -> {}", V1, V2)), "{}", result.output);
+    assert!(result.output.contains(&format!("fresh: installed {}", V1)), "{}", result.output);
+    assert!(result.output.contains("1 package(s) unchanged"), "{}", result.output);
+    assert_eq!(sandbox.read_output("old", "readme.txt").as_deref(), Some("old v2"));
+    assert_eq!(sandbox.installed_version("fresh").as_deref(), Some(V1));
+    assert_eq!(server.hits("/current/filelist.txt"), 1);
+}
+
+#[test]
+fn upgrade_fetches_the_version_once() {
+    let (server, sandbox) = setup("upgrade-single-fetch", &["tool"], "");
+    server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+
+    let result = sandbox.run(&["upgrade"], &[]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert_eq!(sandbox.installed_version("tool").as_deref(), Some(V1));
+    assert_eq!(server.hits("/tool/version.txt"), 1);
+    assert_eq!(server.hits("/tool/filelist.txt"), 1);
+}
+
+#[test]
+fn upgrade_fetches_the_manifest_once() {
+    let server = RepoServer::start();
+    let sandbox = Sandbox::new("upgrade-single-manifest");
+    let extra = format!("manifest_url = \"{}\"", server.url("/tool/manifest.json"));
+    sandbox.write_config(&server, "", &package_config(&server, "tool", &extra));
+    let volumes = server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+    server.set_text("/tool/manifest.json", &format!("{{\"version\": \"{}\", \"volumes\": [{{\"name\": \"{}\"}}]}}", V1, volumes[0]));
+
+    let result = sandbox.run(&["upgrade"], &[]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert_eq!(sandbox.installed_version("tool").as_deref(), Some(V1));
+    assert_eq!(server.hits("/tool/manifest.json"), 1);
+}
+
+#[test]
+fn status_does_not_need_the_loader_version_url() {
+    let (server, sandbox) = setup("status-offline-loader", &["tool", "gone"], "");
+    server.publish("tool", V1, &[("readme.txt", "first release")], 1);
+    server.remove("/loader/version.txt");
+
+    let result = sandbox.run(&["status", "--json"], &[]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    let document: Value = serde_json::from_str(&result.output).unwrap();
+    assert_eq!(state_of(&document, "tool"), "not_installed");
+    assert_eq!(state_of(&document, "gone"), "unreachable");
+}
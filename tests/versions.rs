@@ -0,0 +1,121 @@
+//=-- Choosing which version to install: --version, --as-of and pinned_version
+mod common;
+
+use common::{package_config, versioned_package_config, RepoServer, Sandbox, V1, V2};
+
+const V3: &str = "2024-04-01--1";
+
+//=-- A versioned repository holding V1, V2 and V3 (the latest)
+fn versioned(name: &str, extra: &str) -> (RepoServer, Sandbox) {
+    let server = RepoServer::start();
+    let sandbox = Sandbox::new(name);
+    sandbox.write_config(&server, "", &versioned_package_config(&server, "tool", extra));
+    for version in [V1, V2, V3] {
+        server.publish_version("tool", version, &[("readme.txt", version)]);
+    }
+    (server, sandbox)
+}
+
+#[test]
+fn exact_version_is_installed() {
+    let (_server, sandbox) = versioned("exact", "");
+
+    let result = sandbox.run(&["install", "tool", "--version", V2], &[]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert_eq!(sandbox.installed_version("tool").as_deref(), Some(V2));
+    assert_eq!(sandbox.read_output("tool", "readme.txt").as_deref(), Some(V2));
+}
+
+#[test]
+fn unpublished_version_is_refused() {
+    let (server, sandbox) = versioned("unpublished", "");
+
+    let result = sandbox.run(&["install", "tool", "--version", "2024-02-15--1"], &[]);
+
+    assert_eq!(result.status, Some(3), "{}", result.output);
+    assert!(result.output.contains("Version 2024-02-15--1 of Package tool is not published"), "{}", result.output);
+    assert_eq!(server.hits("/tool/2024-02-15--1/filelist.txt"), 0);
+    assert!(sandbox.installed_version("tool").is_none());
+}
+
+#[test]
+fn as_of_picks_the_latest_version_on_or_before_the_date() {
+    let (_server, sandbox) = versioned("as-of", "");
+
+    let result = sandbox.run(&["install", "tool", "--as-of", "2024-03-15"], &[]);
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert_eq!(sandbox.installed_version("tool").as_deref(), Some(V2));
+
+    //=-- The date itself counts
+    let result = sandbox.run(&["install", "tool", "--as-of", "2024-04-01"], &[]);
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert_eq!(sandbox.installed_version("tool").as_deref(), Some(V3));
+}
+
+#[test]
+fn as_of_before_the_first_version_fails() {
+    let (_server, sandbox) = versioned("as-of-early", "");
+
+    let result = sandbox.run(&["install", "tool", "--as-of", "2024-01-15"], &[]);
+
+    assert_eq!(result.status, Some(3), "{}", result.output);
+    assert!(result.output.contains("has no version published on or before 2024-01-15"), "{}", result.output);
+}
+
+#[test]
+fn as_of_needs_a_real_date() {
+    let (_server, sandbox) = versioned("as-of-invalid", "");
+
+    let result = sandbox.run(&["install", "tool", "--as-of", "2024-02-30"], &[]);
+
+    assert_ne!(result.status, Some(0), "{}", result.output);
+    assert!(sandbox.installed_version("tool").is_none());
+}
+
+#[test]
+fn non_versioned_layout_only_serves_the_latest_version() {
+    let server = RepoServer::start();
+    let sandbox = Sandbox::new("non-versioned");
+    let versions_url = format!("versions_url = \"{}\"", server.url("/tool/versions.txt"));
+    sandbox.write_config(&server, "", &package_config(&server, "tool", &versions_url));
+    server.set_text("/tool/versions.txt", &format!("{}\n{}\n", V1, V2));
+    server.publish("tool", V2, &[("readme.txt", "second release")], 1);
+
+    let old = sandbox.run(&["install", "tool", "--version", V1], &[]);
+    assert_eq!(old.status, Some(3), "{}", old.output);
+    assert!(old.output.contains(&format!("does not use a versioned repository layout and only serves its latest version ({})", V2)), "{}", old.output);
+    assert!(sandbox.installed_version("tool").is_none());
+
+    let latest = sandbox.run(&["install", "tool", "--version", V2], &[]);
+    assert_eq!(latest.status, Some(0), "{}", latest.output);
+    assert_eq!(sandbox.installed_version("tool").as_deref(), Some(V2));
+}
+
+#[test]
+fn pinned_version_is_installed_instead_of_the_latest() {
+    let (_server, sandbox) = versioned("pinned", &format!("pinned_version = \"{}\"", V1));
+
+    let result = sandbox.run(&["install", "tool"], &[]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert_eq!(sandbox.installed_version("tool").as_deref(), Some(V1));
+}
+
+#[test]
+fn pin_survives_upgrade_and_install_all() {
+    let (server, sandbox) = versioned("pinned-upgrade", &format!("pinned_version = \"{}\"", V2));
+    sandbox.run(&["install", "tool"], &[]);
+    assert_eq!(sandbox.installed_version("tool").as_deref(), Some(V2));
+
+    let upgrade = sandbox.run(&["upgrade"], &[]);
+    assert_eq!(upgrade.status, Some(0), "{}", upgrade.output);
+    assert_eq!(sandbox.installed_version("tool").as_deref(), Some(V2));
+    assert_eq!(server.hits(&format!("/tool/{}/tool--n1.globby", V3)), 0);
+
+    //=-- "A" installs every package from the line menu; "N" declines reloading the same version
+    let all = sandbox.run(&["--no-tui"], &["A", "N", ""]);
+    assert_eq!(all.status, Some(0), "{}", all.output);
+    assert_eq!(sandbox.installed_version("tool").as_deref(), Some(V2));
+    assert_eq!(server.hits(&format!("/tool/{}/tool--n1.globby", V3)), 0);
+}
@@ -0,0 +1,85 @@
+//=-- `watch` applies updates per package update_policy without prompting, and stops cleanly on signals
+mod common;
+
+use common::{package_config, setup, RepoServer, Sandbox, V1, V2};
+
+#[test]
+fn watch_once_follows_update_policies() {
+    let server = RepoServer::start();
+    let sandbox = Sandbox::new("watch-policies");
+    let packages = [
+        package_config(&server, "auto", ""),
+        package_config(&server, "missing", ""),
+        package_config(&server, "install", "update_policy = \"install\""),
+        package_config(&server, "notify", "update_policy = \"notify\""),
+        package_config(&server, "off", "update_policy = \"off\""),
+    ];
+    sandbox.write_config(&server, "", &packages.join("\n"));
+    for id in ["auto", "notify"] {
+        server.publish(id, V1, &[("readme.txt", "v1")], 1);
+        sandbox.run(&["install", id], &[]);
+        server.publish(id, V2, &[("readme.txt", "v2")], 1);
+    }
+    for id in ["missing", "install", "off"] {
+        server.publish(id, V1, &[("readme.txt", "v1")], 1);
+    }
+
+    //=-- No answers: nothing may prompt
+    let result = sandbox.run(&["watch", "--once"], &[]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert_eq!(sandbox.installed_version("auto").as_deref(), Some(V2));
+    assert_eq!(sandbox.installed_version("install").as_deref(), Some(V1));
+    assert!(sandbox.installed_version("missing").is_none());
+    assert_eq!(sandbox.installed_version("notify").as_deref(), Some(V1));
+    assert!(result.output.contains(&format!("update available, {} -> {}", V1, V2)), "{}", result.output);
+    assert_eq!(server.hits("/off/version.txt"), 0);
+    assert!(result.output.contains("Check finished: 2 updated, 0 failed, 1 waiting"), "{}", result.output);
+}
+
+#[test]
+fn watch_starts_when_the_loader_version_url_is_unreachable() {
+    let (server, sandbox) = setup("watch-offline-loader", &["tool"], "");
+    server.publish("tool", V1, &[("readme.txt", "v1")], 1);
+    sandbox.run(&["install", "tool"], &[]);
+    server.publish("tool", V2, &[("readme.txt", "v2")], 1);
+    server.remove("/loader/version.txt");
+
+    let result = sandbox.run(&["watch", "--once"], &[]);
+
+    assert_eq!(result.status, Some(0), "{}", result.output);
+    assert!(result.output.contains("Could not check the loader's version"), "{}", result.output);
+    assert_eq!(sandbox.installed_version("tool").as_deref(), Some(V2));
+}
+
+#[cfg(unix)]
+#[test]
+fn watch_stops_on_sigterm() {
+    use std::time::{Duration, Instant};
+
+    let (server, sandbox) = setup("watch-signal", &["tool"], "");
+    server.publish("tool", V1, &[("readme.txt", "v1")], 1);
+
+    let mut child = sandbox.command(&["watch"]).spawn().expect("start loader");
+    let output = || std::fs::read_to_string(sandbox.dir.join("stdout.txt")).unwrap_or_default();
+    let deadline = Instant::now() + Duration::from_secs(30);
+    while !output().contains("Next check in") {
+        assert!(Instant::now() < deadline, "watch did not finish its first check:\n{}", output());
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    std::process::Command::new("kill").arg("-TERM").arg(child.id().to_string()).status().unwrap();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+        if Instant::now() > deadline {
+            let _ = child.kill();
+            panic!("watch did not stop:\n{}", output());
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    };
+    assert_eq!(status.code(), Some(0), "{}", output());
+    assert!(output().contains("Watch stopped"), "{}", output());
+}